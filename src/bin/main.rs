@@ -1,89 +1,605 @@
 use ai_tuber::{
-    config::Config,
+    config::{ChatSourceKind, Config},
     error::{Error, Result},
     model::{
-        conversation::{Message, Role},
+        chat_event::{ChatEvent, Money},
+        conversation::{Conversation, Message, Role},
         emotion::Emotion,
+        reply::ReplyFormat,
     },
-    service::{GeminiClient, audio, avatar_osc, prompt, tts_voicevox, youtube_chat},
+    service::{
+        LlmBackend, audio, avatar_osc, chat_source,
+        chat_source::Lifecycle,
+        commands::{self, Command, Controls, Invocation, Permission},
+        knowledge::KnowledgeBase,
+        llm,
+        moderation::Moderator,
+        persona::{PersonaRegistry, Voice},
+        postprocess::Session,
+        prompt::{self, PromptContext},
+        scheduler::{Comment, CommentQueue},
+        segmenter::{self, Segment, Segmenter},
+        template::{self, Template, TemplateVars},
+        tokens::TokenCounter,
+        tools::{ChatLog, ToolRegistry},
+        tts_voicevox,
+        viewer_memory::ViewerMemory,
+        youtube_api::YouTubeApi,
+    },
+};
+use std::{collections::HashSet, sync::Arc, time::Instant};
+use tokio::{
+    io::{AsyncBufReadExt, BufReader},
+    sync::mpsc,
 };
-use regex::Regex;
 use tokio_stream::StreamExt;
+use tracing::{debug, info, warn};
 
-async fn parse_and_play(rep: &str, speaker: u16, tag_re: &Regex) -> Result<()> {
-    let segments = tag_re
-        .split(rep) // テキスト部分を列挙
-        .zip(
-            // 直前のタグとペアに
-            std::iter::once("neutral") // 先頭の疑似タグ
-                .chain(
-                    tag_re
-                        .find_iter(rep)
-                        .map(|m| &rep[m.start() + 1..m.end() - 1]),
-                ),
-        );
-
-    for (text, tag) in segments.filter(|(t, _)| !t.trim().is_empty()) {
-        let emo = tag.parse().unwrap_or(Emotion::Neutral);
-        avatar_osc::set(emo)?;
-        let wav = tts_voicevox::synth(text, speaker).await?;
-        audio::play(&wav)?;
+/// 1 文を表情付きで読み上げる。合成・再生に失敗した文はログだけ残して飛ばす。
+async fn speak(seg: Segment, voice: &Voice, controls: &Controls) -> Result<()> {
+    if controls.is_muted() {
+        debug!(text = %seg.text, "muted; not speaking");
+        return Ok(());
+    }
+    let text = seg.text.clone();
+    if let Err(e) = speak_now(seg, voice).await {
+        warn!(error = %e, %text, "failed to speak; skipping sentence");
     }
     Ok(())
 }
 
+async fn speak_now(seg: Segment, voice: &Voice) -> Result<()> {
+    let (blend, val) = voice.clip(seg.emotion);
+    avatar_osc::set_blend(blend, val)?;
+    let wav = tts_voicevox::synth(&seg.text, voice.speaker(seg.emotion)).await?;
+    // 再生はブロッキングなので、生成側のストリームを止めないよう別スレッドへ
+    tokio::task::spawn_blocking(move || audio::play(&wav))
+        .await
+        .map_err(|e| Error::External(e.into()))?
+}
+
+/// 文を順に読み上げ、履歴用の `[emotion]text` 形式の全文を返す。
+async fn play_segments(
+    segments: Vec<Segment>,
+    voice: &Voice,
+    controls: &Controls,
+    post: &mut Session<'_>,
+) -> Result<String> {
+    let mut transcript = String::new();
+    for seg in segments {
+        let Some(seg) = post.push(seg) else { continue };
+        transcript.push_str(&seg.to_tagged());
+        speak(seg, voice, controls).await?;
+    }
+    Ok(transcript)
+}
+
+/// 感情タグ付きのセリフ（フォールバック・定型文）を読み上げる。
+async fn parse_and_play(
+    rep: &str,
+    voice: &Voice,
+    controls: &Controls,
+    post: &mut Session<'_>,
+) -> Result<String> {
+    play_segments(segmenter::split_all(rep), voice, controls, post).await
+}
+
+/// 返答をストリーミングで受け取り、完結した文から順に読み上げる。
+///
+/// 読み上げと生成を並行させ、全文が揃う前に最初の文を再生し始める。
+async fn stream_and_play(
+    llm: &dyn LlmBackend,
+    req: &[Message<'_>],
+    voice: &Voice,
+    controls: &Controls,
+    format: ReplyFormat,
+    post: &mut Session<'_>,
+) -> Result<String> {
+    let (tx, mut rx) = mpsc::unbounded_channel::<Segment>();
+
+    let produce = async move {
+        let mut segmenter = Segmenter::new(format);
+        let stream = llm.chat_stream(req);
+        tokio::pin!(stream);
+        while let Some(chunk) = stream.next().await {
+            for seg in segmenter.push(&chunk?)? {
+                let _ = tx.send(seg);
+            }
+        }
+        for seg in segmenter.finish()? {
+            let _ = tx.send(seg);
+        }
+        Ok::<_, Error>(())
+    };
+
+    let consume = async {
+        let mut transcript = String::new();
+        while let Some(seg) = rx.recv().await {
+            let Some(seg) = post.push(seg) else { continue };
+            transcript.push_str(&seg.to_tagged());
+            speak(seg, voice, controls).await?;
+        }
+        Ok(transcript)
+    };
+
+    let ((), transcript) = tokio::try_join!(produce, consume)?;
+    Ok(transcript)
+}
+
+/// 設定に応じてストリーミング／一括のどちらかで返答し、全文を返す。
+///
+/// ブロック・空応答の場合は `cfg.fallback_reply`、LLM が使えない場合は
+/// `cfg.canned_replies` のいずれかを話してそれを返答とする。
+/// 各文は `cfg.postprocess` を通してから読み上げ、返す全文も後処理後のものになる。
+/// `!skip` されたら残りを話さずに空文字を返す。
+async fn reply(
+    llm: &dyn LlmBackend,
+    req: &[Message<'_>],
+    cfg: &Config,
+    voice: &Voice,
+    controls: &Controls,
+) -> Result<String> {
+    tokio::select! {
+        res = reply_and_play(llm, req, cfg, voice, controls) => res,
+        _ = controls.skipped() => {
+            info!("reply skipped");
+            Ok(String::new())
+        }
+    }
+}
+
+async fn reply_and_play(
+    llm: &dyn LlmBackend,
+    req: &[Message<'_>],
+    cfg: &Config,
+    voice: &Voice,
+    controls: &Controls,
+) -> Result<String> {
+    let mut post = cfg.postprocess.session();
+    let res = if cfg.stream_reply {
+        stream_and_play(llm, req, voice, controls, cfg.reply_format, &mut post).await
+    } else {
+        match llm
+            .chat(req)
+            .await
+            .and_then(|rep| segmenter::split(&rep, cfg.reply_format))
+        {
+            Ok(segments) => play_segments(segments, voice, controls, &mut post).await,
+            Err(e) => Err(e),
+        }
+    };
+
+    let line = match res {
+        Err(e) if e.is_unusable_reply() => {
+            warn!(error = %e, "unusable LLM reply; speaking fallback line");
+            cfg.fallback_reply.clone()
+        }
+        // API 停止中は配信を止めず、つなぎのセリフで場を持たせる
+        Err(Error::LlmUnavailable(reason)) => {
+            warn!(%reason, "LLM unavailable; speaking canned reply");
+            if cfg.canned_replies.is_empty() {
+                return Ok(String::new());
+            }
+            cfg.canned_replies[rand::random_range(0..cfg.canned_replies.len())].clone()
+        }
+        other => return other,
+    };
+    // つなぎのセリフは打ち切られないよう、新しいセッションで処理する
+    parse_and_play(&line, voice, controls, &mut cfg.postprocess.session()).await
+}
+
+/// 返答をチャットにも投稿する（感情タグは外す）。失敗してもログのみで配信は止めない。
+fn post_to_chat(poster: Option<&YouTubeApi>, text: &str) {
+    let Some(poster) = poster.cloned() else {
+        return;
+    };
+    let text: String = segmenter::split_all(text)
+        .into_iter()
+        .map(|s| s.text)
+        .collect();
+    tokio::spawn(async move {
+        if let Err(e) = poster.post(&text).await {
+            warn!(error = %e, "failed to post to YouTube chat");
+        }
+    });
+}
+
+/// 返答を話し、最後の感情を `mood` に、全文を履歴に残して、チャットにも投稿する。
+///
+/// 返答に失敗したり `!skip` されたりして何も話さなければ、どれも変えない。
+#[allow(clippy::too_many_arguments)]
+async fn deliver_reply(
+    llm: &dyn LlmBackend,
+    req: &[Message<'_>],
+    cfg: &Config,
+    voice: &Voice,
+    controls: &Controls,
+    poster: Option<&YouTubeApi>,
+    conv: &mut Conversation,
+    mood: &mut Emotion,
+) {
+    let rep = match reply(llm, req, cfg, voice, controls).await {
+        Ok(rep) => rep,
+        Err(e) => {
+            warn!(error = %e, "reply failed");
+            return;
+        }
+    };
+    if rep.is_empty() {
+        return;
+    }
+    *mood = last_emotion(&rep).unwrap_or(*mood);
+    post_to_chat(poster, &rep);
+    conv.push(Message::new(Role::Bot, rep).with_emotion(*mood));
+}
+
+/// 古い履歴をトークン予算（とターン数の上限）に収まるよう要約待ちへ移す。
+///
+/// `ctx` は返答に使うものと同じにし、実際に送るプロンプトで数える。
+async fn trim_history(
+    conv: &mut Conversation,
+    counter: &mut TokenCounter,
+    llm: &dyn LlmBackend,
+    cfg: &Config,
+    system_prompt: &Template,
+    ctx: &PromptContext<'_>,
+) {
+    let req = prompt::build(
+        system_prompt,
+        &conv.history,
+        cfg.max_history,
+        cfg.reply_format,
+        ctx,
+    );
+    let start = cfg
+        .token_budget
+        .history_start(counter, llm, &req, &conv.history)
+        .await;
+    conv.drop_before(start);
+}
+
+/// 履歴から外れたターンを要約に畳み込む。要約が無効なら捨てる。
+async fn summarize(conv: &mut Conversation, llm: &dyn LlmBackend, cfg: &Config) {
+    match &cfg.summarizer {
+        Some(s) => {
+            s.update(llm, conv).await;
+        }
+        None => conv.dropped.clear(),
+    }
+}
+
+/// テンプレート変数に直近の状態を詰める。
+fn template_vars(
+    cfg: &Config,
+    conv: &Conversation,
+    started_at: Instant,
+    viewer_name: &str,
+    mood: Emotion,
+) -> TemplateVars {
+    let mins = started_at.elapsed().as_secs() / 60;
+    let recent: Vec<&str> = conv
+        .history
+        .iter()
+        .rev()
+        .filter(|m| m.role == Role::User)
+        .take(3)
+        .map(|m| m.text.as_ref())
+        .collect();
+    TemplateVars {
+        viewer_name: template::viewer_text(viewer_name, template::MAX_VIEWER_NAME_CHARS),
+        now: chrono::Local::now().format("%Y-%m-%d %H:%M").to_string(),
+        stream_uptime: match mins / 60 {
+            0 => format!("{mins}分"),
+            h => format!("{h}時間{}分", mins % 60),
+        },
+        stream_title: cfg.stream_title.clone(),
+        mood: mood.as_str().to_owned(),
+        recent_topics: template::viewer_text(
+            &recent.into_iter().rev().collect::<Vec<_>>().join(" / "),
+            template::MAX_RECENT_TOPICS_CHARS,
+        ),
+    }
+}
+
+/// 返答の最後の文の感情（次の `{{mood}}`）
+fn last_emotion(rep: &str) -> Option<Emotion> {
+    segmenter::split_all(rep).last().map(|s| s.emotion)
+}
+
+/// メインループで実行するコマンド（実行者・権限・コマンド）
+type ChatCommand = (String, Permission, Invocation);
+
+/// 再生中にも効くコマンドはその場で実行し、それ以外はメインループへ渡す。
+fn dispatch(
+    author: String,
+    permission: Permission,
+    inv: Invocation,
+    controls: &Controls,
+    tx: &mpsc::Sender<ChatCommand>,
+) {
+    match inv.command {
+        Command::Skip => {
+            info!(%author, "skip requested");
+            controls.skip();
+        }
+        Command::Mute => {
+            controls.toggle_mute();
+        }
+        _ => {
+            if tx.try_send((author, permission, inv)).is_err() {
+                warn!("command queue full; dropped");
+            }
+        }
+    }
+}
+
+/// 標準入力から運営用のコマンドを受け取る（`persona <name>` など。`!` は省略可）。
+///
+/// 運営はすべてのコマンドを権限・クールダウンなしで使える。
+fn spawn_console(controls: Controls, tx: mpsc::Sender<ChatCommand>) {
+    tokio::spawn(async move {
+        let mut lines = BufReader::new(tokio::io::stdin()).lines();
+        while let Ok(Some(line)) = lines.next_line().await {
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            match Command::parse(line) {
+                Ok(command) => {
+                    let inv = Invocation {
+                        command,
+                        spoken: false,
+                    };
+                    dispatch("console".into(), Permission::Owner, inv, &controls, &tx);
+                }
+                Err(e) => warn!(command = %line, reason = %e, "console command rejected"),
+            }
+        }
+    });
+}
+
+fn context<'a>(summary: &'a str, viewer: Option<&'a str>) -> PromptContext<'a> {
+    PromptContext {
+        summary: Some(summary),
+        viewer,
+        ..PromptContext::default()
+    }
+}
+
 #[tokio::main]
 async fn main() -> Result<()> {
     tracing_subscriber::fmt()
         .with_env_filter("info,ai_tuber=debug")
         .init();
 
-    let cfg = Config::from_env()?;
-    let gemini = GeminiClient::new(&cfg.gemini_api_key, &cfg.gemini_model)?;
-    let mut history: Vec<Message> = Vec::new();
-    let tag_re = Regex::new(r"(?i)\[(neutral|happy|sad|angry|relaxed|surprised)\]\s*")
-        .map_err(|e| Error::External(e.into()))?;
-    let (tx, mut rx) = tokio::sync::mpsc::channel::<String>(32);
+    let base_cfg = Config::from_env()?;
+    let mut personas = PersonaRegistry::from_config(&base_cfg)?;
+    // 現在のペルソナを反映した設定（ペルソナを切り替えたら作り直す）
+    let mut cfg = personas.active().apply(&base_cfg);
+    let chat_log = ChatLog::new();
+    let viewers = match &cfg.viewer_memory_file {
+        Some(path) => ViewerMemory::open(path)?,
+        None => ViewerMemory::in_memory(),
+    };
+    let started_at = Instant::now();
+    let knowledge = match &cfg.knowledge_dir {
+        Some(dir) => KnowledgeBase::load(dir)?,
+        None => KnowledgeBase::default(),
+    };
+    let tools = cfg.tools_enabled.then(|| {
+        Arc::new(ToolRegistry::builtin(
+            started_at,
+            chat_log.clone(),
+            viewers.clone(),
+        ))
+    });
+    let mut llm = llm::from_config(&cfg, tools.clone())?;
+    let mut conv = Conversation::new();
+    let mut mood = Emotion::Neutral;
+    let mut token_counter = TokenCounter::new();
+    let mut moderator = Moderator::new(cfg.moderation_rules.clone())?;
+    if let Some(path) = &cfg.moderation_log {
+        moderator = moderator.with_audit_log(path);
+    }
+    let queue = CommentQueue::new(cfg.scheduler.clone());
+    let controls = Controls::default();
+    let (cmd_tx, mut cmd_rx) = mpsc::channel::<ChatCommand>(16);
+    // 読み取りと返答の投稿で同じクライアントを使う（自分の投稿に答えないため）
+    let youtube_api = cfg.youtube_api.clone().map(YouTubeApi::new).transpose()?;
+    let sources = chat_source::from_config(&cfg, youtube_api.as_ref())?;
+    let poster = match &cfg.youtube_api {
+        Some(api) if api.post_replies => youtube_api.clone(),
+        _ => None,
+    };
+    let mut lifecycle = chat_source::lifecycles(&sources);
+    let mut chat = chat_source::merge(sources);
+    let mut opened = HashSet::new();
     tokio::spawn({
-        let url = cfg.youtube_live_url.clone();
+        let chat_log = chat_log.clone();
+        let viewers = viewers.clone();
+        let queue = queue.clone();
+        let controls = controls.clone();
+        let cmd_tx = cmd_tx.clone();
+        let mut router = cfg.commands.clone();
         async move {
-            if let Ok(stream) = youtube_chat::subscribe(&url).await {
-                tokio::pin!(stream);
-                while let Some((_, msg)) = stream.next().await {
-                    if !msg.starts_with('!') {
-                        let _ = tx.try_send(msg);
+            while let Some(event) = chat.next().await {
+                let now = Instant::now();
+                if moderator.check_event(&event, now).is_some() {
+                    continue;
+                }
+                let msg = event.message();
+                chat_log.record(&msg.author.name, &msg.text);
+                let record = viewers.record(&msg.author);
+                let permission = Permission::from_badges(&msg.badges);
+                // コマンドには会話として答えない
+                if let ChatEvent::Message(msg) = &event
+                    && let Some(routed) = router.route(&msg.text, permission, now)
+                {
+                    match routed {
+                        Ok(inv) => {
+                            dispatch(msg.author.name.clone(), permission, inv, &controls, &cmd_tx)
+                        }
+                        Err(e) => {
+                            info!(author = %msg.author, command = %msg.text, reason = %e, "command rejected")
+                        }
                     }
+                    continue;
                 }
+                match &event {
+                    ChatEvent::Message(_) => {}
+                    // お礼・歓迎は優先して返す（注記は prompt::event_text）
+                    other => info!(
+                        kind = other.kind(),
+                        author = %msg.author,
+                        amount = other.paid().map(|m| m.display.as_str()),
+                        "chat event"
+                    ),
+                }
+                let mut comment = Comment::new(msg.author.clone(), prompt::event_text(&event), now);
+                comment.timestamp = msg.timestamp;
+                comment.paid_amount = event.paid().map(Money::approx_jpy);
+                comment.membership = matches!(event, ChatEvent::Membership { .. });
+                comment.first_time = record.stream_count <= 1 && record.message_count == 1;
+                queue.push(comment);
             }
         }
     });
 
+    // stdin を入力元にしたときは、そちらの `!` コマンドが配信者として通る
+    if cfg.chat_sources.contains(&ChatSourceKind::Stdin) {
+        drop(cmd_tx);
+    } else {
+        spawn_console(controls.clone(), cmd_tx);
+    }
+    let mut voice = personas.active().voice.clone();
+
     let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(
         cfg.spontaneous_interval.as_secs(),
     ));
 
     loop {
         tokio::select! {
-            Some(user_msg) = rx.recv() => {
-                history.push(Message { role: Role::User, text: std::borrow::Cow::Owned(user_msg.clone()) });
+            batch = queue.next_batch(cfg.reply_batch_size, cfg.reply_batch_window) => {
+                let (author, message) = match batch.as_slice() {
+                    [c] => (
+                        c.author.name.clone(),
+                        Message::user(c.text.clone()).with_author(c.author.clone()).at(c.timestamp),
+                    ),
+                    batch => (
+                        batch.iter().map(|c| c.author.name.as_str()).collect::<Vec<_>>().join("、"),
+                        Message::user(prompt::batch_comments(batch.iter().map(|c| (c.author.name.as_str(), c.text.as_str()))))
+                            .at(batch[0].timestamp),
+                    ),
+                };
+                conv.push(message);
 
-                if history.len() > cfg.max_history * 2 {
-                    history.drain(0..history.len() - cfg.max_history * 2);
+                let persona = personas.active();
+                let viewer = batch
+                    .iter()
+                    .filter_map(|c| viewers.get(&c.author.key()))
+                    .map(|r| r.describe(cfg.regular_viewer_streams))
+                    .reduce(|a, b| a + "\n" + &b);
+                // まとめの見出しや注記で検索がぶれないよう、コメントの本文だけで引く
+                let query = batch.iter().map(|c| prompt::comment_body(&c.text)).collect::<Vec<_>>().join("\n");
+                let passages = knowledge.search(&query, cfg.knowledge_top_k);
+                let vars = template_vars(&cfg, &conv, started_at, &author, mood);
+                let summary = conv.summary.clone();
+                let ctx = PromptContext { knowledge: &passages, vars: Some(&vars), ..context(&summary, viewer.as_deref()) };
+                trim_history(&mut conv, &mut token_counter, llm.as_ref(), &cfg, &persona.system_prompt, &ctx).await;
+                // 返答を履歴に足すので、送る分は写しから組み立てる
+                let history = conv.history.clone();
+                let req = prompt::build(&persona.system_prompt, &history, cfg.max_history, cfg.reply_format, &ctx);
+                deliver_reply(llm.as_ref(), &req, &cfg, &voice, &controls, poster.as_ref(), &mut conv, &mut mood).await;
+                summarize(&mut conv, llm.as_ref(), &cfg).await;
+                if let Err(e) = viewers.flush() {
+                    warn!(error = %e, "failed to save viewer memory");
                 }
-
-                let req = prompt::build(&cfg.bot_system_prompt, &history, cfg.max_history);
-                let rep = gemini.ask(&req).await?;
-                history.push(Message { role: Role::Bot, text: std::borrow::Cow::Owned(rep.clone()) });
-
-                parse_and_play(&rep, cfg.voicevox_speaker, &tag_re).await?;
             },
 
             _ = interval.tick() => {
-                let req = prompt::build(&cfg.spontaneous_prompt, &[], 0);
-                let rep = gemini.ask(&req).await?;
-                history.push(Message { role: Role::Bot, text: std::borrow::Cow::Owned(rep.clone()) });
+                let persona = personas.active();
+                let vars = template_vars(&cfg, &conv, started_at, "", mood);
+                let ctx = PromptContext { vars: Some(&vars), topic: persona.pick_topic(), ..context(&conv.summary, None) };
+                let req = prompt::build_spontaneous_prompt(&persona.system_prompt, &persona.spontaneous_prompt, cfg.reply_format, &ctx);
+                deliver_reply(llm.as_ref(), &req, &cfg, &voice, &controls, poster.as_ref(), &mut conv, &mut mood).await;
+            },
+
+            // 配信の開始・終了にあいさつする
+            Some((source, state)) = lifecycle.next() => {
+                info!(%source, %state, "stream lifecycle");
+                let instruction = match state {
+                    // 再接続後の Live ではあいさつし直さない
+                    Lifecycle::Live if opened.insert(source) => &cfg.opening_prompt,
+                    Lifecycle::Ended => &cfg.closing_prompt,
+                    _ => continue,
+                };
+                let persona = personas.active();
+                let vars = template_vars(&cfg, &conv, started_at, "", mood);
+                let ctx = PromptContext { vars: Some(&vars), ..context(&conv.summary, None) };
+                let req = prompt::build_spontaneous_prompt(&persona.system_prompt, instruction, cfg.reply_format, &ctx);
+                deliver_reply(llm.as_ref(), &req, &cfg, &voice, &controls, poster.as_ref(), &mut conv, &mut mood).await;
+            },
 
-                parse_and_play(&rep, cfg.voicevox_speaker, &tag_re).await?;
+            Some((author, permission, inv)) = cmd_rx.recv() => {
+                let response = match &inv.command {
+                    Command::Help => Some(format!("[neutral]{}", cfg.commands.help(permission))),
+                    Command::Dice { sides } => {
+                        Some(format!("[happy]{author}さんのサイコロは {} だよ！", commands::roll(*sides)))
+                    }
+                    // 以降は感情ごとの話者を使わず、指定の声だけで話す
+                    Command::Voice { speaker } => match tts_voicevox::speaker_ids().await {
+                        Ok(ids) if ids.contains(speaker) => {
+                            voice.speaker = *speaker;
+                            voice.speakers.clear();
+                            info!(%author, speaker, "voice changed");
+                            None
+                        }
+                        Ok(_) => Some(format!("[sad]話者 {speaker} は見つからなかったよ")),
+                        Err(e) => {
+                            warn!(error = %e, speaker, "failed to list VOICEVOX speakers; voice unchanged");
+                            None
+                        }
+                    },
+                    // 履歴はそのままに、プロンプト・声・生成パラメータだけ切り替える
+                    Command::Persona { name } => {
+                        // LLM・設定・声をすべて作ってから入れ替える（失敗したら現在のペルソナのまま）
+                        let switched = personas.switch_with(name, |persona| {
+                            let next = persona.apply(&base_cfg);
+                            let backend = llm::from_config(&next, tools.clone())?;
+                            Ok((backend, next, persona.voice.clone()))
+                        });
+                        match switched {
+                            Ok((backend, next, next_voice)) => {
+                                llm = backend;
+                                cfg = next;
+                                voice = next_voice;
+                            }
+                            Err(e) => {
+                                warn!(error = %e, personas = ?personas.names().collect::<Vec<_>>(), "persona switch failed; keeping the current persona");
+                            }
+                        }
+                        None
+                    },
+                    Command::Clear => {
+                        conv = Conversation::new();
+                        queue.clear();
+                        info!(%author, "conversation cleared");
+                        None
+                    }
+                    // dispatch で実行済み
+                    Command::Skip | Command::Mute => None,
+                };
+                if let Some(text) = response {
+                    if inv.spoken {
+                        if let Err(e) = parse_and_play(&text, &voice, &controls, &mut cfg.postprocess.session()).await {
+                            warn!(error = %e, "failed to speak command response");
+                        }
+                    } else {
+                        info!(%author, command = inv.command.name(), response = %text, "command response");
+                        // 読み上げない応答はチャットへのお知らせにする
+                        post_to_chat(poster.as_ref(), &text);
+                    }
+                }
             },
         }
     }
@@ -4,17 +4,84 @@
 //! - パースエラーは [`Error::InvalidConfig`] で早期に失敗させる。
 //! - インターバルは `std::time::Duration` で保持し、呼び出し側で即 `sleep` 可能。
 
-use crate::error::{Error, Result};
+use crate::{
+    error::{Error, Result},
+    model::{
+        gemini_dto::{GenerationConfig, SafetySetting},
+        reply::ReplyFormat,
+    },
+    service::{
+        api::{
+            gemini_client,
+            retry::RetryPolicy,
+            twitch_chat,
+            youtube_api::{self, YouTubeApiConfig},
+        },
+        commands::CommandRouter,
+        moderation::ModerationRules,
+        postprocess::{Pipeline, Stage},
+        scheduler::SchedulerConfig,
+        summarizer::Summarizer,
+        template::Template,
+        tokens::TokenBudget,
+    },
+};
 use anyhow::Context;
-use std::{env, fs, time::Duration};
+use serde::de::DeserializeOwned;
+use std::{env, fs, path::PathBuf, str::FromStr, time::Duration};
 
 /// デフォルト値集約
 mod defaults {
     pub const GEMINI_MODEL: &str = "gemini-2.0-flash";
+    /// llama.cpp server の既定ポート
+    pub const OPENAI_BASE_URL: &str = "http://127.0.0.1:8080/v1";
+    pub const OPENAI_MODEL: &str = "local-model";
     pub const VOICEVOX_SPEAKER: u16 = 3;
+    /// 履歴のターン数の上限（さらにトークン予算で削る）
     pub const MAX_HISTORY: usize = 10;
+    /// システム指示・履歴・返答を合わせたトークン予算
+    pub const HISTORY_TOKEN_BUDGET: usize = 8_000;
+    /// `maxOutputTokens` 未指定時に返答用に空けておくトークン数
+    pub const REPLY_TOKEN_RESERVE: usize = 1_024;
     /// 180 秒 = 3 分
     pub const SPONTANEOUS_INTERVAL_SEC: u64 = 180;
+    pub const STREAM_REPLY: bool = true;
+    pub const TOOLS_ENABLED: bool = false;
+    pub const SUMMARY_ENABLED: bool = true;
+    /// 履歴から外れたターンが何件たまったら要約するか
+    pub const SUMMARY_BATCH: usize = 6;
+    pub const SUMMARY_MAX_CHARS: usize = 400;
+    pub const VIEWER_MEMORY_FILE: &str = "viewer_memory.json";
+    /// 何回目の配信参加から常連として扱うか
+    pub const REGULAR_VIEWER_STREAMS: u64 = 3;
+    /// コメントごとに差し込むナレッジの段落数
+    pub const KNOWLEDGE_TOP_K: usize = 3;
+    /// 返答の後処理の順序
+    pub const POSTPROCESS_STAGES: &str = "markdown,url,emoji,ng_words";
+    pub const URL_REPLACEMENT: &str = "リンク";
+    pub const NG_WORD_MASK: &str = "ピー";
+    /// 1 回の返答で話す最大文字数
+    pub const MAX_SPOKEN_CHARS: usize = 300;
+    /// これより古いコメントには返答しない
+    pub const COMMENT_MAX_AGE_SEC: u64 = 60;
+    /// 返答待ちのコメントを溜めておく最大件数
+    pub const COMMENT_QUEUE_CAPACITY: usize = 50;
+    /// 1 回の返答でまとめて答えるコメント数（1 でまとめない）
+    pub const REPLY_BATCH_SIZE: usize = 1;
+    /// まとめるとき、1 件目から後続を待つ時間
+    pub const REPLY_BATCH_WINDOW_MS: u64 = 1_500;
+    /// リハーサル用のローカル入力の待ち受け先
+    pub const LOCAL_CHAT_TCP_ADDR: &str = "127.0.0.1:7878";
+    pub const LOCAL_CHAT_WS_ADDR: &str = "127.0.0.1:7879";
+    /// `@名前` を付けずに送ったコメントの投稿者名
+    pub const LOCAL_CHAT_AUTHOR: &str = "rehearsal";
+    /// `youtube_api` を使うとき、返答をチャットにも投稿する
+    pub const YOUTUBE_POST_REPLIES: bool = true;
+    pub const FALLBACK_REPLY: &str = "[sad]ごめんね、今のはうまく答えられなかったみたい。";
+    /// API 停止中に無作為に選んで話すつなぎのセリフ（1 行 1 セリフ）
+    pub const CANNED_REPLIES: &str = "[relaxed]ちょっと考えごと中…少し待っててね。\n\
+        [happy]コメントありがとう！今ちょっと頭が回ってないから、後でちゃんと答えるね。\n\
+        [neutral]みんなは今日なにしてたの？";
 }
 
 /// 必須の環境変数を取得する。存在しなければ [`Error::MissingEnvVar`].
@@ -37,17 +104,185 @@ where
     }
 }
 
+/// 使用する LLM バックエンド。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LlmBackendKind {
+    /// Google Gemini (`generateContent`)
+    #[default]
+    Gemini,
+    /// OpenAI 互換 `/v1/chat/completions`
+    OpenAi,
+}
+
+impl FromStr for LlmBackendKind {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "gemini" => Ok(Self::Gemini),
+            "openai" => Ok(Self::OpenAi),
+            other => Err(Error::InvalidConfig(format!(
+                "unknown LLM_BACKEND \"{other}\" (expected gemini|openai)"
+            ))),
+        }
+    }
+}
+
+/// チャットの入力元。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChatSourceKind {
+    /// 配信ページのスクレイピング（認証不要・読み取りのみ）
+    YouTube,
+    /// YouTube Data API v3（OAuth・投稿もできる）
+    YouTubeApi,
+    Twitch,
+    /// 標準入力の 1 行を 1 コメントにする
+    Stdin,
+    /// 行単位の TCP（`LOCAL_CHAT_TCP_ADDR`）
+    Tcp,
+    /// WebSocket（`LOCAL_CHAT_WS_ADDR`）
+    WebSocket,
+}
+
+impl FromStr for ChatSourceKind {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "youtube" => Ok(Self::YouTube),
+            "youtube_api" => Ok(Self::YouTubeApi),
+            "twitch" => Ok(Self::Twitch),
+            "stdin" => Ok(Self::Stdin),
+            "tcp" => Ok(Self::Tcp),
+            "websocket" | "ws" => Ok(Self::WebSocket),
+            other => Err(Error::InvalidConfig(format!(
+                "unknown chat source \"{other}\" (expected youtube|youtube_api|twitch|stdin|tcp|websocket)"
+            ))),
+        }
+    }
+}
+
+/// `CHAT_SOURCES`（カンマ区切り）を読む。
+///
+/// 未指定なら `YOUTUBE_LIVE_URL` / `TWITCH_CHANNEL` があるものを使う。
+/// 選んだ入力元に必要な URL・チャンネルがなければ [`Error::MissingEnvVar`]。
+fn chat_sources_from_env(
+    youtube_live_url: Option<&str>,
+    twitch_channel: Option<&str>,
+) -> Result<Vec<ChatSourceKind>> {
+    let sources = match env::var("CHAT_SOURCES") {
+        Ok(list) => list
+            .split(',')
+            .filter(|s| !s.trim().is_empty())
+            .map(str::parse)
+            .collect::<Result<Vec<_>>>()?,
+        Err(_) => {
+            let mut sources = vec![];
+            if youtube_live_url.is_some() || twitch_channel.is_none() {
+                sources.push(ChatSourceKind::YouTube);
+            }
+            if twitch_channel.is_some() {
+                sources.push(ChatSourceKind::Twitch);
+            }
+            sources
+        }
+    };
+    if sources.is_empty() {
+        return Err(Error::InvalidConfig("CHAT_SOURCES is empty".into()));
+    }
+    if sources.contains(&ChatSourceKind::YouTube) && youtube_live_url.is_none() {
+        return Err(Error::MissingEnvVar("YOUTUBE_LIVE_URL".into()));
+    }
+    if sources.contains(&ChatSourceKind::Twitch) && twitch_channel.is_none() {
+        return Err(Error::MissingEnvVar("TWITCH_CHANNEL".into()));
+    }
+    Ok(sources)
+}
+
 /// アプリ全体で共有する設定。
 #[derive(Debug, Clone)]
 pub struct Config {
+    pub llm_backend: LlmBackendKind,
     pub gemini_api_key: String,
     pub gemini_model: String,
+    /// Gemini API のベース URL（`.../v1beta` まで）。モックサーバでの検証用
+    pub gemini_base_url: String,
+    /// Gemini 呼び出しのリトライ・レート制限・ブレーカー方針
+    pub retry_policy: RetryPolicy,
+    pub openai_base_url: String,
+    pub openai_api_key: Option<String>,
+    pub openai_model: String,
     pub voicevox_speaker: u16,
-    pub youtube_live_url: String,
-    pub bot_system_prompt: String,
+    /// 使うチャットの入力元（`CHAT_SOURCES`）
+    pub chat_sources: Vec<ChatSourceKind>,
+    /// `youtube` を使うときは必須
+    pub youtube_live_url: Option<String>,
+    /// 配信者のチャンネル ID（`youtube` ではこの ID の投稿だけを配信者として扱う）
+    pub youtube_owner_channel_id: Option<String>,
+    /// `youtube_api` を使うときの接続先と認証情報
+    pub youtube_api: Option<YouTubeApiConfig>,
+    /// 読む Twitch のチャンネル（`twitch` を使うときは必須）
+    pub twitch_channel: Option<String>,
+    pub twitch_irc_addr: String,
+    pub twitch_nick: String,
+    /// ボットのアカウントの OAuth トークン（未指定なら匿名）
+    pub twitch_oauth_token: Option<String>,
+    pub local_chat_tcp_addr: String,
+    pub local_chat_ws_addr: String,
+    /// ローカル入力で `@名前` を省いたときの投稿者名
+    pub local_chat_author: String,
+    /// ペルソナのシステムプロンプト（[`Template`]）。文字どおりの `{{` は `\{{` と書く
+    pub bot_system_prompt: Template,
     pub max_history: usize,
     pub spontaneous_interval: Duration,
-    pub spontaneous_prompt: String,
+    pub spontaneous_prompt: Template,
+    /// 配信が始まったときのあいさつの指示
+    pub opening_prompt: Template,
+    /// 配信が終わったときのあいさつの指示
+    pub closing_prompt: Template,
+    /// テンプレート変数 `{{stream_title}}` の値
+    pub stream_title: String,
+    /// 返答をストリーミング受信し、文ごとに読み上げる
+    pub stream_reply: bool,
+    /// 生成パラメータ（temperature, topP, maxOutputTokens など）。OpenAI 互換バックエンドにも写す
+    pub generation_config: GenerationConfig,
+    /// Gemini の `safetySettings`
+    pub safety_settings: Vec<SafetySetting>,
+    /// 返答がブロック・空だったときに代わりに話すセリフ（空なら何も話さない）
+    pub fallback_reply: String,
+    /// LLM が使えない間（ブレーカー作動中など）に無作為に選んで話すセリフ
+    pub canned_replies: Vec<String>,
+    /// Gemini の function calling で組み込みツールを使わせる
+    pub tools_enabled: bool,
+    /// 返答形式（感情タグ or JSON スキーマ）
+    pub reply_format: ReplyFormat,
+    /// 履歴のトークン予算
+    pub token_budget: TokenBudget,
+    /// 履歴から外れたターンを要約して残す（無効なら `None`）
+    pub summarizer: Option<Summarizer>,
+    /// 視聴者の長期記憶の保存先（空文字で保存しない）
+    pub viewer_memory_file: Option<PathBuf>,
+    pub regular_viewer_streams: u64,
+    /// ナレッジベース（Markdown / テキスト）のディレクトリ
+    pub knowledge_dir: Option<PathBuf>,
+    pub knowledge_top_k: usize,
+    /// ペルソナのプロファイル（`*.json`）のディレクトリ
+    pub persona_dir: Option<PathBuf>,
+    /// 起動時のペルソナ名（未指定ならディレクトリの先頭）
+    pub persona: Option<String>,
+    /// 読み上げ前の後処理
+    pub postprocess: Pipeline,
+    /// コメントのモデレーションルール
+    pub moderation_rules: ModerationRules,
+    /// 落としたコメントの監査ログ（JSON Lines）
+    pub moderation_log: Option<PathBuf>,
+    /// 返答するコメントの選び方
+    pub scheduler: SchedulerConfig,
+    /// 1 回の返答でまとめて答えるコメント数（1 でまとめない）
+    pub reply_batch_size: usize,
+    pub reply_batch_window: Duration,
+    /// チャットコマンドの権限・クールダウン
+    pub commands: CommandRouter,
 }
 
 impl Config {
@@ -56,31 +291,199 @@ impl Config {
         // .env は必須ではない
         dotenvy::dotenv().ok();
 
-        let bot_system_prompt = read_text_or_env(
+        let bot_system_prompt = read_template(
             "BOT_SYSTEM_PROMPT_FILE",
             "BOT_SYSTEM_PROMPT",
             "あなたは優しい VTuber AI です。",
         )?;
 
-        let spontaneous_prompt = read_text_or_env(
+        let spontaneous_prompt = read_template(
             "SPONTANEOUS_PROMPT_FILE",
             "SPONTANEOUS_PROMPT", // 将来の拡張を見据えてキーを用意
             "コメントが途切れたら自由に話してね。",
         )?;
 
+        let opening_prompt = read_template(
+            "OPENING_PROMPT_FILE",
+            "OPENING_PROMPT",
+            "配信が始まったよ。来てくれたみんなに元気にあいさつして、今日の配信の話をしてね。",
+        )?;
+        let closing_prompt = read_template(
+            "CLOSING_PROMPT_FILE",
+            "CLOSING_PROMPT",
+            "配信が終わったよ。見てくれたみんなにお礼を言って、お別れのあいさつをしてね。",
+        )?;
+
+        let llm_backend = match env::var("LLM_BACKEND") {
+            Ok(v) => v.parse()?,
+            Err(_) => LlmBackendKind::default(),
+        };
+
+        let tools_enabled = parse_env("TOOLS_ENABLED", defaults::TOOLS_ENABLED)?;
+        let reply_format = match env::var("REPLY_FORMAT") {
+            Ok(v) => v.parse()?,
+            Err(_) => ReplyFormat::default(),
+        };
+        // Gemini は function calling と JSON モード（responseSchema）を併用できない
+        if llm_backend == LlmBackendKind::Gemini
+            && tools_enabled
+            && reply_format == ReplyFormat::Json
+        {
+            return Err(Error::InvalidConfig(
+                "REPLY_FORMAT=json cannot be combined with TOOLS_ENABLED=true on Gemini".into(),
+            ));
+        }
+
+        // Gemini を使わない場合は API キー不要
+        let gemini_api_key = match llm_backend {
+            LlmBackendKind::Gemini => env_must("GEMINI_API_KEY")?,
+            LlmBackendKind::OpenAi => env::var("GEMINI_API_KEY").unwrap_or_default(),
+        };
+
+        let youtube_live_url = env::var("YOUTUBE_LIVE_URL").ok().filter(|u| !u.is_empty());
+        let twitch_channel = env::var("TWITCH_CHANNEL").ok().filter(|c| !c.is_empty());
+        let chat_sources =
+            chat_sources_from_env(youtube_live_url.as_deref(), twitch_channel.as_deref())?;
+        let youtube_api = if chat_sources.contains(&ChatSourceKind::YouTubeApi) {
+            Some(youtube_api_from_env()?)
+        } else {
+            None
+        };
+
+        let generation_config: GenerationConfig =
+            read_json_or_env("GENERATION_CONFIG_FILE", "GENERATION_CONFIG")?;
+        let token_budget = TokenBudget {
+            context_tokens: parse_env("HISTORY_TOKEN_BUDGET", defaults::HISTORY_TOKEN_BUDGET)?,
+            reply_tokens: parse_env(
+                "REPLY_TOKEN_RESERVE",
+                generation_config
+                    .max_output_tokens
+                    .map_or(defaults::REPLY_TOKEN_RESERVE, |n| n as usize),
+            )?,
+        };
+
         Ok(Self {
-            gemini_api_key: env_must("GEMINI_API_KEY")?,
+            llm_backend,
+            gemini_api_key,
             gemini_model: env::var("GEMINI_MODEL")
                 .unwrap_or_else(|_| defaults::GEMINI_MODEL.into()),
+            gemini_base_url: env::var("GEMINI_BASE_URL")
+                .unwrap_or_else(|_| gemini_client::DEFAULT_BASE_URL.into()),
+            retry_policy: retry_policy_from_env()?,
+            openai_base_url: env::var("OPENAI_BASE_URL")
+                .unwrap_or_else(|_| defaults::OPENAI_BASE_URL.into()),
+            openai_api_key: env::var("OPENAI_API_KEY").ok().filter(|k| !k.is_empty()),
+            openai_model: env::var("OPENAI_MODEL")
+                .unwrap_or_else(|_| defaults::OPENAI_MODEL.into()),
             voicevox_speaker: parse_env("VOICEVOX_SPEAKER", defaults::VOICEVOX_SPEAKER)?,
-            youtube_live_url: env_must("YOUTUBE_LIVE_URL")?,
+            chat_sources,
+            youtube_live_url,
+            youtube_owner_channel_id: env::var("YOUTUBE_OWNER_CHANNEL_ID")
+                .ok()
+                .filter(|id| !id.is_empty()),
+            youtube_api,
+            twitch_channel,
+            twitch_irc_addr: env::var("TWITCH_IRC_ADDR")
+                .unwrap_or_else(|_| twitch_chat::DEFAULT_ADDR.into()),
+            twitch_nick: env::var("TWITCH_NICK").unwrap_or_default(),
+            twitch_oauth_token: env::var("TWITCH_OAUTH_TOKEN")
+                .ok()
+                .filter(|t| !t.is_empty()),
+            local_chat_tcp_addr: env::var("LOCAL_CHAT_TCP_ADDR")
+                .unwrap_or_else(|_| defaults::LOCAL_CHAT_TCP_ADDR.into()),
+            local_chat_ws_addr: env::var("LOCAL_CHAT_WS_ADDR")
+                .unwrap_or_else(|_| defaults::LOCAL_CHAT_WS_ADDR.into()),
+            local_chat_author: env::var("LOCAL_CHAT_AUTHOR")
+                .unwrap_or_else(|_| defaults::LOCAL_CHAT_AUTHOR.into()),
             bot_system_prompt,
             spontaneous_prompt,
+            opening_prompt,
+            closing_prompt,
+            stream_title: env::var("STREAM_TITLE").unwrap_or_default(),
             max_history: parse_env("MAX_HISTORY", defaults::MAX_HISTORY)?,
             spontaneous_interval: Duration::from_secs(parse_env(
                 "SPONTANEOUS_INTERVAL_SEC",
                 defaults::SPONTANEOUS_INTERVAL_SEC,
             )?),
+            stream_reply: parse_env("STREAM_REPLY", defaults::STREAM_REPLY)?,
+            generation_config,
+            token_budget,
+            safety_settings: read_json_or_env("SAFETY_SETTINGS_FILE", "SAFETY_SETTINGS")?,
+            fallback_reply: env::var("FALLBACK_REPLY")
+                .unwrap_or_else(|_| defaults::FALLBACK_REPLY.into()),
+            canned_replies: read_text_or_env(
+                "CANNED_REPLIES_FILE",
+                "CANNED_REPLIES", // 直接指定は `|` 区切り
+                defaults::CANNED_REPLIES,
+            )?
+            .split(['\n', '|'])
+            .map(str::trim)
+            .filter(|l| !l.is_empty())
+            .map(str::to_owned)
+            .collect(),
+            tools_enabled,
+            reply_format,
+            summarizer: parse_env("SUMMARY_ENABLED", defaults::SUMMARY_ENABLED)?
+                .then(|| {
+                    Ok::<_, Error>(Summarizer::new(
+                        parse_env("SUMMARY_BATCH", defaults::SUMMARY_BATCH)?,
+                        parse_env("SUMMARY_MAX_CHARS", defaults::SUMMARY_MAX_CHARS)?,
+                    ))
+                })
+                .transpose()?,
+            viewer_memory_file: Some(
+                env::var("VIEWER_MEMORY_FILE")
+                    .unwrap_or_else(|_| defaults::VIEWER_MEMORY_FILE.into()),
+            )
+            .filter(|p| !p.is_empty())
+            .map(PathBuf::from),
+            regular_viewer_streams: parse_env(
+                "REGULAR_VIEWER_STREAMS",
+                defaults::REGULAR_VIEWER_STREAMS,
+            )?,
+            knowledge_dir: env::var("KNOWLEDGE_DIR")
+                .ok()
+                .filter(|p| !p.is_empty())
+                .map(PathBuf::from),
+            knowledge_top_k: parse_env("KNOWLEDGE_TOP_K", defaults::KNOWLEDGE_TOP_K)?,
+            persona_dir: env::var("PERSONA_DIR")
+                .ok()
+                .filter(|p| !p.is_empty())
+                .map(PathBuf::from),
+            persona: env::var("PERSONA").ok().filter(|p| !p.is_empty()),
+            postprocess: postprocess_from_env()?,
+            moderation_rules: match env::var("MODERATION_RULES_FILE") {
+                Ok(path) => ModerationRules::load(path)?,
+                Err(_) => ModerationRules::default(),
+            },
+            moderation_log: env::var("MODERATION_LOG_FILE")
+                .ok()
+                .filter(|p| !p.is_empty())
+                .map(PathBuf::from),
+            scheduler: SchedulerConfig {
+                max_age: Duration::from_secs(parse_env(
+                    "COMMENT_MAX_AGE_SEC",
+                    defaults::COMMENT_MAX_AGE_SEC,
+                )?),
+                capacity: parse_env("COMMENT_QUEUE_CAPACITY", defaults::COMMENT_QUEUE_CAPACITY)?,
+                // 「|」区切りの呼び名（言及されたコメントを優先する）
+                character_names: env::var("CHARACTER_NAMES")
+                    .unwrap_or_default()
+                    .split('|')
+                    .map(str::trim)
+                    .filter(|n| !n.is_empty())
+                    .map(String::from)
+                    .collect(),
+            },
+            reply_batch_size: parse_env("REPLY_BATCH_SIZE", defaults::REPLY_BATCH_SIZE)?.max(1),
+            reply_batch_window: Duration::from_millis(parse_env(
+                "REPLY_BATCH_WINDOW_MS",
+                defaults::REPLY_BATCH_WINDOW_MS,
+            )?),
+            commands: match env::var("COMMANDS_FILE") {
+                Ok(path) => CommandRouter::load(path)?,
+                Err(_) => CommandRouter::default(),
+            },
         })
     }
 }
@@ -95,3 +498,97 @@ fn read_text_or_env(file_key: &str, direct_key: &str, fallback: &str) -> Result<
         Ok(env::var(direct_key).unwrap_or_else(|_| fallback.into()))
     }
 }
+
+/// テンプレートを読み込んで検証する。エラーには設定キーを添える。
+fn read_template(file_key: &str, direct_key: &str, fallback: &str) -> Result<Template> {
+    Template::parse(&read_text_or_env(file_key, direct_key, fallback)?).map_err(|e| match e {
+        Error::InvalidConfig(msg) => Error::InvalidConfig(format!("{direct_key}: {msg}")),
+        other => other,
+    })
+}
+
+/// JSON 形式の設定を `*_FILE` → 直接指定の順で読み込む。未指定なら `T::default()`。
+fn read_json_or_env<T: DeserializeOwned + Default>(file_key: &str, direct_key: &str) -> Result<T> {
+    let text = read_text_or_env(file_key, direct_key, "")?;
+    if text.trim().is_empty() {
+        return Ok(T::default());
+    }
+    serde_json::from_str(&text).map_err(|e| Error::InvalidConfig(format!("{direct_key}: {e}")))
+}
+
+/// `YOUTUBE_CLIENT_ID` などから YouTube Data API の設定を組み立てる。
+fn youtube_api_from_env() -> Result<YouTubeApiConfig> {
+    Ok(YouTubeApiConfig {
+        base_url: env::var("YOUTUBE_API_BASE_URL")
+            .unwrap_or_else(|_| youtube_api::DEFAULT_BASE_URL.into()),
+        token_url: env::var("YOUTUBE_OAUTH_TOKEN_URL")
+            .unwrap_or_else(|_| youtube_api::DEFAULT_TOKEN_URL.into()),
+        client_id: env_must("YOUTUBE_CLIENT_ID")?,
+        client_secret: env_must("YOUTUBE_CLIENT_SECRET")?,
+        refresh_token: env_must("YOUTUBE_REFRESH_TOKEN")?,
+        live_chat_id: env::var("YOUTUBE_LIVE_CHAT_ID")
+            .ok()
+            .filter(|id| !id.is_empty()),
+        post_replies: parse_env("YOUTUBE_POST_REPLIES", defaults::YOUTUBE_POST_REPLIES)?,
+    })
+}
+
+/// `RETRY_*` / `GEMINI_RPM` / `BREAKER_*` からリトライ方針を組み立てる。
+fn retry_policy_from_env() -> Result<RetryPolicy> {
+    let d = RetryPolicy::default();
+    Ok(RetryPolicy {
+        max_retries: parse_env("RETRY_MAX", d.max_retries)?,
+        base_delay: Duration::from_millis(parse_env(
+            "RETRY_BASE_MS",
+            d.base_delay.as_millis() as u64,
+        )?),
+        max_delay: Duration::from_millis(parse_env(
+            "RETRY_MAX_DELAY_MS",
+            d.max_delay.as_millis() as u64,
+        )?),
+        requests_per_minute: parse_env("GEMINI_RPM", d.requests_per_minute)?,
+        breaker_threshold: parse_env("BREAKER_THRESHOLD", d.breaker_threshold)?,
+        breaker_cooldown: Duration::from_secs(parse_env(
+            "BREAKER_COOLDOWN_SEC",
+            d.breaker_cooldown.as_secs(),
+        )?),
+    })
+}
+
+/// 後処理のステージを `POSTPROCESS_STAGES`（カンマ区切り）の順で組み立てる。
+fn postprocess_from_env() -> Result<Pipeline> {
+    let names =
+        env::var("POSTPROCESS_STAGES").unwrap_or_else(|_| defaults::POSTPROCESS_STAGES.into());
+    let mut stages = Vec::new();
+    for name in names.split(',').map(str::trim).filter(|n| !n.is_empty()) {
+        match name {
+            "markdown" => stages.push(Stage::Markdown),
+            "url" => stages.push(Stage::Url {
+                replacement: env::var("URL_REPLACEMENT")
+                    .unwrap_or_else(|_| defaults::URL_REPLACEMENT.into()),
+            }),
+            "emoji" => stages.push(Stage::emoji(read_json_or_env(
+                "EMOJI_READINGS_FILE",
+                "EMOJI_READINGS",
+            )?)),
+            "ng_words" => {
+                let words: Vec<String> = read_text_or_env("NG_WORDS_FILE", "NG_WORDS", "")?
+                    .split(['\n', '|'])
+                    .map(str::to_owned)
+                    .collect();
+                let mask =
+                    env::var("NG_WORD_MASK").unwrap_or_else(|_| defaults::NG_WORD_MASK.into());
+                stages.extend(Stage::ng_words(&words, &mask)?);
+            }
+            other => {
+                return Err(Error::InvalidConfig(format!(
+                    "POSTPROCESS_STAGES: unknown stage `{other}` (markdown, url, emoji, ng_words)"
+                )));
+            }
+        }
+    }
+    Ok(Pipeline {
+        stages,
+        max_chars: parse_env("MAX_SPOKEN_CHARS", defaults::MAX_SPOKEN_CHARS)?,
+    })
+}
@@ -34,6 +34,18 @@ pub enum Error {
     #[error("invalid gemini response: {0}")]
     InvalidGeminiResponse(String),
 
+    /// Gemini がプロンプトまたは応答をブロックした（安全性フィルタなど）。
+    #[error("gemini blocked the response: {0}")]
+    GeminiBlocked(String),
+
+    /// `MAX_TOKENS` に達し、読み上げ可能なテキストが得られなかった。
+    #[error("gemini response truncated by MAX_TOKENS before any text")]
+    GeminiTruncated,
+
+    /// 再試行を使い切った、またはサーキットブレーカーが開いていて LLM を呼べない。
+    #[error("LLM backend unavailable: {0}")]
+    LlmUnavailable(String),
+
     // ───────────────────────────────
     // 外部ライブラリ
     // ───────────────────────────────
@@ -41,3 +53,15 @@ pub enum Error {
     #[error(transparent)]
     External(#[from] anyhow::Error),
 }
+
+impl Error {
+    /// API 呼び出し自体は成功したが、読み上げに使える返答が得られなかったか。
+    ///
+    /// 呼び出し側はこの場合に代替のセリフで場をつなげる。
+    pub fn is_unusable_reply(&self) -> bool {
+        matches!(
+            self,
+            Self::InvalidGeminiResponse(_) | Self::GeminiBlocked(_) | Self::GeminiTruncated
+        )
+    }
+}
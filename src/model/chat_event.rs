@@ -0,0 +1,165 @@
+//! 配信チャットから届くイベント。
+//!
+//! - 通常のコメント・スーパーチャット・スーパーステッカー・メンバー加入を区別する。
+//! - 金額は表示文字列（`"￥1,000"`, `"$5.00"` など）から数値と通貨コードを取り出す。
+//!
+//! ```
+//! use ai_tuber::model::chat_event::Money;
+//!
+//! let m = Money::parse("￥1,000");
+//! assert_eq!((m.amount, m.currency.as_str()), (1000.0, "JPY"));
+//! ```
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::model::conversation::Author;
+
+/// 通貨記号 → ISO 4217 コードと、円換算のおおよそのレート（長い記号から照合する）
+const CURRENCIES: &[(&str, &str, f64)] = &[
+    ("CA$", "CAD", 110.0),
+    ("NT$", "TWD", 4.7),
+    ("HK$", "HKD", 19.0),
+    ("A$", "AUD", 100.0),
+    ("R$", "BRL", 28.0),
+    ("MX$", "MXN", 8.5),
+    ("$", "USD", 150.0),
+    ("￥", "JPY", 1.0),
+    ("¥", "JPY", 1.0),
+    ("€", "EUR", 160.0),
+    ("£", "GBP", 190.0),
+    ("₩", "KRW", 0.11),
+    ("₹", "INR", 1.8),
+    // Twitch の Bits（1 Bit ≒ 1.4 円）
+    ("Bits", "BITS", 1.4),
+];
+
+/// 投稿者のバッジ。
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Badges {
+    pub owner: bool,
+    pub moderator: bool,
+    pub member: bool,
+    pub verified: bool,
+}
+
+/// スーパーチャットなどの金額。
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Money {
+    /// 数値（読み取れなければ 0）
+    pub amount: f64,
+    /// ISO 4217 のコード（未知の記号ならその記号）
+    pub currency: String,
+    /// 表示用の元の文字列
+    pub display: String,
+}
+
+impl Money {
+    /// `"￥1,000"`, `"$5.00"`, `"€5,00"` などを読む。
+    pub fn parse(display: &str) -> Self {
+        let display = display.trim();
+        let split = display
+            .find(|c: char| c.is_ascii_digit())
+            .unwrap_or(display.len());
+        let (symbol, number) = display.split_at(split);
+        let symbol = symbol.trim();
+        let currency = CURRENCIES
+            .iter()
+            .find(|(s, code, _)| symbol == *s || symbol.eq_ignore_ascii_case(code))
+            .map_or(symbol, |(_, code, _)| code);
+
+        // 「5,00」のように , の後が 2 桁で . がなければ小数点とみなす
+        let number: String = number
+            .chars()
+            .take_while(|c| c.is_ascii_digit() || matches!(c, ',' | '.'))
+            .collect();
+        let decimal_comma = !number.contains('.')
+            && number
+                .rsplit_once(',')
+                .is_some_and(|(_, frac)| frac.len() == 2);
+        let number = if decimal_comma {
+            number.replace(',', ".")
+        } else {
+            number.replace(',', "")
+        };
+
+        Self {
+            amount: number.parse().unwrap_or(0.0),
+            currency: currency.to_owned(),
+            display: display.to_owned(),
+        }
+    }
+
+    /// 円換算のおおよその額（優先度付け用。未知の通貨は 1 として扱う）
+    pub fn approx_jpy(&self) -> f64 {
+        let rate = CURRENCIES
+            .iter()
+            .find(|(_, code, _)| *code == self.currency)
+            .map_or(1.0, |(_, _, rate)| *rate);
+        self.amount * rate
+    }
+}
+
+/// イベントに共通する投稿の情報。
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChatMessage {
+    pub id: String,
+    pub author: Author,
+    pub badges: Badges,
+    /// 本文（絵文字は代替テキストに置き換え済み）
+    pub text: String,
+    pub timestamp: DateTime<Utc>,
+}
+
+/// 配信チャットのイベント。
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+#[non_exhaustive]
+pub enum ChatEvent {
+    Message(ChatMessage),
+    SuperChat {
+        message: ChatMessage,
+        amount: Money,
+    },
+    SuperSticker {
+        message: ChatMessage,
+        amount: Money,
+        /// ステッカーの代替テキスト
+        sticker: String,
+    },
+    /// 新しいメンバー加入
+    Membership {
+        message: ChatMessage,
+        /// メンバーのランク（バッジの表示名）
+        tier: Option<String>,
+    },
+}
+
+impl ChatEvent {
+    pub fn message(&self) -> &ChatMessage {
+        match self {
+            Self::Message(message)
+            | Self::SuperChat { message, .. }
+            | Self::SuperSticker { message, .. }
+            | Self::Membership { message, .. } => message,
+        }
+    }
+
+    /// 支払われた金額（スーパーチャット・スーパーステッカー）
+    pub fn paid(&self) -> Option<&Money> {
+        match self {
+            Self::SuperChat { amount, .. } | Self::SuperSticker { amount, .. } => Some(amount),
+            _ => None,
+        }
+    }
+
+    /// ログ用の種別
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Message(_) => "message",
+            Self::SuperChat { .. } => "super_chat",
+            Self::SuperSticker { .. } => "super_sticker",
+            Self::Membership { .. } => "membership",
+        }
+    }
+}
@@ -3,6 +3,7 @@
 //! - `Role` は Gemini/OpenAI が使う `"system" | "user" | "assistant"` に合わせています。  
 //! - `Message` は `Cow<'a, str>` で借用 or 所有を自動切替し、不要な `clone()` を回避。  
 //! - `#[non_exhaustive]` で将来ロールが増えても後方互換を保証。  
+//! - 視聴者のコメントには投稿者（[`Author`]）と時刻、ボットの返答には使った感情を付けられる。  
 //!
 //! ## 例
 //! ```rust
@@ -14,8 +15,11 @@
 
 use std::{borrow::Cow, fmt};
 
+use chrono::{DateTime, Local, Utc};
 use serde::{Deserialize, Serialize};
 
+use crate::model::emotion::Emotion;
+
 /// Speaker type in a conversation.
 ///
 /// Variant names must stay in sync with Gemini/OpenAI API.
@@ -52,6 +56,88 @@ impl fmt::Display for Role {
     }
 }
 
+/// Chat platform a comment came from.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+#[non_exhaustive]
+pub enum Platform {
+    #[default]
+    YouTube,
+    Twitch,
+    /// Local rehearsal input (stdin / TCP / WebSocket).
+    Local,
+}
+
+impl Platform {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Platform::YouTube => "youtube",
+            Platform::Twitch => "twitch",
+            Platform::Local => "local",
+        }
+    }
+}
+
+impl fmt::Display for Platform {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Who posted a comment.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Author {
+    /// Display name.
+    pub name: String,
+    /// Platform-specific channel / user id (stable across renames).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub channel_id: Option<String>,
+    #[serde(default)]
+    pub platform: Platform,
+}
+
+impl Author {
+    pub fn new(name: impl Into<String>, platform: Platform) -> Self {
+        Self {
+            name: name.into(),
+            channel_id: None,
+            platform,
+        }
+    }
+
+    pub fn with_channel_id(mut self, id: impl Into<String>) -> Self {
+        self.channel_id = Some(id.into());
+        self
+    }
+
+    /// Stable per-viewer key: `platform:channel_id`, or `platform:name` when the id is unknown.
+    pub fn key(&self) -> String {
+        format!(
+            "{}:{}",
+            self.platform,
+            self.channel_id.as_deref().unwrap_or(&self.name)
+        )
+    }
+}
+
+impl From<&str> for Author {
+    fn from(name: &str) -> Self {
+        Self::new(name, Platform::default())
+    }
+}
+
+impl From<String> for Author {
+    fn from(name: String) -> Self {
+        Self::new(name, Platform::default())
+    }
+}
+
+impl fmt::Display for Author {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.name)
+    }
+}
+
 /// Chat message.
 ///
 /// Generic lifetime `'a` lets the text be either borrowed or owned.
@@ -60,6 +146,14 @@ pub struct Message<'a> {
     pub role: Role,
     #[serde(borrow)]
     pub text: Cow<'a, str>,
+    /// Poster of a viewer comment (`None` for system / bot turns and merged batches).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub author: Option<Author>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timestamp: Option<DateTime<Utc>>,
+    /// Emotion the bot ended its reply with.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub emotion: Option<Emotion>,
 }
 
 impl<'a> Message<'a> {
@@ -68,6 +162,40 @@ impl<'a> Message<'a> {
         Self {
             role,
             text: text.into(),
+            author: None,
+            timestamp: None,
+            emotion: None,
+        }
+    }
+
+    pub fn with_author(mut self, author: Author) -> Self {
+        self.author = Some(author);
+        self
+    }
+
+    pub fn at(mut self, timestamp: DateTime<Utc>) -> Self {
+        self.timestamp = Some(timestamp);
+        self
+    }
+
+    pub fn with_emotion(mut self, emotion: Emotion) -> Self {
+        self.emotion = Some(emotion);
+        self
+    }
+
+    /// Text as shown to the model: viewer comments get `[HH:MM] name: ` in front.
+    pub fn attributed(&self) -> Cow<'_, str> {
+        let Some(author) = &self.author else {
+            return Cow::Borrowed(&self.text);
+        };
+        match self.timestamp {
+            Some(ts) => Cow::Owned(format!(
+                "[{}] {}: {}",
+                ts.with_timezone(&Local).format("%H:%M"),
+                author.name,
+                self.text
+            )),
+            None => Cow::Owned(format!("{}: {}", author.name, self.text)),
         }
     }
 
@@ -91,3 +219,31 @@ impl<'a, T: Into<Cow<'a, str>>> From<(Role, T)> for Message<'a> {
         Self::new(role, text)
     }
 }
+
+/// Conversation state for the current stream.
+///
+/// Turns that fall out of the budget move to `dropped` and are folded into `summary` later.
+#[derive(Debug, Clone, Default)]
+pub struct Conversation {
+    /// Recent turns included in the prompt.
+    pub history: Vec<Message<'static>>,
+    /// Summary of the stream so far.
+    pub summary: String,
+    /// Turns dropped from `history` that are not summarized yet.
+    pub dropped: Vec<Message<'static>>,
+}
+
+impl Conversation {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, msg: Message<'static>) {
+        self.history.push(msg);
+    }
+
+    /// Moves `history[..start]` to `dropped`.
+    pub fn drop_before(&mut self, start: usize) {
+        self.dropped.extend(self.history.drain(..start));
+    }
+}
@@ -1,4 +1,7 @@
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum Emotion {
     Neutral,
     Happy,
@@ -9,6 +12,18 @@ pub enum Emotion {
 }
 
 impl Emotion {
+    /// タグ・JSON で使う小文字の名前。
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Neutral => "neutral",
+            Self::Happy => "happy",
+            Self::Sad => "sad",
+            Self::Angry => "angry",
+            Self::Relaxed => "relaxed",
+            Self::Surprised => "surprised",
+        }
+    }
+
     /// Unity-VMC の BlendShape 名と値を返す。
     pub const fn clip(self) -> (&'static str, f32) {
         match self {
@@ -1,55 +1,357 @@
 //! Google Gemini 向け DTO
+//!
+//! リクエスト側の DTO は `Cow` でテキスト・設定を借用／所有のどちらでも持てる。
+//! 借用のまま組み立てて送るのが基本で、リクエストを保持・別タスクへ渡すときは
+//! [`GenerateReq::into_owned`] で `'static` にする。
+
+use std::borrow::Cow;
 
 use serde::{Deserialize, Serialize};
 
-#[derive(Serialize)]
+use crate::error::{Error, Result};
+
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
 pub struct GenerateReq<'a> {
     pub contents: Vec<Content<'a>>,
+    /// ペルソナなどのシステム指示（`contents` とは別枠）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub system_instruction: Option<SystemInstruction<'a>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub generation_config: Option<Cow<'a, GenerationConfig>>,
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    pub safety_settings: Cow<'a, [SafetySetting]>,
+    /// Function calling 用の関数宣言
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    pub tools: Cow<'a, [Tool]>,
+}
+#[derive(Serialize, Clone)]
+pub struct SystemInstruction<'a> {
+    pub parts: Vec<Part<'a>>,
 }
 #[derive(Serialize, Clone)]
 pub struct Content<'a> {
-    pub role: &'a str,
+    /// `"user"` / `"model"`
+    pub role: &'static str,
     pub parts: Vec<Part<'a>>,
 }
+/// `{"text": ...}` / `{"functionCall": ...}` / `{"functionResponse": ...}` のいずれか。
 #[derive(Serialize, Clone)]
-pub struct Part<'a> {
-    pub text: &'a str,
+#[serde(rename_all = "camelCase")]
+pub enum Part<'a> {
+    Text(Cow<'a, str>),
+    FunctionCall(FunctionCall),
+    FunctionResponse(FunctionResponse),
 }
-impl<'a> From<&'a str> for GenerateReq<'a> {
-    fn from(p: &'a str) -> Self {
+
+impl GenerateReq<'_> {
+    /// 借用しているテキスト・設定をすべて複製し、所有したリクエストにする。
+    pub fn into_owned(self) -> GenerateReq<'static> {
+        GenerateReq {
+            contents: self.contents.into_iter().map(Content::into_owned).collect(),
+            system_instruction: self.system_instruction.map(|s| SystemInstruction {
+                parts: s.parts.into_iter().map(Part::into_owned).collect(),
+            }),
+            generation_config: self.generation_config.map(|c| Cow::Owned(c.into_owned())),
+            safety_settings: Cow::Owned(self.safety_settings.into_owned()),
+            tools: Cow::Owned(self.tools.into_owned()),
+        }
+    }
+}
+
+impl Content<'_> {
+    pub fn into_owned(self) -> Content<'static> {
+        Content {
+            role: self.role,
+            parts: self.parts.into_iter().map(Part::into_owned).collect(),
+        }
+    }
+}
+
+impl Part<'_> {
+    pub fn into_owned(self) -> Part<'static> {
+        match self {
+            Part::Text(t) => Part::Text(Cow::Owned(t.into_owned())),
+            Part::FunctionCall(c) => Part::FunctionCall(c),
+            Part::FunctionResponse(r) => Part::FunctionResponse(r),
+        }
+    }
+}
+
+impl<'a> GenerateReq<'a> {
+    /// 1 ユーザーターンだけのリクエスト。
+    pub fn user(text: impl Into<Cow<'a, str>>) -> Self {
         Self {
             contents: vec![Content {
-                role: "system",
-                parts: vec![Part { text: p }],
+                role: "user",
+                parts: vec![Part::Text(text.into())],
             }],
+            system_instruction: None,
+            generation_config: None,
+            safety_settings: Cow::Borrowed(&[]),
+            tools: Cow::Borrowed(&[]),
         }
     }
 }
+impl<'a> From<&'a str> for GenerateReq<'a> {
+    fn from(p: &'a str) -> Self {
+        Self::user(p)
+    }
+}
+impl From<String> for GenerateReq<'static> {
+    fn from(p: String) -> Self {
+        Self::user(p)
+    }
+}
+impl<'a> From<&GenerateReq<'a>> for GenerateReq<'a> {
+    fn from(req: &GenerateReq<'a>) -> Self {
+        req.clone()
+    }
+}
+
+/// `countTokens` のリクエスト。
+#[derive(Serialize)]
+pub struct CountTokensReq<'a> {
+    pub contents: Vec<Content<'a>>,
+}
 
 #[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CountTokensRes {
+    pub total_tokens: usize,
+}
+
+/// `tools` の 1 要素。
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Tool {
+    pub function_declarations: Vec<FunctionDeclaration>,
+}
+
+/// モデルに公開する関数の宣言。`parameters` は OpenAPI 形式のスキーマ。
+#[derive(Debug, Clone, Serialize)]
+pub struct FunctionDeclaration {
+    pub name: String,
+    pub description: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parameters: Option<serde_json::Value>,
+}
+
+/// モデルからの関数呼び出し要求。
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FunctionCall {
+    pub name: String,
+    #[serde(default)]
+    pub args: serde_json::Value,
+}
+
+/// 関数の実行結果。`response` は JSON オブジェクトである必要がある。
+#[derive(Debug, Clone, Serialize)]
+pub struct FunctionResponse {
+    pub name: String,
+    pub response: serde_json::Value,
+}
+
+/// `generationConfig`。未指定の項目は送信せず API 既定値に任せる。
+///
+/// 設定ファイルでも API と同じ camelCase のキーで記述する。
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GenerationConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_p: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_k: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_output_tokens: Option<u32>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub stop_sequences: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub candidate_count: Option<u32>,
+    /// `application/json` を指定すると構造化出力になる
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub response_mime_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub response_schema: Option<serde_json::Value>,
+}
+
+/// `safetySettings` の 1 要素（例: `HARM_CATEGORY_HARASSMENT` / `BLOCK_ONLY_HIGH`）。
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SafetySetting {
+    pub category: String,
+    pub threshold: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct GenerateRes {
+    #[serde(default)]
     pub candidates: Vec<Candidate>,
+    /// プロンプト自体がブロックされた場合に `blockReason` が入る
+    pub prompt_feedback: Option<PromptFeedback>,
+    pub usage_metadata: Option<UsageMetadata>,
 }
 #[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct Candidate {
+    /// ストリームの最終チャンクやブロック時には省略されることがある
+    #[serde(default)]
     pub content: RespContent,
+    pub finish_reason: Option<FinishReason>,
+    #[serde(default)]
+    pub safety_ratings: Vec<SafetyRating>,
 }
-#[derive(Deserialize)]
+#[derive(Deserialize, Default)]
 pub struct RespContent {
+    #[serde(default)]
     pub parts: Vec<RespPart>,
 }
 #[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct RespPart {
+    #[serde(default)]
     pub text: String,
+    pub function_call: Option<FunctionCall>,
+}
+
+/// 候補の生成が終了した理由。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum FinishReason {
+    Stop,
+    MaxTokens,
+    Safety,
+    Recitation,
+    Language,
+    Blocklist,
+    ProhibitedContent,
+    Spii,
+    /// 未知の値（API 側の追加に備える）
+    #[serde(other)]
+    Other,
+}
+
+impl FinishReason {
+    /// 内容を理由に出力が止められたか。
+    pub fn is_blocked(self) -> bool {
+        matches!(
+            self,
+            Self::Safety
+                | Self::Recitation
+                | Self::Blocklist
+                | Self::ProhibitedContent
+                | Self::Spii
+        )
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SafetyRating {
+    pub category: String,
+    pub probability: String,
+    #[serde(default)]
+    pub blocked: bool,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PromptFeedback {
+    pub block_reason: Option<String>,
+    #[serde(default)]
+    pub safety_ratings: Vec<SafetyRating>,
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UsageMetadata {
+    #[serde(default)]
+    pub prompt_token_count: u32,
+    #[serde(default)]
+    pub candidates_token_count: u32,
+    #[serde(default)]
+    pub total_token_count: u32,
 }
 
 impl GenerateRes {
-    pub fn first_text(self) -> String {
+    /// ブロック・空応答を検査したうえで先頭候補のテキストを返す。
+    ///
+    /// - `promptFeedback.blockReason` / 安全性による停止 → [`Error::GeminiBlocked`]
+    /// - `MAX_TOKENS` でテキストが空 → [`Error::GeminiTruncated`]
+    /// - 候補なし・テキストなし → [`Error::InvalidGeminiResponse`]
+    pub fn into_text(self) -> Result<String> {
+        self.check_blocked()?;
+        let finish = self.finish_reason();
+        if self.candidates.is_empty() {
+            return Err(Error::InvalidGeminiResponse("no candidates".into()));
+        }
+
+        let text = self.chunk_text();
+        if !text.trim().is_empty() {
+            return Ok(text);
+        }
+        Err(empty_reason(finish))
+    }
+
+    /// ブロックされていればエラーを返す（ストリームの各チャンク用）。
+    pub fn check_blocked(&self) -> Result<()> {
+        if let Some(reason) = self
+            .prompt_feedback
+            .as_ref()
+            .and_then(|f| f.block_reason.as_deref())
+        {
+            return Err(Error::GeminiBlocked(format!("prompt: {reason}")));
+        }
+        if let Some(c) = self.candidates.first()
+            && let Some(reason) = c.finish_reason.filter(|r| r.is_blocked())
+        {
+            let categories = c
+                .safety_ratings
+                .iter()
+                .filter(|r| r.blocked)
+                .map(|r| r.category.as_str())
+                .collect::<Vec<_>>()
+                .join(", ");
+            return Err(Error::GeminiBlocked(format!("{reason:?} [{categories}]")));
+        }
+        Ok(())
+    }
+
+    /// 先頭候補の終了理由。
+    pub fn finish_reason(&self) -> Option<FinishReason> {
+        self.candidates.first().and_then(|c| c.finish_reason)
+    }
+
+    /// 先頭候補に含まれる関数呼び出し要求。
+    pub fn function_calls(&self) -> Vec<FunctionCall> {
+        self.candidates
+            .first()
+            .map(|c| {
+                c.content
+                    .parts
+                    .iter()
+                    .filter_map(|p| p.function_call.clone())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// 先頭候補の全 part を連結したテキスト（ストリームのチャンク用）。
+    pub fn chunk_text(self) -> String {
         self.candidates
             .into_iter()
-            .flat_map(|c| c.content.parts)
-            .map(|p| p.text)
             .next()
+            .map(|c| c.content.parts.into_iter().map(|p| p.text).collect())
             .unwrap_or_default()
     }
 }
+
+/// テキストが得られなかったときのエラーを終了理由から決める。
+pub fn empty_reason(finish: Option<FinishReason>) -> Error {
+    match finish {
+        Some(FinishReason::MaxTokens) => Error::GeminiTruncated,
+        other => Error::InvalidGeminiResponse(format!("empty text (finishReason={other:?})")),
+    }
+}
@@ -1,3 +1,7 @@
+pub mod chat_event;
 pub mod conversation;
 pub mod emotion;
 pub mod gemini_dto;
+pub mod openai_dto;
+pub mod reply;
+pub mod youtube_dto;
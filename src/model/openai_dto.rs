@@ -0,0 +1,57 @@
+//! OpenAI 互換 `/v1/chat/completions` 向け DTO
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize)]
+pub struct ChatReq<'a> {
+    pub model: &'a str,
+    pub messages: Vec<ChatMessage<'a>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_p: Option<f32>,
+    /// 標準外だが llama.cpp server / vLLM は受け付ける
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_k: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<u32>,
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    pub stop: &'a [String],
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub response_format: Option<ResponseFormat>,
+}
+/// `{"type": "json_object"}` で JSON モード
+#[derive(Serialize)]
+pub struct ResponseFormat {
+    #[serde(rename = "type")]
+    pub kind: &'static str,
+}
+#[derive(Serialize, Clone)]
+pub struct ChatMessage<'a> {
+    pub role: &'a str,
+    pub content: &'a str,
+}
+
+#[derive(Deserialize)]
+pub struct ChatRes {
+    pub choices: Vec<Choice>,
+}
+#[derive(Deserialize)]
+pub struct Choice {
+    pub message: RespMessage,
+}
+#[derive(Deserialize)]
+pub struct RespMessage {
+    #[serde(default)]
+    pub content: Option<String>,
+}
+
+impl ChatRes {
+    pub fn first_text(self) -> String {
+        self.choices
+            .into_iter()
+            .filter_map(|c| c.message.content)
+            .next()
+            .unwrap_or_default()
+    }
+}
@@ -0,0 +1,77 @@
+//! 読み上げ単位の返答モデルと、構造化 (JSON) 返答のスキーマ。
+//!
+//! - [`ReplyFormat::Tags`] はテキスト中の `[happy]` などのタグで感情を指定する従来方式。
+//! - [`ReplyFormat::Json`] は `responseSchema` で `[{emotion, text}]` 形式を強制する。
+
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+
+use crate::{
+    error::{Error, Result},
+    model::emotion::Emotion,
+};
+
+/// 読み上げ 1 回分の単位。
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Segment {
+    pub emotion: Emotion,
+    pub text: String,
+}
+
+impl Segment {
+    /// 履歴に残す `[happy]こんにちは` 形式。
+    pub fn to_tagged(&self) -> String {
+        format!("[{}]{}", self.emotion.as_str(), self.text)
+    }
+}
+
+/// LLM に求める返答形式。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ReplyFormat {
+    /// 文頭の感情タグ（正規表現で分割）
+    #[default]
+    Tags,
+    /// `application/json` + `responseSchema`
+    Json,
+}
+
+impl FromStr for ReplyFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "tags" => Ok(Self::Tags),
+            "json" => Ok(Self::Json),
+            other => Err(Error::InvalidConfig(format!(
+                "unknown REPLY_FORMAT \"{other}\" (expected tags|json)"
+            ))),
+        }
+    }
+}
+
+/// Gemini `responseSchema` 用のスキーマ（OpenAPI サブセット）。
+pub fn response_schema() -> Value {
+    json!({
+        "type": "ARRAY",
+        "items": {
+            "type": "OBJECT",
+            "properties": {
+                "emotion": {
+                    "type": "STRING",
+                    "enum": ["neutral", "happy", "sad", "angry", "relaxed", "surprised"]
+                },
+                "text": { "type": "STRING" }
+            },
+            "required": ["emotion", "text"],
+            "propertyOrdering": ["emotion", "text"]
+        }
+    })
+}
+
+/// JSON 返答全体をパースする。
+pub fn parse_json(text: &str) -> Result<Vec<Segment>> {
+    serde_json::from_str::<Vec<Segment>>(text.trim())
+        .map_err(|e| Error::InvalidGeminiResponse(format!("reply does not match schema: {e}")))
+}
@@ -0,0 +1,167 @@
+//! YouTube Data API v3（`liveChatMessages` / `liveBroadcasts`）と OAuth トークン向け DTO
+//!
+//! レスポンス側は使うフィールドだけを持ち、欠けていても読めるよう `default` にする。
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// `POST https://oauth2.googleapis.com/token`（`grant_type=refresh_token`）の応答
+#[derive(Deserialize)]
+pub struct TokenRes {
+    pub access_token: String,
+    /// 有効期間（秒）
+    #[serde(default = "default_expires_in")]
+    pub expires_in: u64,
+}
+
+fn default_expires_in() -> u64 {
+    3600
+}
+
+/// `GET liveBroadcasts`
+#[derive(Deserialize)]
+pub struct LiveBroadcastList {
+    #[serde(default)]
+    pub items: Vec<LiveBroadcast>,
+}
+#[derive(Deserialize)]
+pub struct LiveBroadcast {
+    pub snippet: LiveBroadcastSnippet,
+}
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LiveBroadcastSnippet {
+    #[serde(default)]
+    pub live_chat_id: Option<String>,
+}
+
+/// `GET channels?mine=true`
+#[derive(Deserialize)]
+pub struct ChannelList {
+    #[serde(default)]
+    pub items: Vec<Channel>,
+}
+#[derive(Deserialize)]
+pub struct Channel {
+    pub id: String,
+}
+
+/// `GET liveChat/messages`
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LiveChatMessageList {
+    #[serde(default)]
+    pub next_page_token: Option<String>,
+    /// 次に取得するまで待つ時間
+    #[serde(default)]
+    pub polling_interval_millis: Option<u64>,
+    /// チャットが終了した時刻（終了していれば）
+    #[serde(default)]
+    pub offline_at: Option<String>,
+    #[serde(default)]
+    pub items: Vec<LiveChatMessage>,
+}
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LiveChatMessage {
+    pub id: String,
+    pub snippet: LiveChatSnippet,
+    #[serde(default)]
+    pub author_details: AuthorDetails,
+}
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LiveChatSnippet {
+    /// `textMessageEvent` / `superChatEvent` / `newSponsorEvent` / `chatEndedEvent` など
+    #[serde(rename = "type")]
+    pub kind: String,
+    #[serde(default)]
+    pub published_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub display_message: Option<String>,
+    #[serde(default)]
+    pub text_message_details: Option<TextMessageDetails>,
+    #[serde(default)]
+    pub super_chat_details: Option<SuperChatDetails>,
+    #[serde(default)]
+    pub super_sticker_details: Option<SuperStickerDetails>,
+    #[serde(default)]
+    pub new_sponsor_details: Option<NewSponsorDetails>,
+    #[serde(default)]
+    pub member_milestone_chat_details: Option<MemberMilestoneChatDetails>,
+}
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TextMessageDetails {
+    pub message_text: String,
+}
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SuperChatDetails {
+    /// `"￥1,000"` など
+    pub amount_display_string: String,
+    #[serde(default)]
+    pub user_comment: Option<String>,
+}
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SuperStickerDetails {
+    pub amount_display_string: String,
+    #[serde(default)]
+    pub super_sticker_metadata: Option<SuperStickerMetadata>,
+}
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SuperStickerMetadata {
+    #[serde(default)]
+    pub alt_text: String,
+}
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NewSponsorDetails {
+    #[serde(default)]
+    pub member_level_name: Option<String>,
+}
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MemberMilestoneChatDetails {
+    #[serde(default)]
+    pub member_level_name: Option<String>,
+    #[serde(default)]
+    pub user_comment: Option<String>,
+}
+#[derive(Deserialize, Default)]
+#[serde(default, rename_all = "camelCase")]
+pub struct AuthorDetails {
+    pub channel_id: String,
+    pub display_name: String,
+    pub is_verified: bool,
+    pub is_chat_owner: bool,
+    pub is_chat_sponsor: bool,
+    pub is_chat_moderator: bool,
+}
+
+/// `POST liveChat/messages?part=snippet`
+#[derive(Serialize)]
+pub struct InsertReq<'a> {
+    pub snippet: InsertSnippet<'a>,
+}
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InsertSnippet<'a> {
+    pub live_chat_id: &'a str,
+    /// 常に `"textMessageEvent"`
+    #[serde(rename = "type")]
+    pub kind: &'static str,
+    pub text_message_details: InsertText<'a>,
+}
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InsertText<'a> {
+    pub message_text: &'a str,
+}
+/// 投稿したメッセージ（使うのは ID だけ）
+#[derive(Deserialize)]
+pub struct InsertRes {
+    pub id: String,
+}
@@ -0,0 +1,173 @@
+//! 配信チャットの入力元の抽象化。
+//!
+//! - 入力元ごとに [`ChatSource`] を実装し、共通の [`ChatEvent`] を流す。
+//! - 使う入力元は `Config` から [`from_config`] で選ぶ（複数同時に使える）。
+//! - [`merge`] で全入力元を 1 本のストリームにまとめる。接続に失敗した入力元はログして無視する。
+//! - 配信の開始・終了がわかる入力元は [`Lifecycle`] を流す（[`lifecycles`] でまとめて受け取る）。
+
+use std::fmt;
+
+use tokio::sync::{
+    broadcast::{self, error::RecvError},
+    mpsc::unbounded_channel,
+};
+use tokio_stream::{StreamExt, wrappers::UnboundedReceiverStream};
+use tracing::{info, warn};
+
+use crate::{
+    config::{ChatSourceKind, Config},
+    error::Result,
+    model::chat_event::ChatEvent,
+    service::api::{
+        llm::{BoxFuture, BoxStream},
+        local_chat::{Listen, LocalChat},
+        twitch_chat::TwitchChat,
+        youtube_api::YouTubeApi,
+        youtube_chat::YouTubeChat,
+    },
+};
+
+/// 配信の状態の変化。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Lifecycle {
+    /// 配信の開始待ち（予約枠・未配信）
+    Waiting,
+    /// 配信中でチャットを読んでいる
+    Live,
+    /// 配信中に接続が切れた・つながらないため、つなぎ直している
+    Reconnecting,
+    /// 配信が終わった（ストリームも閉じる）
+    Ended,
+}
+
+impl Lifecycle {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Waiting => "waiting",
+            Self::Live => "live",
+            Self::Reconnecting => "reconnecting",
+            Self::Ended => "ended",
+        }
+    }
+}
+
+impl fmt::Display for Lifecycle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// チャットの入力元。
+pub trait ChatSource: Send + Sync {
+    /// ログ用の名前
+    fn name(&self) -> &str;
+
+    /// 接続し、イベントのストリームを返す。
+    fn subscribe(&self) -> BoxFuture<'_, Result<BoxStream<'static, ChatEvent>>>;
+
+    /// 配信の状態の変化を受け取る。状態を持たない入力元は `None`。
+    fn lifecycle(&self) -> Option<broadcast::Receiver<Lifecycle>> {
+        None
+    }
+}
+
+/// 設定に応じた入力元を作る。
+///
+/// `youtube_api` は返答の投稿にも使うクライアント。自分の投稿を見分けられるよう、同じものを読み取りに使う。
+pub fn from_config(
+    cfg: &Config,
+    youtube_api: Option<&YouTubeApi>,
+) -> Result<Vec<Box<dyn ChatSource>>> {
+    let local = |listen| Box::new(LocalChat::new(listen, &cfg.local_chat_author));
+    let mut sources: Vec<Box<dyn ChatSource>> = vec![];
+    for kind in &cfg.chat_sources {
+        match kind {
+            // URL・チャンネルの有無は Config の読み込み時に確かめている
+            ChatSourceKind::YouTube => {
+                if let Some(url) = &cfg.youtube_live_url {
+                    sources.push(Box::new(
+                        YouTubeChat::new(url)
+                            .with_owner_channel_id(cfg.youtube_owner_channel_id.clone()),
+                    ));
+                }
+            }
+            ChatSourceKind::YouTubeApi => {
+                if let Some(api) = youtube_api {
+                    sources.push(Box::new(api.clone()));
+                }
+            }
+            ChatSourceKind::Twitch => {
+                if let Some(channel) = &cfg.twitch_channel {
+                    let mut twitch = TwitchChat::new(&cfg.twitch_irc_addr, channel);
+                    if let Some(token) = &cfg.twitch_oauth_token {
+                        twitch = twitch.with_login(&cfg.twitch_nick, token);
+                    }
+                    sources.push(Box::new(twitch));
+                }
+            }
+            ChatSourceKind::Stdin => sources.push(local(Listen::Stdin)),
+            ChatSourceKind::Tcp => {
+                sources.push(local(Listen::Tcp(cfg.local_chat_tcp_addr.clone())))
+            }
+            ChatSourceKind::WebSocket => {
+                sources.push(local(Listen::WebSocket(cfg.local_chat_ws_addr.clone())))
+            }
+        }
+    }
+    Ok(sources)
+}
+
+/// すべての入力元に接続し、届いた順に 1 本のストリームにまとめる。
+pub fn merge(sources: Vec<Box<dyn ChatSource>>) -> BoxStream<'static, ChatEvent> {
+    let (tx, rx) = unbounded_channel();
+    for source in sources {
+        let tx = tx.clone();
+        tokio::spawn(async move {
+            let mut stream = match source.subscribe().await {
+                Ok(stream) => stream,
+                Err(e) => {
+                    warn!(source = source.name(), error = %e, "failed to connect chat source");
+                    return;
+                }
+            };
+            info!(source = source.name(), "chat source connected");
+            while let Some(event) = stream.next().await {
+                if tx.send(event).is_err() {
+                    return;
+                }
+            }
+            info!(source = source.name(), "chat source closed");
+        });
+    }
+    Box::pin(UnboundedReceiverStream::new(rx))
+}
+
+/// 各入力元の [`Lifecycle`] を `(入力元の名前, 状態)` の 1 本のストリームにまとめる。
+///
+/// [`merge`] より前に呼ぶ（受信は呼んだ時点以降の変化から）。
+pub fn lifecycles(sources: &[Box<dyn ChatSource>]) -> BoxStream<'static, (String, Lifecycle)> {
+    let (tx, rx) = unbounded_channel();
+    for source in sources {
+        let Some(mut changes) = source.lifecycle() else {
+            continue;
+        };
+        let name = source.name().to_owned();
+        let tx = tx.clone();
+        tokio::spawn(async move {
+            loop {
+                match changes.recv().await {
+                    Ok(state) => {
+                        if tx.send((name.clone(), state)).is_err() {
+                            return;
+                        }
+                    }
+                    Err(RecvError::Lagged(n)) => {
+                        warn!(source = %name, skipped = n, "lifecycle lagged")
+                    }
+                    Err(RecvError::Closed) => return,
+                }
+            }
+        });
+    }
+    Box::pin(UnboundedReceiverStream::new(rx))
+}
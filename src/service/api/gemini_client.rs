@@ -1,24 +1,66 @@
 use crate::{
     error::{Error, Result},
-    model::gemini_dto::{self, Content},
+    model::{
+        conversation::{Message, Role},
+        gemini_dto::{
+            self, Content, FinishReason, FunctionCall, FunctionResponse, GenerateReq, GenerateRes,
+            GenerationConfig, Part, SafetySetting, Tool,
+        },
+    },
+    service::{
+        api::{
+            llm::{BoxFuture, BoxStream, LlmBackend},
+            retry::{Retrier, RetryPolicy},
+        },
+        tools::ToolRegistry,
+    },
 };
 use anyhow::Context;
+use async_stream::try_stream;
 use reqwest::{Client, Url};
-use std::time::Duration;
+use std::{borrow::Cow, sync::Arc, time::Duration};
+use tokio_stream::StreamExt;
+use tracing::{debug, warn};
 
 #[derive(Clone)]
 pub struct GeminiClient {
     client: Client,
     endpoint: Url,
+    stream_endpoint: Url,
+    count_endpoint: Url,
+    generation_config: Option<GenerationConfig>,
+    safety_settings: Vec<SafetySetting>,
+    /// クローン間でレート制限・ブレーカーの状態を共有する
+    retrier: Arc<Retrier>,
+    tools: Option<Arc<ToolRegistry>>,
+    tool_decls: Vec<Tool>,
 }
 
+/// 公式 API のベース URL
+pub const DEFAULT_BASE_URL: &str = "https://generativelanguage.googleapis.com/v1beta";
+
+/// 関数呼び出しの往復回数の上限（無限ループ防止）
+const MAX_TOOL_ROUNDS: usize = 4;
+
 impl GeminiClient {
     pub fn new(api_key: &str, model: &str) -> Result<Self> {
-        let base = format!(
-            "https://generativelanguage.googleapis.com/v1beta/models/{model}:generateContent"
-        );
+        Self::with_base_url(DEFAULT_BASE_URL, api_key, model)
+    }
+
+    /// ベース URL（`.../v1beta` まで）を指定して生成する。テスト用のモックサーバにも使う。
+    pub fn with_base_url(base_url: &str, api_key: &str, model: &str) -> Result<Self> {
+        let base = format!("{}/models/{model}", base_url.trim_end_matches('/'));
         let endpoint =
-            Url::parse_with_params(&base, &[("key", api_key)]).context("construct endpoint url")?;
+            Url::parse_with_params(&format!("{base}:generateContent"), &[("key", api_key)])
+                .context("construct endpoint url")?;
+        let stream_endpoint = Url::parse_with_params(
+            &format!("{base}:streamGenerateContent"),
+            &[("alt", "sse"), ("key", api_key)],
+        )
+        .context("construct stream endpoint url")?;
+        let count_endpoint =
+            Url::parse_with_params(&format!("{base}:countTokens"), &[("key", api_key)])
+                .context("construct countTokens endpoint url")?;
 
         let client = Client::builder()
             .timeout(Duration::from_secs(20))
@@ -26,24 +68,307 @@ impl GeminiClient {
             .build()
             .context("build reqwest client")?;
 
-        Ok(Self { client, endpoint })
+        Ok(Self {
+            client,
+            endpoint,
+            stream_endpoint,
+            count_endpoint,
+            generation_config: None,
+            safety_settings: Vec::new(),
+            retrier: Arc::new(Retrier::new(RetryPolicy::default())),
+            tools: None,
+            tool_decls: Vec::new(),
+        })
+    }
+
+    /// Function calling で使うツールを登録する。
+    pub fn with_tools(mut self, tools: Arc<ToolRegistry>) -> Self {
+        self.tool_decls = tools.declarations();
+        self.tools = Some(tools);
+        self
+    }
+
+    /// リトライ・レート制限・ブレーカーの方針を設定する。
+    pub fn with_retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.retrier = Arc::new(Retrier::new(policy));
+        self
+    }
+
+    /// 全リクエストに付与する `generationConfig` を設定する。
+    pub fn with_generation_config(mut self, cfg: GenerationConfig) -> Self {
+        self.generation_config = Some(cfg);
+        self
+    }
+
+    /// 全リクエストに付与する `safetySettings` を設定する。
+    pub fn with_safety_settings(mut self, settings: Vec<SafetySetting>) -> Self {
+        self.safety_settings = settings;
+        self
+    }
+
+    /// JSON モード（`responseMimeType` / `responseSchema`）とツールを外した複製。
+    ///
+    /// レート制限・ブレーカーの状態は共有する。
+    pub fn plain(&self) -> Self {
+        let mut plain = self.clone();
+        if let Some(cfg) = &mut plain.generation_config {
+            cfg.response_mime_type = None;
+            cfg.response_schema = None;
+        }
+        plain.tools = None;
+        plain.tool_decls.clear();
+        plain
+    }
+
+    /// 汎用メッセージからリクエストを組み立てる。
+    ///
+    /// [`Role::System`] のメッセージは `systemInstruction` にまとめ、
+    /// 残りを `contents` (`user` / `model`) に変換する。
+    pub fn request<'a>(&'a self, messages: &'a [Message<'a>]) -> GenerateReq<'a> {
+        let (system, turns): (Vec<_>, Vec<_>) =
+            messages.iter().partition(|m| m.role == Role::System);
+
+        GenerateReq {
+            contents: turns
+                .into_iter()
+                .map(|msg| Content {
+                    role: match msg.role {
+                        Role::Assistant | Role::Bot => "model",
+                        _ => "user",
+                    },
+                    parts: vec![Part::Text(Cow::Borrowed(&msg.text))],
+                })
+                .collect(),
+            system_instruction: (!system.is_empty()).then(|| gemini_dto::SystemInstruction {
+                parts: system
+                    .into_iter()
+                    .map(|m| Part::Text(Cow::Borrowed(&m.text)))
+                    .collect(),
+            }),
+            generation_config: self.generation_config.as_ref().map(Cow::Borrowed),
+            safety_settings: Cow::Borrowed(&self.safety_settings),
+            tools: Cow::Borrowed(&self.tool_decls),
+        }
+    }
+
+    /// 1 回だけ生成し、本文を返す（ツールの往復はしない）。
+    ///
+    /// 借用・所有どちらのリクエストも受け付ける。`&str` / `String` は 1 ユーザーターンになる。
+    /// リクエストに含まれない生成パラメータ等は補わないので、必要なら [`Self::request`] で作る。
+    pub async fn ask<'r>(&self, req: impl Into<GenerateReq<'r>>) -> Result<String> {
+        self.generate(&req.into()).await?.into_text()
+    }
+
+    /// `generateContent` を呼び、レスポンスをそのまま返す。
+    pub async fn generate(&self, req: &GenerateReq<'_>) -> Result<GenerateRes> {
+        let res = self
+            .retrier
+            .send(|| self.client.post(self.endpoint.clone()).json(req))
+            .await?
+            .json::<gemini_dto::GenerateRes>()
+            .await
+            .context("parse json")
+            .map_err(Error::External)?;
+
+        inspect(&res);
+        Ok(res)
     }
 
-    pub async fn ask<'a>(&self, contents: &'a [Content<'a>]) -> Result<String> {
-        self.client
-            .post(self.endpoint.clone())
-            .json(&gemini_dto::GenerateReq {
-                contents: contents.to_vec(),
+    /// `streamGenerateContent` (SSE) のチャンクを順に返す。
+    pub fn generate_stream<'a>(
+        &'a self,
+        req: &'a GenerateReq<'a>,
+    ) -> impl tokio_stream::Stream<Item = Result<GenerateRes>> + Send + 'a {
+        try_stream! {
+            let res = self
+                .retrier
+                .send(|| {
+                    self.client
+                        .post(self.stream_endpoint.clone())
+                        // 長い生成でも途中で切れないよう、クライアント既定 (20 秒) より長くする
+                        .timeout(Duration::from_secs(120))
+                        .json(req)
+                })
+                .await?;
+
+            let mut body = res.bytes_stream();
+            let mut buf: Vec<u8> = Vec::new();
+            loop {
+                let next = body.next().await;
+                let eof = next.is_none();
+                if let Some(bytes) = next {
+                    buf.extend_from_slice(&bytes.context("read gemini stream")?);
+                } else {
+                    // 末尾に改行のない最終行も処理する
+                    buf.push(b'\n');
+                }
+
+                // SSE は行単位。未完の行は次のチャンクまで持ち越す
+                while let Some(nl) = buf.iter().position(|&b| b == b'\n') {
+                    let line: Vec<u8> = buf.drain(..=nl).collect();
+                    if let Some(chunk) = parse_sse_line(&line)? {
+                        inspect(&chunk);
+                        yield chunk;
+                    }
+                }
+                if eof {
+                    break;
+                }
+            }
+        }
+    }
+
+    /// `countTokens` で `text` を 1 ユーザーターンとして数える。
+    ///
+    /// 生成とは別枠のクォータなので、リトライ・予算管理は通さない。
+    /// ただしブレーカー作動中は API に送らず [`Error::LlmUnavailable`] を返す
+    /// （呼び出し側は概算で代用する）。
+    pub async fn count_tokens(&self, text: &str) -> Result<usize> {
+        if self.retrier.is_open() {
+            return Err(Error::LlmUnavailable("circuit breaker is open".into()));
+        }
+        let res = self
+            .client
+            .post(self.count_endpoint.clone())
+            .json(&gemini_dto::CountTokensReq {
+                contents: vec![Content {
+                    role: "user",
+                    parts: vec![Part::Text(Cow::Borrowed(text))],
+                }],
             })
             .send()
             .await
-            .context("POST gemini")?
+            .context("POST countTokens")?
             .error_for_status()
-            .context("non-2xx")?
-            .json::<gemini_dto::GenerateRes>()
+            .context("countTokens non-2xx")?
+            .json::<gemini_dto::CountTokensRes>()
             .await
-            .context("parse json")
-            .map(|r| r.first_text())
-            .map_err(Error::External)
+            .context("parse countTokens json")?;
+        Ok(res.total_tokens)
+    }
+
+    /// 関数呼び出しを実行し、会話に追記する `model` / `user` ターンを返す。
+    fn run_tools(&self, calls: Vec<FunctionCall>) -> [Content<'static>; 2] {
+        let responses = calls
+            .iter()
+            .map(|c| {
+                let response = match &self.tools {
+                    Some(t) => t.call(&c.name, &c.args),
+                    None => serde_json::json!({ "error": "tools are disabled" }),
+                };
+                Part::FunctionResponse(FunctionResponse {
+                    name: c.name.clone(),
+                    response,
+                })
+            })
+            .collect();
+        [
+            Content {
+                role: "model",
+                parts: calls.into_iter().map(Part::FunctionCall).collect(),
+            },
+            Content {
+                role: "user",
+                parts: responses,
+            },
+        ]
+    }
+}
+
+/// `data: {...}` 行をパースする。それ以外の行は `None`。
+fn parse_sse_line(line: &[u8]) -> Result<Option<gemini_dto::GenerateRes>> {
+    let line = std::str::from_utf8(line)
+        .context("gemini stream is not UTF-8")?
+        .trim();
+    let Some(data) = line.strip_prefix("data:") else {
+        return Ok(None);
+    };
+    let res = serde_json::from_str(data.trim()).context("parse stream chunk")?;
+    Ok(Some(res))
+}
+
+/// トークン使用量と途中終了をログに残す。
+fn inspect(res: &gemini_dto::GenerateRes) {
+    if let Some(u) = res.usage_metadata {
+        debug!(
+            prompt = u.prompt_token_count,
+            candidates = u.candidates_token_count,
+            total = u.total_token_count,
+            "gemini usage"
+        );
+    }
+    if res.finish_reason() == Some(FinishReason::MaxTokens) {
+        warn!("gemini reply hit MAX_TOKENS; the reply may be cut off");
+    }
+}
+
+/// 関数呼び出しの往復を含めて返答を生成する。
+///
+/// モデルが関数呼び出しを返したらツールを実行して結果を送り返し、
+/// テキストが返るまで（最大 [`MAX_TOOL_ROUNDS`] 回）繰り返す。
+impl LlmBackend for GeminiClient {
+    fn chat<'a>(&'a self, messages: &'a [Message<'a>]) -> BoxFuture<'a, Result<String>> {
+        Box::pin(async move {
+            let mut extra: Vec<Content<'static>> = Vec::new();
+            for _ in 0..MAX_TOOL_ROUNDS {
+                let mut req = self.request(messages);
+                req.contents.extend(extra.iter().cloned());
+
+                let res = self.generate(&req).await?;
+                res.check_blocked()?;
+                let calls = res.function_calls();
+                if calls.is_empty() || self.tools.is_none() {
+                    return res.into_text();
+                }
+                extra.extend(self.run_tools(calls));
+            }
+            Err(Error::InvalidGeminiResponse(
+                "too many function call rounds".into(),
+            ))
+        })
+    }
+
+    fn count_tokens<'a>(&'a self, text: &'a str) -> BoxFuture<'a, Result<usize>> {
+        Box::pin(GeminiClient::count_tokens(self, text))
+    }
+
+    fn plain(&self) -> Option<Box<dyn LlmBackend>> {
+        Some(Box::new(GeminiClient::plain(self)))
+    }
+
+    fn chat_stream<'a>(&'a self, messages: &'a [Message<'a>]) -> BoxStream<'a, Result<String>> {
+        Box::pin(try_stream! {
+            let mut extra: Vec<Content<'static>> = Vec::new();
+            let mut got_text = false;
+            let mut finish = None;
+            for _ in 0..MAX_TOOL_ROUNDS {
+                let mut req = self.request(messages);
+                req.contents.extend(extra.iter().cloned());
+
+                let mut calls = Vec::new();
+                let stream = self.generate_stream(&req);
+                tokio::pin!(stream);
+                while let Some(chunk) = stream.next().await {
+                    let chunk = chunk?;
+                    chunk.check_blocked()?;
+                    finish = chunk.finish_reason().or(finish);
+                    calls.extend(chunk.function_calls());
+                    let text = chunk.chunk_text();
+                    if !text.is_empty() {
+                        got_text = true;
+                        yield text;
+                    }
+                }
+
+                if calls.is_empty() || self.tools.is_none() {
+                    break;
+                }
+                extra.extend(self.run_tools(calls));
+            }
+
+            if !got_text {
+                Err(gemini_dto::empty_reason(finish))?;
+            }
+        })
     }
 }
@@ -0,0 +1,91 @@
+//! LLM バックエンドの抽象化。
+//!
+//! - 会話履歴は [`Message`] の列で受け取り、返答テキストを返す。
+//! - 実装は `Config::llm_backend` から [`from_config`] で選択する。
+//! - `dyn` で扱えるよう、非同期メソッドは [`BoxFuture`] を返す形にしている。
+
+use std::{future::Future, pin::Pin, sync::Arc};
+
+use tokio_stream::{Stream, StreamExt};
+
+use crate::{
+    config::{Config, LlmBackendKind},
+    error::Result,
+    model::{
+        conversation::Message,
+        reply::{self, ReplyFormat},
+    },
+    service::{
+        api::{gemini_client::GeminiClient, openai_client::OpenAiClient},
+        tokens,
+        tools::ToolRegistry,
+    },
+};
+
+/// `Send` な boxed future。
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+/// `Send` な boxed stream。
+pub type BoxStream<'a, T> = Pin<Box<dyn Stream<Item = T> + Send + 'a>>;
+
+/// チャット形式で返答を生成するバックエンド。
+pub trait LlmBackend: Send + Sync {
+    /// 履歴 `messages`（先頭に [`Role::System`](crate::model::conversation::Role::System) を含んでよい）から返答を生成する。
+    fn chat<'a>(&'a self, messages: &'a [Message<'a>]) -> BoxFuture<'a, Result<String>>;
+
+    /// 返答をテキストチャンクの列として返す。
+    ///
+    /// ストリーミング非対応のバックエンドは [`chat`](Self::chat) の結果を 1 チャンクで返す。
+    fn chat_stream<'a>(&'a self, messages: &'a [Message<'a>]) -> BoxStream<'a, Result<String>> {
+        Box::pin(tokio_stream::once(()).then(move |_| self.chat(messages)))
+    }
+
+    /// `text` のトークン数を返す。
+    ///
+    /// 計測 API を持たないバックエンドは [`tokens::estimate`] の概算を返す。
+    fn count_tokens<'a>(&'a self, text: &'a str) -> BoxFuture<'a, Result<usize>> {
+        Box::pin(async move { Ok(tokens::estimate(text)) })
+    }
+
+    /// 構造化出力・ツールを外した複製（要約など、自由記述のテキストが欲しい呼び出し用）。
+    ///
+    /// それらを使わないバックエンドは `None` を返し、呼び出し側は自身をそのまま使う。
+    fn plain(&self) -> Option<Box<dyn LlmBackend>> {
+        None
+    }
+}
+
+/// 設定に応じたバックエンドを生成する。
+///
+/// `tools` は function calling に対応したバックエンド (Gemini) でのみ使われる。
+pub fn from_config(cfg: &Config, tools: Option<Arc<ToolRegistry>>) -> Result<Box<dyn LlmBackend>> {
+    let mut generation = cfg.generation_config.clone();
+    if cfg.reply_format == ReplyFormat::Json {
+        generation.response_mime_type = Some("application/json".into());
+        generation.response_schema = Some(reply::response_schema());
+    }
+    Ok(match cfg.llm_backend {
+        LlmBackendKind::Gemini => {
+            let mut client = GeminiClient::with_base_url(
+                &cfg.gemini_base_url,
+                &cfg.gemini_api_key,
+                &cfg.gemini_model,
+            )?
+            .with_retry_policy(cfg.retry_policy.clone())
+            .with_generation_config(generation)
+            .with_safety_settings(cfg.safety_settings.clone());
+            if let Some(tools) = tools {
+                client = client.with_tools(tools);
+            }
+            Box::new(client)
+        }
+        LlmBackendKind::OpenAi => Box::new(
+            OpenAiClient::new(
+                &cfg.openai_base_url,
+                cfg.openai_api_key.as_deref(),
+                &cfg.openai_model,
+            )?
+            .with_generation_config(generation),
+        ),
+    })
+}
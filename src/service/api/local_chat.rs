@@ -0,0 +1,208 @@
+//! 配信せずにリハーサルするためのローカルなチャット入力。
+//!
+//! - 標準入力・行単位の TCP・WebSocket の 3 種類。どれも 1 行（1 メッセージ）を 1 コメントにする。
+//! - `@名前 本文` で投稿者を指定できる。省略時は設定の既定名。
+//! - ローカルの投稿者は配信者扱い（コマンドをすべて使える）。
+//! - WebSocket は `tokio-tungstenite` で受け、テキストメッセージだけを読む。
+//!
+//! ```text
+//! $ nc 127.0.0.1 7878
+//! こんにちは
+//! @たろう 今日は何するの？
+//! ```
+
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use anyhow::Context;
+use chrono::Utc;
+use tokio::{
+    io::{AsyncBufReadExt, AsyncRead, BufReader},
+    net::{TcpListener, TcpStream},
+    sync::mpsc::{UnboundedSender, unbounded_channel},
+};
+use tokio_stream::{StreamExt, wrappers::UnboundedReceiverStream};
+use tokio_tungstenite::tungstenite::{self, Message, protocol::WebSocketConfig};
+use tracing::{debug, info, warn};
+
+use crate::{
+    error::Result,
+    model::{
+        chat_event::{Badges, ChatEvent, ChatMessage},
+        conversation::{Author, Platform},
+    },
+    service::api::{
+        chat_source::ChatSource,
+        llm::{BoxFuture, BoxStream},
+        retry::backoff,
+    },
+};
+
+/// 1 メッセージの上限（リハーサル用なので小さめ）
+const MAX_MESSAGE: usize = 64 * 1024;
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+
+/// ローカル入力の種類。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Listen {
+    Stdin,
+    /// 行単位の TCP（`host:port` で待ち受け）
+    Tcp(String),
+    /// WebSocket（`host:port` で待ち受け、パスは問わない）
+    WebSocket(String),
+}
+
+/// [`ChatSource`] としてのローカル入力。
+#[derive(Debug, Clone)]
+pub struct LocalChat {
+    listen: Listen,
+    default_author: String,
+}
+
+impl LocalChat {
+    pub fn new(listen: Listen, default_author: impl Into<String>) -> Self {
+        Self {
+            listen,
+            default_author: default_author.into(),
+        }
+    }
+}
+
+impl ChatSource for LocalChat {
+    fn name(&self) -> &str {
+        match self.listen {
+            Listen::Stdin => "stdin",
+            Listen::Tcp(_) => "tcp",
+            Listen::WebSocket(_) => "websocket",
+        }
+    }
+
+    fn subscribe(&self) -> BoxFuture<'_, Result<BoxStream<'static, ChatEvent>>> {
+        Box::pin(async move {
+            let (tx, rx) = unbounded_channel();
+            let author = self.default_author.clone();
+            match &self.listen {
+                Listen::Stdin => {
+                    tokio::spawn(read_lines(tokio::io::stdin(), author, tx));
+                }
+                Listen::Tcp(addr) => {
+                    let listener = bind(addr).await?;
+                    info!(%addr, "local chat listening (tcp)");
+                    tokio::spawn(accept(listener, author, tx, false));
+                }
+                Listen::WebSocket(addr) => {
+                    let listener = bind(addr).await?;
+                    info!(%addr, "local chat listening (websocket)");
+                    tokio::spawn(accept(listener, author, tx, true));
+                }
+            }
+            let stream: BoxStream<'static, ChatEvent> = Box::pin(UnboundedReceiverStream::new(rx));
+            Ok(stream)
+        })
+    }
+}
+
+/// `@名前 本文` または `本文` をコメントにする。空行は `None`。
+pub fn parse_line(line: &str, default_author: &str) -> Option<ChatEvent> {
+    let line = line.trim();
+    let (name, text) = match line
+        .strip_prefix('@')
+        .and_then(|l| l.split_once(char::is_whitespace))
+    {
+        Some((name, text)) if !name.is_empty() => (name, text.trim()),
+        _ => (default_author, line),
+    };
+    if text.is_empty() {
+        return None;
+    }
+    Some(ChatEvent::Message(ChatMessage {
+        id: format!("local-{}", NEXT_ID.fetch_add(1, Ordering::Relaxed)),
+        author: Author::new(name, Platform::Local),
+        badges: Badges {
+            owner: true,
+            ..Badges::default()
+        },
+        text: text.to_owned(),
+        timestamp: Utc::now(),
+    }))
+}
+
+async fn bind(addr: &str) -> Result<TcpListener> {
+    Ok(TcpListener::bind(addr)
+        .await
+        .with_context(|| format!("failed to listen on {addr}"))?)
+}
+
+fn send(line: &str, author: &str, tx: &UnboundedSender<ChatEvent>) -> bool {
+    match parse_line(line, author) {
+        Some(event) => tx.send(event).is_ok(),
+        None => true,
+    }
+}
+
+async fn read_lines(read: impl AsyncRead + Unpin, author: String, tx: UnboundedSender<ChatEvent>) {
+    let mut lines = BufReader::new(read).lines();
+    while let Ok(Some(line)) = lines.next_line().await {
+        if !send(&line, &author, &tx) {
+            break;
+        }
+    }
+}
+
+/// 接続ごとにタスクを分けて読む。
+async fn accept(
+    listener: TcpListener,
+    author: String,
+    tx: UnboundedSender<ChatEvent>,
+    websocket: bool,
+) {
+    let mut attempt = 0;
+    while !tx.is_closed() {
+        let (conn, peer) = match listener.accept().await {
+            Ok(accepted) => accepted,
+            // EMFILE などは続けて失敗するので、待ってから受け直す
+            Err(e) => {
+                warn!(error = %e, attempt, "local chat accept failed");
+                tokio::time::sleep(backoff(attempt)).await;
+                attempt += 1;
+                continue;
+            }
+        };
+        attempt = 0;
+        debug!(%peer, websocket, "local chat client connected");
+        let (author, tx) = (author.clone(), tx.clone());
+        tokio::spawn(async move {
+            if websocket {
+                if let Err(e) = serve_websocket(conn, &author, &tx).await {
+                    debug!(%peer, error = %e, "websocket client closed");
+                }
+            } else {
+                read_lines(conn, author, tx).await;
+            }
+        });
+    }
+}
+
+/// テキストメッセージを 1 行ずつコメントにする。ping・close への応答は tungstenite に任せる。
+async fn serve_websocket(
+    conn: TcpStream,
+    author: &str,
+    tx: &UnboundedSender<ChatEvent>,
+) -> tungstenite::Result<()> {
+    let config = WebSocketConfig::default()
+        .max_message_size(Some(MAX_MESSAGE))
+        .max_frame_size(Some(MAX_MESSAGE));
+    let mut ws = tokio_tungstenite::accept_async_with_config(conn, Some(config)).await?;
+    while let Some(message) = ws.next().await {
+        // バイナリ・ping・pong は無視
+        let Message::Text(text) = message? else {
+            continue;
+        };
+        for line in text.lines() {
+            if !send(line, author, tx) {
+                return Ok(());
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,103 @@
+//! OpenAI 互換 Chat Completions クライアント。
+//!
+//! llama.cpp server / vLLM / LM Studio などローカルのモデルサーバでも動作する。
+
+use crate::{
+    error::{Error, Result},
+    model::{conversation::Message, gemini_dto::GenerationConfig, openai_dto},
+    service::api::llm::{BoxFuture, LlmBackend},
+};
+use anyhow::Context;
+use reqwest::{Client, Url};
+use std::time::Duration;
+
+#[derive(Clone)]
+pub struct OpenAiClient {
+    client: Client,
+    endpoint: Url,
+    api_key: Option<String>,
+    model: String,
+    generation: GenerationConfig,
+}
+
+impl OpenAiClient {
+    /// `base_url` は `http://127.0.0.1:8080/v1` のように `/v1` までを指定する。
+    pub fn new(base_url: &str, api_key: Option<&str>, model: &str) -> Result<Self> {
+        let endpoint = Url::parse(&format!(
+            "{}/chat/completions",
+            base_url.trim_end_matches('/')
+        ))
+        .context("construct endpoint url")?;
+
+        let client = Client::builder()
+            .timeout(Duration::from_secs(60))
+            .user_agent(concat!("ai_tuber/", env!("CARGO_PKG_VERSION")))
+            .build()
+            .context("build reqwest client")?;
+
+        Ok(Self {
+            client,
+            endpoint,
+            api_key: api_key.map(str::to_owned),
+            model: model.to_owned(),
+            generation: GenerationConfig::default(),
+        })
+    }
+
+    /// 生成パラメータを設定する。
+    ///
+    /// `temperature` / `topP` / `topK` / `maxOutputTokens` / `stopSequences` を対応する項目に写す。
+    /// `responseMimeType` が `application/json` なら `response_format` を JSON モードにする
+    /// （スキーマは送らず、形はシステムプロンプトの指示に任せる）。
+    /// 返答は先頭の候補しか使わないため `candidateCount` は送らない。
+    pub fn with_generation_config(mut self, generation: GenerationConfig) -> Self {
+        self.generation = generation;
+        self
+    }
+
+    pub async fn ask(&self, messages: &[Message<'_>]) -> Result<String> {
+        let req = openai_dto::ChatReq {
+            model: &self.model,
+            messages: messages
+                .iter()
+                .map(|m| openai_dto::ChatMessage {
+                    role: m.role.as_str(),
+                    content: &m.text,
+                })
+                .collect(),
+            temperature: self.generation.temperature,
+            top_p: self.generation.top_p,
+            top_k: self.generation.top_k,
+            max_tokens: self.generation.max_output_tokens,
+            stop: &self.generation.stop_sequences,
+            response_format: (self.generation.response_mime_type.as_deref()
+                == Some("application/json"))
+            .then_some(openai_dto::ResponseFormat {
+                kind: "json_object",
+            }),
+        };
+
+        let mut builder = self.client.post(self.endpoint.clone()).json(&req);
+        if let Some(key) = &self.api_key {
+            builder = builder.bearer_auth(key);
+        }
+
+        builder
+            .send()
+            .await
+            .context("POST chat/completions")?
+            .error_for_status()
+            .context("non-2xx")?
+            .json::<openai_dto::ChatRes>()
+            .await
+            .context("parse json")
+            .map(|r| r.first_text())
+            .map_err(Error::External)
+    }
+}
+
+impl LlmBackend for OpenAiClient {
+    fn chat<'a>(&'a self, messages: &'a [Message<'a>]) -> BoxFuture<'a, Result<String>> {
+        Box::pin(self.ask(messages))
+    }
+}
@@ -0,0 +1,272 @@
+//! HTTP 呼び出しのリトライ・レート制限・サーキットブレーカー。
+//!
+//! ```text
+//! 1. ブレーカーが開いていれば即 Error::LlmUnavailable
+//! 2. 1 分あたりの予算に空きが出るまで待つ
+//! 3. 429 / 5xx / 接続失敗は指数バックオフ + ジッタで再試行
+//!    （Retry-After ヘッダ、または Gemini の RetryInfo.retryDelay を優先）
+//! 4. 再試行を使い切ったら失敗を記録し、連続失敗が閾値に達したらブレーカーを開く
+//! ```
+
+use std::{
+    collections::VecDeque,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use reqwest::{RequestBuilder, Response, StatusCode, header::RETRY_AFTER};
+use tracing::{info, warn};
+
+use crate::error::{Error, Result};
+
+/// リトライ方針。
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /// 初回を除く最大再試行回数
+    pub max_retries: u32,
+    /// バックオフの基準時間（`base * 2^attempt`）
+    pub base_delay: Duration,
+    /// 1 回の待ち時間の上限（Retry-After にも適用）
+    pub max_delay: Duration,
+    /// 1 分あたりの最大リクエスト数（0 は無制限）
+    pub requests_per_minute: u32,
+    /// ブレーカーを開く連続失敗回数（0 は無効）
+    pub breaker_threshold: u32,
+    /// ブレーカーを開いておく時間
+    pub breaker_cooldown: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 3,
+            base_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(20),
+            requests_per_minute: 15,
+            breaker_threshold: 3,
+            breaker_cooldown: Duration::from_secs(60),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// `attempt` 回目（0 始まり）の失敗後の待ち時間。半分固定 + 半分ランダムのジッタ。
+    fn backoff(&self, attempt: u32) -> Duration {
+        let exp = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(attempt))
+            .min(self.max_delay);
+        let half = exp / 2;
+        half + half.mul_f64(rand::random::<f64>())
+    }
+}
+
+/// チャットの再接続・配信開始待ちの間隔（指数バックオフの基準と上限）
+const RETRY_BASE_DELAY: Duration = Duration::from_secs(2);
+const RETRY_MAX_DELAY: Duration = Duration::from_secs(60);
+
+/// チャットの再接続で、`attempt` 回目（0 始まり）の失敗後に待つ時間。
+pub(crate) fn backoff(attempt: u32) -> Duration {
+    RetryPolicy {
+        base_delay: RETRY_BASE_DELAY,
+        max_delay: RETRY_MAX_DELAY,
+        ..RetryPolicy::default()
+    }
+    .backoff(attempt)
+}
+
+/// 再試行すべきステータスか。
+fn is_retryable(status: StatusCode) -> bool {
+    status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+}
+
+/// [`RetryPolicy`] に従ってリクエストを送る。レート制限とブレーカーの状態を保持する。
+#[derive(Debug)]
+pub struct Retrier {
+    policy: RetryPolicy,
+    /// 直近 1 分間の送信時刻
+    window: Mutex<VecDeque<Instant>>,
+    breaker: Mutex<Breaker>,
+}
+
+#[derive(Debug, Default)]
+struct Breaker {
+    consecutive_failures: u32,
+    open_until: Option<Instant>,
+    /// クールダウン後の試行（half-open）が送信中か
+    probing: bool,
+}
+
+impl Breaker {
+    /// クールダウン中、または half-open の試行が送信中なら `true`。
+    fn is_open(&self) -> bool {
+        match self.open_until {
+            Some(t) => Instant::now() < t || self.probing,
+            None => false,
+        }
+    }
+}
+
+/// half-open の試行枠。完了・中断のどちらでも解放する。
+struct Probe<'a>(Option<&'a Mutex<Breaker>>);
+
+impl Drop for Probe<'_> {
+    fn drop(&mut self) {
+        if let Some(breaker) = self.0 {
+            breaker.lock().unwrap().probing = false;
+        }
+    }
+}
+
+impl Retrier {
+    pub fn new(policy: RetryPolicy) -> Self {
+        Self {
+            policy,
+            window: Mutex::new(VecDeque::new()),
+            breaker: Mutex::new(Breaker::default()),
+        }
+    }
+
+    pub fn policy(&self) -> &RetryPolicy {
+        &self.policy
+    }
+
+    /// ブレーカーが開いているか。
+    ///
+    /// クールダウン経過後は 1 回だけ試行を許し、その結果が出るまでは開いたままとみなす。
+    pub fn is_open(&self) -> bool {
+        self.breaker.lock().unwrap().is_open()
+    }
+
+    /// 送信してよければ試行枠を返す。half-open なら試行中の印を付ける。
+    fn enter(&self) -> Option<Probe<'_>> {
+        let mut b = self.breaker.lock().unwrap();
+        if b.is_open() {
+            return None;
+        }
+        if b.open_until.is_none() {
+            return Some(Probe(None));
+        }
+        info!("circuit breaker half-open; sending a trial request");
+        b.probing = true;
+        Some(Probe(Some(&self.breaker)))
+    }
+
+    /// `make` でリクエストを組み立てて送信し、成功 (2xx) したレスポンスを返す。
+    ///
+    /// 再試行しても失敗した場合やブレーカーが開いている場合は
+    /// [`Error::LlmUnavailable`]、再試行対象外の 4xx はそのままエラーにする。
+    pub async fn send(&self, make: impl Fn() -> RequestBuilder) -> Result<Response> {
+        let Some(_probe) = self.enter() else {
+            return Err(Error::LlmUnavailable("circuit breaker is open".into()));
+        };
+
+        let mut last_err = String::new();
+        for attempt in 0..=self.policy.max_retries {
+            self.acquire().await;
+
+            let wait = match make().send().await {
+                Ok(res) if res.status().is_success() => {
+                    self.record_success();
+                    return Ok(res);
+                }
+                Ok(res) if is_retryable(res.status()) => {
+                    last_err = format!("HTTP {}", res.status());
+                    retry_delay(res).await
+                }
+                Ok(res) => {
+                    // 400 / 403 などはリトライしても直らない
+                    return res
+                        .error_for_status()
+                        .map_err(|e| Error::External(e.into()));
+                }
+                Err(e) => {
+                    last_err = e.to_string();
+                    None
+                }
+            };
+
+            if attempt == self.policy.max_retries {
+                break;
+            }
+            let wait = wait
+                .unwrap_or_else(|| self.policy.backoff(attempt))
+                .min(self.policy.max_delay);
+            warn!(attempt, error = %last_err, ?wait, "request failed; retrying");
+            tokio::time::sleep(wait).await;
+        }
+
+        self.record_failure();
+        Err(Error::LlmUnavailable(last_err))
+    }
+
+    /// 1 分あたりの予算に空きができるまで待つ。
+    async fn acquire(&self) {
+        let rpm = self.policy.requests_per_minute as usize;
+        if rpm == 0 {
+            return;
+        }
+        loop {
+            let wait = {
+                let mut w = self.window.lock().unwrap();
+                let now = Instant::now();
+                while w
+                    .front()
+                    .is_some_and(|t| now.duration_since(*t) >= Duration::from_secs(60))
+                {
+                    w.pop_front();
+                }
+                if w.len() < rpm {
+                    w.push_back(now);
+                    return;
+                }
+                Duration::from_secs(60) - now.duration_since(w[0])
+            };
+            info!(?wait, "request budget exhausted; waiting");
+            tokio::time::sleep(wait).await;
+        }
+    }
+
+    fn record_success(&self) {
+        let mut b = self.breaker.lock().unwrap();
+        if b.open_until.take().is_some() {
+            info!("circuit breaker closed");
+        }
+        b.consecutive_failures = 0;
+    }
+
+    fn record_failure(&self) {
+        let mut b = self.breaker.lock().unwrap();
+        b.consecutive_failures += 1;
+        let threshold = self.policy.breaker_threshold;
+        if threshold > 0 && b.consecutive_failures >= threshold {
+            b.open_until = Some(Instant::now() + self.policy.breaker_cooldown);
+            warn!(
+                failures = b.consecutive_failures,
+                cooldown = ?self.policy.breaker_cooldown,
+                "circuit breaker opened"
+            );
+        }
+    }
+}
+
+/// `Retry-After`（秒 or HTTP-date）か、Gemini のエラー本文 `retryDelay: "12s"` から待ち時間を得る。
+async fn retry_delay(res: Response) -> Option<Duration> {
+    if let Some(v) = res.headers().get(RETRY_AFTER).and_then(|v| v.to_str().ok()) {
+        if let Ok(secs) = v.trim().parse::<u64>() {
+            return Some(Duration::from_secs(secs));
+        }
+        if let Ok(at) = chrono::DateTime::parse_from_rfc2822(v.trim()) {
+            let secs = (at.timestamp() - chrono::Utc::now().timestamp()).max(0);
+            return Some(Duration::from_secs(secs as u64));
+        }
+    }
+
+    let body: serde_json::Value = res.json().await.ok()?;
+    body["error"]["details"]
+        .as_array()?
+        .iter()
+        .find_map(|d| d["retryDelay"].as_str())
+        .and_then(|s| s.strip_suffix('s')?.parse::<f64>().ok())
+        .map(Duration::from_secs_f64)
+}
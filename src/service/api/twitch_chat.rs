@@ -0,0 +1,345 @@
+//! Twitch チャット（TMI）の IRC 入力。
+//!
+//! - 平文 TCP（既定 [`DEFAULT_ADDR`]）で接続し、`twitch.tv/tags` でバッジ・Bits などのタグを受け取る。
+//! - トークンがなければ匿名（`justinfan…`）で読み取り専用に入る。
+//! - `PRIVMSG` は通常のコメント、Bits 付きはスーパーチャット相当、
+//!   `USERNOTICE` のサブスク（sub / resub / subgift）はメンバー加入として扱う。
+//! - `PING` には `PONG` を返す。切断・`RECONNECT` では待ってから接続し直し、ストリームは閉じない。
+
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    net::TcpStream,
+    sync::mpsc::{UnboundedSender, unbounded_channel},
+};
+use tokio_stream::wrappers::UnboundedReceiverStream;
+use tracing::{debug, info, warn};
+
+use crate::{
+    error::Result,
+    model::{
+        chat_event::{Badges, ChatEvent, ChatMessage, Money},
+        conversation::{Author, Platform},
+    },
+    service::api::{
+        chat_source::ChatSource,
+        llm::{BoxFuture, BoxStream},
+        retry::backoff,
+    },
+};
+
+/// Twitch の IRC サーバ（平文）
+pub const DEFAULT_ADDR: &str = "irc.chat.twitch.tv:6667";
+
+/// これ以上続いた接続が切れたら、再接続のバックオフを最初からにする
+const STABLE_CONNECTION: Duration = Duration::from_secs(30);
+
+/// 匿名接続のニックネーム
+const ANONYMOUS_NICK: &str = "justinfan12345";
+
+/// [`ChatSource`] としての Twitch チャット。
+#[derive(Debug, Clone)]
+pub struct TwitchChat {
+    addr: String,
+    channel: String,
+    /// `(nick, oauth token)`。`None` なら匿名
+    login: Option<(String, String)>,
+}
+
+impl TwitchChat {
+    /// `addr`（`host:port`）の `channel` に匿名で入る。
+    pub fn new(addr: impl Into<String>, channel: &str) -> Self {
+        Self {
+            addr: addr.into(),
+            channel: channel.trim_start_matches('#').to_lowercase(),
+            login: None,
+        }
+    }
+
+    /// ボットのアカウントでログインする（`oauth:` は省略可）。
+    pub fn with_login(mut self, nick: &str, token: &str) -> Self {
+        let token = token.strip_prefix("oauth:").unwrap_or(token);
+        self.login = Some((nick.to_lowercase(), format!("oauth:{token}")));
+        self
+    }
+
+    fn handshake(&self) -> String {
+        let mut out = String::from("CAP REQ :twitch.tv/tags twitch.tv/commands\r\n");
+        match &self.login {
+            Some((nick, token)) => out.push_str(&format!("PASS {token}\r\nNICK {nick}\r\n")),
+            None => out.push_str(&format!("NICK {ANONYMOUS_NICK}\r\n")),
+        }
+        out.push_str(&format!("JOIN #{}\r\n", self.channel));
+        out
+    }
+
+    /// 接続してログイン・入室まで済ませる。
+    async fn connect(&self) -> Result<TcpStream> {
+        let mut conn = TcpStream::connect(&self.addr)
+            .await
+            .with_context(|| format!("failed to connect to Twitch IRC {}", self.addr))?;
+        conn.write_all(self.handshake().as_bytes())
+            .await
+            .context("failed to log in to Twitch IRC")?;
+        Ok(conn)
+    }
+}
+
+impl ChatSource for TwitchChat {
+    fn name(&self) -> &str {
+        "twitch"
+    }
+
+    fn subscribe(&self) -> BoxFuture<'_, Result<BoxStream<'static, ChatEvent>>> {
+        Box::pin(async move {
+            // 最初の接続だけは失敗をそのまま返す
+            let conn = self.connect().await?;
+            let (tx, rx) = unbounded_channel();
+            let chat = self.clone();
+            tokio::spawn(async move {
+                let mut conn = Some(conn);
+                let mut attempt = 0;
+                while !tx.is_closed() {
+                    let current = match conn.take() {
+                        Some(c) => c,
+                        None => match chat.connect().await {
+                            Ok(c) => c,
+                            Err(e) => {
+                                warn!(error = %e, attempt, "Twitch IRC reconnect failed");
+                                tokio::time::sleep(backoff(attempt)).await;
+                                attempt += 1;
+                                continue;
+                            }
+                        },
+                    };
+                    let connected_at = Instant::now();
+                    if !read_messages(current, &tx).await {
+                        break;
+                    }
+                    // すぐ切られる接続（認証失敗・BAN など）では待ち時間を延ばし続ける
+                    if connected_at.elapsed() >= STABLE_CONNECTION {
+                        attempt = 0;
+                    }
+                    info!(attempt, "Twitch IRC disconnected; reconnecting");
+                    tokio::time::sleep(backoff(attempt)).await;
+                    attempt += 1;
+                }
+                debug!("Twitch IRC stream closed");
+            });
+
+            let stream: BoxStream<'static, ChatEvent> = Box::pin(UnboundedReceiverStream::new(rx));
+            Ok(stream)
+        })
+    }
+}
+
+/// 1 接続ぶんを読む。受け手がいなくなったら `false`、切断・`RECONNECT` なら `true`。
+async fn read_messages(conn: TcpStream, tx: &UnboundedSender<ChatEvent>) -> bool {
+    let (read, mut write) = conn.into_split();
+    let mut lines = BufReader::new(read).lines();
+    loop {
+        let line = match lines.next_line().await {
+            Ok(Some(line)) => line,
+            Ok(None) => return true,
+            Err(e) => {
+                warn!(error = %e, "Twitch IRC read failed");
+                return true;
+            }
+        };
+        let Some(msg) = IrcMessage::parse(&line) else {
+            continue;
+        };
+        match msg.command {
+            "PING" => {
+                let pong = format!("PONG :{}\r\n", msg.params.last().unwrap_or(&""));
+                if write.write_all(pong.as_bytes()).await.is_err() {
+                    return true;
+                }
+            }
+            "RECONNECT" => {
+                info!("Twitch IRC asked to reconnect");
+                return true;
+            }
+            "NOTICE" => warn!(
+                notice = msg.params.last().unwrap_or(&""),
+                "Twitch IRC notice"
+            ),
+            _ => {
+                if let Some(event) = msg.to_event()
+                    && tx.send(event).is_err()
+                {
+                    return false;
+                }
+            }
+        }
+    }
+}
+
+/// IRC の 1 行（IRCv3 タグ付き）。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IrcMessage<'a> {
+    /// エスケープを戻したタグ
+    pub tags: HashMap<&'a str, String>,
+    pub prefix: Option<&'a str>,
+    pub command: &'a str,
+    /// 末尾の `:` 以降も 1 要素として含む
+    pub params: Vec<&'a str>,
+}
+
+impl<'a> IrcMessage<'a> {
+    /// `@tags :prefix COMMAND params :trailing` を読む。
+    pub fn parse(line: &'a str) -> Option<Self> {
+        let mut rest = line.trim_end_matches(['\r', '\n']);
+        let mut tags = HashMap::new();
+        if let Some(r) = rest.strip_prefix('@') {
+            let (raw, r) = r.split_once(' ')?;
+            for tag in raw.split(';') {
+                let (key, value) = tag.split_once('=').unwrap_or((tag, ""));
+                tags.insert(key, unescape(value));
+            }
+            rest = r;
+        }
+        let mut prefix = None;
+        if let Some(r) = rest.strip_prefix(':') {
+            let (p, r) = r.split_once(' ')?;
+            prefix = Some(p);
+            rest = r;
+        }
+        let (head, trailing) = match rest.split_once(" :") {
+            Some((head, trailing)) => (head, Some(trailing)),
+            None => (rest, None),
+        };
+        let mut words = head.split(' ').filter(|w| !w.is_empty());
+        let command = words.next()?;
+        let mut params: Vec<&str> = words.collect();
+        params.extend(trailing);
+        Some(Self {
+            tags,
+            prefix,
+            command,
+            params,
+        })
+    }
+
+    fn tag(&self, key: &str) -> Option<&str> {
+        self.tags
+            .get(key)
+            .map(String::as_str)
+            .filter(|v| !v.is_empty())
+    }
+
+    /// コメント・Bits・サブスクを [`ChatEvent`] にする。それ以外は `None`。
+    pub fn to_event(&self) -> Option<ChatEvent> {
+        match self.command {
+            "PRIVMSG" => {
+                let message = self.chat_message()?;
+                match self.tag("bits").and_then(|b| b.parse::<u32>().ok()) {
+                    Some(bits) => Some(ChatEvent::SuperChat {
+                        message,
+                        amount: Money {
+                            amount: f64::from(bits),
+                            currency: "BITS".into(),
+                            display: format!("{bits} Bits"),
+                        },
+                    }),
+                    None => Some(ChatEvent::Message(message)),
+                }
+            }
+            "USERNOTICE" => {
+                let mut message = self.chat_message()?;
+                match self.tag("msg-id")? {
+                    "sub" | "resub" => {}
+                    // ギフトは受け取った人の加入として扱う
+                    "subgift" => {
+                        let name = self.tag("msg-param-recipient-display-name")?;
+                        message.author = Author::new(name, Platform::Twitch);
+                        if let Some(id) = self.tag("msg-param-recipient-id") {
+                            message.author = message.author.with_channel_id(id);
+                        }
+                        message.badges = Badges::default();
+                        message.text.clear();
+                    }
+                    _ => return None,
+                }
+                let tier = self.tag("msg-param-sub-plan").map(|plan| match plan {
+                    "1000" => "Tier 1".to_owned(),
+                    "2000" => "Tier 2".to_owned(),
+                    "3000" => "Tier 3".to_owned(),
+                    other => other.to_owned(),
+                });
+                Some(ChatEvent::Membership { message, tier })
+            }
+            _ => None,
+        }
+    }
+
+    fn chat_message(&self) -> Option<ChatMessage> {
+        let nick = self.prefix.and_then(|p| p.split('!').next());
+        let name = self.tag("display-name").or(self.tag("login")).or(nick)?;
+        let mut author = Author::new(name, Platform::Twitch);
+        if let Some(id) = self.tag("user-id") {
+            author = author.with_channel_id(id);
+        }
+
+        // PRIVMSG #channel :text（USERNOTICE は本文がないこともある）
+        let text = match self.params.as_slice() {
+            [_, text, ..] => text,
+            _ => "",
+        };
+        // /me は \x01ACTION ...\x01 で届く
+        let text = text
+            .strip_prefix("\u{1}ACTION ")
+            .map_or(text, |t| t.trim_end_matches('\u{1}'));
+
+        let badges: Vec<&str> = self
+            .tag("badges")
+            .unwrap_or_default()
+            .split(',')
+            .filter_map(|b| b.split('/').next())
+            .collect();
+        let has = |name: &str| badges.contains(&name);
+        Some(ChatMessage {
+            id: self.tag("id").unwrap_or_default().to_owned(),
+            author,
+            badges: Badges {
+                owner: has("broadcaster"),
+                moderator: has("moderator") || self.tag("mod") == Some("1"),
+                member: has("subscriber") || has("founder"),
+                verified: has("partner"),
+            },
+            text: text.to_owned(),
+            timestamp: self
+                .tag("tmi-sent-ts")
+                .and_then(|ts| ts.parse().ok())
+                .and_then(DateTime::from_timestamp_millis)
+                .unwrap_or_else(Utc::now),
+        })
+    }
+}
+
+/// IRCv3 のタグ値のエスケープを戻す。
+fn unescape(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some(':') => out.push(';'),
+            Some('s') => out.push(' '),
+            Some('r') => out.push('\r'),
+            Some('n') => out.push('\n'),
+            Some(other) => out.push(other),
+            None => {}
+        }
+    }
+    out
+}
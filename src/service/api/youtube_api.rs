@@ -0,0 +1,452 @@
+//! YouTube Data API v3 の `liveChatMessages` を使うチャット入力と投稿。
+//!
+//! - `liveChatMessages.list` を `pollingIntervalMillis` の間隔で、`nextPageToken` をたどって取得する。
+//! - 最初のページは接続前の過去ログなので流さない。
+//! - `liveChatMessages.insert` で返答・お知らせをチャットに投稿できる（スクレイピング版ではできない）。
+//!   投稿したメッセージは取得結果にも含まれるので、返ってきた ID と投稿したチャンネルで除いて自分に答えないようにする。
+//!   読み取りと投稿には同じインスタンス（のクローン）を使う。
+//! - 認証は OAuth のリフレッシュトークン。アクセストークンは期限の少し前に取り直す。
+//! - チャット ID を指定しなければ、認証したアカウントの配信中の枠（`liveBroadcasts`）から探す。
+//! - ベース URL・トークン URL は差し替えられる（ローカルの代用サーバでの検証用）。
+
+use std::{
+    collections::HashSet,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use anyhow::{Context, anyhow};
+use chrono::Utc;
+use reqwest::{Client, RequestBuilder, StatusCode};
+use tokio::{
+    sync::{Mutex, OnceCell, broadcast, mpsc::unbounded_channel},
+    time::sleep,
+};
+use tokio_stream::{Stream, wrappers::UnboundedReceiverStream};
+use tracing::{debug, info, warn};
+
+use crate::{
+    error::{Error, Result},
+    model::{
+        chat_event::{Badges, ChatEvent, ChatMessage, Money},
+        conversation::{Author, Platform},
+        youtube_dto::{self, LiveChatMessage, LiveChatMessageList},
+    },
+    service::api::{
+        chat_source::{ChatSource, Lifecycle},
+        llm::{BoxFuture, BoxStream},
+        retry::backoff,
+    },
+};
+
+/// 公式 API のベース URL
+pub const DEFAULT_BASE_URL: &str = "https://www.googleapis.com/youtube/v3";
+/// Google の OAuth トークンエンドポイント
+pub const DEFAULT_TOKEN_URL: &str = "https://oauth2.googleapis.com/token";
+
+/// 投稿できる最大文字数（API の上限）
+const MAX_POST_CHARS: usize = 200;
+
+/// `pollingIntervalMillis` がないときの取得間隔
+const DEFAULT_POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// アクセストークンの期限のこれだけ前に取り直す
+const TOKEN_MARGIN: Duration = Duration::from_secs(60);
+
+/// 接続先と認証情報。
+#[derive(Debug, Clone)]
+pub struct YouTubeApiConfig {
+    /// `.../youtube/v3` まで
+    pub base_url: String,
+    pub token_url: String,
+    pub client_id: String,
+    pub client_secret: String,
+    pub refresh_token: String,
+    /// 未指定なら配信中の枠から探す
+    pub live_chat_id: Option<String>,
+    /// 返答をチャットにも投稿する
+    pub post_replies: bool,
+}
+
+/// [`ChatSource`] としての YouTube Data API。クローンはトークンとチャット ID を共有する。
+#[derive(Debug, Clone)]
+pub struct YouTubeApi {
+    inner: Arc<Inner>,
+}
+
+#[derive(Debug)]
+struct Inner {
+    client: Client,
+    cfg: YouTubeApiConfig,
+    /// `(アクセストークン, 取り直す時刻)`
+    token: Mutex<Option<(String, Instant)>>,
+    live_chat_id: OnceCell<String>,
+    /// 投稿に使うアカウントのチャンネル ID（最初の投稿の前に調べる）。
+    /// 配信者のアカウントで投稿するなら、配信者自身のコメントも読まない
+    own_channel_id: OnceCell<String>,
+    /// 投稿して、まだ取得結果に現れていないメッセージの ID
+    posted: Mutex<HashSet<String>>,
+    lifecycle: broadcast::Sender<Lifecycle>,
+}
+
+impl YouTubeApi {
+    pub fn new(cfg: YouTubeApiConfig) -> Result<Self> {
+        let client = Client::builder()
+            .timeout(Duration::from_secs(20))
+            .user_agent(concat!("ai_tuber/", env!("CARGO_PKG_VERSION")))
+            .build()
+            .context("build reqwest client")?;
+        let live_chat_id = OnceCell::new_with(cfg.live_chat_id.clone());
+        let (lifecycle, _) = broadcast::channel(16);
+        Ok(Self {
+            inner: Arc::new(Inner {
+                client,
+                cfg,
+                token: Mutex::new(None),
+                live_chat_id,
+                own_channel_id: OnceCell::new(),
+                posted: Mutex::new(HashSet::new()),
+                lifecycle,
+            }),
+        })
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}/{path}", self.inner.cfg.base_url.trim_end_matches('/'))
+    }
+
+    /// 有効なアクセストークン（期限が近ければリフレッシュトークンで取り直す）。
+    async fn access_token(&self) -> Result<String> {
+        let mut token = self.inner.token.lock().await;
+        if let Some((access, refresh_at)) = token.as_ref()
+            && Instant::now() < *refresh_at
+        {
+            return Ok(access.clone());
+        }
+        let cfg = &self.inner.cfg;
+        let res: youtube_dto::TokenRes = self
+            .inner
+            .client
+            .post(&cfg.token_url)
+            .form(&[
+                ("grant_type", "refresh_token"),
+                ("client_id", &cfg.client_id),
+                ("client_secret", &cfg.client_secret),
+                ("refresh_token", &cfg.refresh_token),
+            ])
+            .send()
+            .await
+            .context("POST OAuth token")?
+            .error_for_status()
+            .context("OAuth token refresh failed")?
+            .json()
+            .await
+            .context("parse OAuth token json")?;
+        let lifetime = Duration::from_secs(res.expires_in).saturating_sub(TOKEN_MARGIN);
+        debug!(
+            expires_in = res.expires_in,
+            "YouTube access token refreshed"
+        );
+        *token = Some((res.access_token.clone(), Instant::now() + lifetime));
+        Ok(res.access_token)
+    }
+
+    async fn authorized(&self, builder: RequestBuilder) -> Result<reqwest::Response> {
+        Ok(builder
+            .bearer_auth(self.access_token().await?)
+            .send()
+            .await
+            .context("YouTube API request failed")?)
+    }
+
+    /// 読み書きするライブチャットの ID。
+    pub async fn live_chat_id(&self) -> Result<&str> {
+        let id = self
+            .inner
+            .live_chat_id
+            .get_or_try_init(|| async {
+                let list: youtube_dto::LiveBroadcastList = self
+                    .authorized(self.inner.client.get(self.url("liveBroadcasts")).query(&[
+                        ("part", "snippet"),
+                        ("broadcastStatus", "active"),
+                        ("broadcastType", "all"),
+                    ]))
+                    .await?
+                    .error_for_status()
+                    .context("GET liveBroadcasts")?
+                    .json()
+                    .await
+                    .context("parse liveBroadcasts json")?;
+                list.items
+                    .into_iter()
+                    .find_map(|b| b.snippet.live_chat_id)
+                    .ok_or_else(|| Error::External(anyhow!("no active live broadcast")))
+            })
+            .await?;
+        Ok(id)
+    }
+
+    /// 投稿に使う（認証した）アカウントのチャンネル ID。
+    async fn own_channel_id(&self) -> Result<&str> {
+        let id = self
+            .inner
+            .own_channel_id
+            .get_or_try_init(|| async {
+                let list: youtube_dto::ChannelList = self
+                    .authorized(
+                        self.inner
+                            .client
+                            .get(self.url("channels"))
+                            .query(&[("part", "id"), ("mine", "true")]),
+                    )
+                    .await?
+                    .error_for_status()
+                    .context("GET channels")?
+                    .json()
+                    .await
+                    .context("parse channels json")?;
+                list.items.into_iter().next().map(|c| c.id).ok_or_else(|| {
+                    Error::External(anyhow!("no channel for the authorized account"))
+                })
+            })
+            .await?;
+        Ok(id)
+    }
+
+    /// 1 ページ取得する。チャットが終わって取得できなければ `None`。
+    async fn list(&self, page_token: Option<&str>) -> Result<Option<LiveChatMessageList>> {
+        let chat_id = self.live_chat_id().await?;
+        let mut query = vec![
+            ("liveChatId", chat_id),
+            ("part", "snippet,authorDetails"),
+            ("maxResults", "2000"),
+        ];
+        query.extend(page_token.map(|t| ("pageToken", t)));
+        let res = self
+            .authorized(
+                self.inner
+                    .client
+                    .get(self.url("liveChat/messages"))
+                    .query(&query),
+            )
+            .await?;
+
+        // 終了・無効化されたチャットは 403 / 404 で理由が返る
+        let status = res.status();
+        if matches!(status, StatusCode::FORBIDDEN | StatusCode::NOT_FOUND) {
+            let body = res.text().await.unwrap_or_default();
+            if ["liveChatEnded", "liveChatNotFound", "liveChatDisabled"]
+                .iter()
+                .any(|reason| body.contains(reason))
+            {
+                return Ok(None);
+            }
+            return Err(Error::External(anyhow!(
+                "GET liveChat/messages: HTTP {status}: {body}"
+            )));
+        }
+        let list = res
+            .error_for_status()
+            .context("GET liveChat/messages")?
+            .json()
+            .await
+            .context("parse liveChat/messages json")?;
+        Ok(Some(list))
+    }
+
+    /// チャットに投稿する（長すぎる文は切り詰める）。
+    pub async fn post(&self, text: &str) -> Result<()> {
+        let text = text.trim();
+        let text = match text.char_indices().nth(MAX_POST_CHARS) {
+            Some((end, _)) => &text[..end],
+            None => text,
+        };
+        if text.is_empty() {
+            return Ok(());
+        }
+        let chat_id = self.live_chat_id().await?;
+        // 投稿の応答より先に取得結果に現れても除けるよう、チャンネルを先に調べておく
+        self.own_channel_id().await?;
+        let body = youtube_dto::InsertReq {
+            snippet: youtube_dto::InsertSnippet {
+                live_chat_id: chat_id,
+                kind: "textMessageEvent",
+                text_message_details: youtube_dto::InsertText { message_text: text },
+            },
+        };
+        let res: youtube_dto::InsertRes = self
+            .authorized(
+                self.inner
+                    .client
+                    .post(self.url("liveChat/messages"))
+                    .query(&[("part", "snippet")])
+                    .json(&body),
+            )
+            .await?
+            .error_for_status()
+            .context("POST liveChat/messages")?
+            .json()
+            .await
+            .context("parse liveChat/messages insert json")?;
+        self.inner.posted.lock().await.insert(res.id);
+        Ok(())
+    }
+
+    /// チャットを取得し続ける。状態の変化は [`ChatSource::lifecycle`] に流す。
+    pub fn poll(&self) -> impl Stream<Item = ChatEvent> + Send + 'static {
+        let (tx, rx) = unbounded_channel();
+        let api = self.clone();
+        tokio::spawn(async move {
+            let mut state = None;
+            let mut set = |next: Lifecycle| {
+                if state != Some(next) {
+                    info!(state = %next, "YouTube API chat lifecycle");
+                    state = Some(next);
+                    let _ = api.inner.lifecycle.send(next);
+                }
+            };
+            let mut page_token: Option<String> = None;
+            let mut attempt = 0;
+            let mut was_live = false;
+
+            while !tx.is_closed() {
+                let list = match api.list(page_token.as_deref()).await {
+                    Ok(Some(list)) => list,
+                    Ok(None) => {
+                        set(Lifecycle::Ended);
+                        break;
+                    }
+                    Err(e) => {
+                        warn!(error = %e, attempt, "YouTube API chat poll failed");
+                        set(if was_live {
+                            Lifecycle::Reconnecting
+                        } else {
+                            Lifecycle::Waiting
+                        });
+                        sleep(backoff(attempt)).await;
+                        attempt += 1;
+                        continue;
+                    }
+                };
+                set(Lifecycle::Live);
+                attempt = 0;
+                let ended = list.offline_at.is_some()
+                    || list
+                        .items
+                        .iter()
+                        .any(|m| m.snippet.kind == "chatEndedEvent");
+                let own = api.inner.own_channel_id.get();
+                let mut posted = api.inner.posted.lock().await;
+                for item in list.items {
+                    // 自分の投稿には答えない。最初のページは接続前の過去ログ
+                    if posted.remove(&item.id)
+                        || own == Some(&item.author_details.channel_id)
+                        || !was_live
+                    {
+                        continue;
+                    }
+                    if let Some(event) = to_event(item)
+                        && tx.send(event).is_err()
+                    {
+                        return;
+                    }
+                }
+                drop(posted);
+                if ended {
+                    set(Lifecycle::Ended);
+                    break;
+                }
+                was_live = true;
+                page_token = list.next_page_token.or(page_token);
+                sleep(
+                    list.polling_interval_millis
+                        .map_or(DEFAULT_POLL_INTERVAL, Duration::from_millis),
+                )
+                .await;
+            }
+        });
+        UnboundedReceiverStream::new(rx)
+    }
+}
+
+impl ChatSource for YouTubeApi {
+    fn name(&self) -> &str {
+        "youtube_api"
+    }
+
+    fn subscribe(&self) -> BoxFuture<'_, Result<BoxStream<'static, ChatEvent>>> {
+        Box::pin(async move {
+            let stream: BoxStream<'static, ChatEvent> = Box::pin(self.poll());
+            Ok(stream)
+        })
+    }
+
+    fn lifecycle(&self) -> Option<broadcast::Receiver<Lifecycle>> {
+        Some(self.inner.lifecycle.subscribe())
+    }
+}
+
+/// `liveChatMessages` の 1 件を [`ChatEvent`] にする。削除通知などは `None`。
+pub fn to_event(item: LiveChatMessage) -> Option<ChatEvent> {
+    let snippet = item.snippet;
+    let author = item.author_details;
+    let text = |comment: Option<String>| comment.unwrap_or_default();
+    let mut message = ChatMessage {
+        id: item.id,
+        author: Author::new(author.display_name, Platform::YouTube)
+            .with_channel_id(author.channel_id),
+        badges: Badges {
+            owner: author.is_chat_owner,
+            moderator: author.is_chat_moderator,
+            member: author.is_chat_sponsor,
+            verified: author.is_verified,
+        },
+        text: String::new(),
+        timestamp: snippet.published_at.unwrap_or_else(Utc::now),
+    };
+    let event = match snippet.kind.as_str() {
+        "textMessageEvent" => {
+            message.text = snippet
+                .text_message_details
+                .map(|d| d.message_text)
+                .or(snippet.display_message)?;
+            ChatEvent::Message(message)
+        }
+        "superChatEvent" => {
+            let details = snippet.super_chat_details?;
+            message.text = text(details.user_comment);
+            ChatEvent::SuperChat {
+                message,
+                amount: Money::parse(&details.amount_display_string),
+            }
+        }
+        "superStickerEvent" => {
+            let details = snippet.super_sticker_details?;
+            ChatEvent::SuperSticker {
+                message,
+                amount: Money::parse(&details.amount_display_string),
+                sticker: details
+                    .super_sticker_metadata
+                    .map(|m| m.alt_text)
+                    .unwrap_or_default(),
+            }
+        }
+        "newSponsorEvent" => ChatEvent::Membership {
+            message,
+            tier: snippet
+                .new_sponsor_details
+                .and_then(|d| d.member_level_name),
+        },
+        // 継続のお祝いも加入と同じく歓迎する（コメント付き）
+        "memberMilestoneChatEvent" => {
+            let details = snippet.member_milestone_chat_details?;
+            message.text = text(details.user_comment);
+            ChatEvent::Membership {
+                message,
+                tier: details.member_level_name,
+            }
+        }
+        _ => return None,
+    };
+    Some(event)
+}
@@ -1,71 +1,375 @@
-//! YouTube Live のチャットを `(author, message)` で非同期ストリーム化する。
+//! YouTube Live のチャットを [`ChatEvent`] の非同期ストリームにする。
+//!
+//! - 配信ページを確かめてから接続する。予約枠・未配信なら開始まで待つ。
+//! - 接続・取得に失敗したら指数バックオフでつなぎ直す。
+//! - 配信の終了を検知したらストリームを閉じる。
+//! - 状態の変化は [`Lifecycle`] として流す。
+//!
+//! ```text
+//! Waiting ──(配信開始)──▶ Live ──(取得失敗が続く)──▶ Reconnecting ──▶ Live
+//!                          │                              │
+//!                          └──────(配信終了)──────────────┴──▶ Ended
+//! ```
 
-use std::time::Duration;
+use std::{
+    sync::{
+        Arc,
+        atomic::{AtomicU32, Ordering},
+    },
+    time::Duration,
+};
 
 use anyhow::Context;
-use async_stream::stream;
+use chrono::Utc;
+use once_cell::sync::Lazy;
+use regex::Regex;
 use tokio::{
-    sync::mpsc::{UnboundedSender, unbounded_channel},
-    time::interval,
+    sync::{
+        broadcast,
+        mpsc::{UnboundedSender, unbounded_channel},
+    },
+    time::{interval, sleep},
 };
 use tokio_stream::{Stream, wrappers::UnboundedReceiverStream};
-use tracing::warn;
-use youtube_chat::{item::MessageItem, live_chat::LiveChatClientBuilder};
+use tracing::{info, warn};
+use youtube_chat::{
+    item::{ChatItem, MessageItem, SuperChat},
+    live_chat::LiveChatClientBuilder,
+};
 
-use crate::error::Result;
+use crate::{
+    error::Result,
+    model::{
+        chat_event::{Badges, ChatEvent, ChatMessage, Money},
+        conversation::{Author, Platform},
+    },
+    service::api::{
+        chat_source::{ChatSource, Lifecycle},
+        llm::{BoxFuture, BoxStream},
+        retry::backoff,
+    },
+};
 
 /// ポーリング間隔（YouTube 制限を考慮して 3 秒）
 const POLL_INTERVAL: Duration = Duration::from_secs(3);
 
-/// 指定 URL のライブチャットを購読し、投稿 `(author, text)` を返す。
-pub async fn subscribe(url: &str) -> Result<impl Stream<Item = (String, String)> + Send + 'static> {
-    let (tx, rx) = unbounded_channel::<(String, String)>();
+/// 取得がこの回数続けて失敗したら、配信ページを確かめ直す
+const MAX_POLL_ERRORS: u32 = 5;
 
-    /* ---------- LiveChatClient ---------- */
-    let mut client = LiveChatClientBuilder::new()
-        .url(url)
-        .context("invalid YouTube live URL")?
-        .on_chat(move |chat| forward_chat(&tx, chat))
-        .on_error(|e| warn!(error = ?e, "YouTube chat error"))
-        .build();
+/// 配信ページに埋め込まれた状態のフラグ
+static PAGE_FLAG: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r#"['"](isLiveNow|isUpcoming|isReplay)['"]\s*:\s*(true|false)"#).unwrap()
+});
 
-    client.start().await.context("start YouTube chat client")?;
+/// 配信ページに埋め込まれたチャンネル名（JSON の文字列リテラルのまま）
+static CHANNEL_NAME: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r#""ownerChannelName"\s*:\s*("(?:[^"\\]|\\.)*")"#).unwrap());
+
+/// [`ChatSource`] としての YouTube Live。
+#[derive(Debug, Clone)]
+pub struct YouTubeChat {
+    url: String,
+    owner_channel_id: Option<String>,
+    lifecycle: broadcast::Sender<Lifecycle>,
+}
+
+impl YouTubeChat {
+    pub fn new(url: impl Into<String>) -> Self {
+        let (lifecycle, _) = broadcast::channel(16);
+        Self {
+            url: url.into(),
+            owner_channel_id: None,
+            lifecycle,
+        }
+    }
+
+    /// 配信者のチャンネル ID。このチャンネルの投稿だけを配信者として扱う。
+    pub fn with_owner_channel_id(mut self, id: Option<String>) -> Self {
+        self.owner_channel_id = id;
+        self
+    }
+}
+
+impl ChatSource for YouTubeChat {
+    fn name(&self) -> &str {
+        "youtube"
+    }
+
+    fn subscribe(&self) -> BoxFuture<'_, Result<BoxStream<'static, ChatEvent>>> {
+        Box::pin(async move {
+            let stream: BoxStream<'static, ChatEvent> = Box::pin(subscribe(
+                &self.url,
+                self.owner_channel_id.clone(),
+                self.lifecycle.clone(),
+            ));
+            Ok(stream)
+        })
+    }
+
+    fn lifecycle(&self) -> Option<broadcast::Receiver<Lifecycle>> {
+        Some(self.lifecycle.subscribe())
+    }
+}
+
+/// 配信ページから読み取った状態。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PageState {
+    Live,
+    /// 予約枠（開始前）
+    Upcoming,
+    /// 配信済み（アーカイブ）
+    Ended,
+    /// 配信が見つからない（`/live` の URL で未配信など）
+    Offline,
+}
+
+/// 配信ページの HTML から状態を読む。
+pub fn page_state(html: &str) -> PageState {
+    let flag = |name: &str| {
+        PAGE_FLAG
+            .captures_iter(html)
+            .find(|c| &c[1] == name)
+            .map(|c| &c[2] == "true")
+    };
+    if flag("isLiveNow") == Some(true) {
+        PageState::Live
+    } else if flag("isUpcoming") == Some(true) {
+        PageState::Upcoming
+    } else if flag("isReplay") == Some(true) || flag("isLiveNow") == Some(false) {
+        PageState::Ended
+    } else {
+        PageState::Offline
+    }
+}
+
+/// 配信ページの HTML からチャンネル名を読む。
+pub fn channel_name(html: &str) -> Option<String> {
+    let literal = CHANNEL_NAME.captures(html)?.get(1)?.as_str();
+    serde_json::from_str(literal).ok()
+}
+
+/// 指定 URL のライブチャットを購読し、イベントを返す。
+///
+/// 配信の開始を待ち、切れたらつなぎ直す。状態が変わるたびに `lifecycle` へ送り、
+/// 配信が終わるとストリームを閉じる。
+pub fn subscribe(
+    url: &str,
+    owner_channel_id: Option<String>,
+    lifecycle: broadcast::Sender<Lifecycle>,
+) -> impl Stream<Item = ChatEvent> + Send + 'static {
+    let (tx, rx) = unbounded_channel::<ChatEvent>();
+    let url = url.to_owned();
+    let owner: Option<Arc<str>> = owner_channel_id.map(Into::into);
 
-    /* ---------- Poll loop ---------- */
     tokio::spawn(async move {
-        let mut ticker = interval(POLL_INTERVAL);
-        loop {
-            ticker.tick().await;
-            client.execute().await; // 返り値は ()
+        let mut state = None;
+        let mut set = |next: Lifecycle| {
+            if state != Some(next) {
+                info!(%url, state = %next, "YouTube chat lifecycle");
+                state = Some(next);
+                let _ = lifecycle.send(next);
+            }
+        };
+        let mut attempt = 0;
+        let mut was_live = false;
+        let mut channel: Option<Arc<str>> = None;
+
+        while !tx.is_closed() {
+            let page = match probe(&url).await {
+                Ok((page, name)) => {
+                    if let Some(name) = name {
+                        channel = Some(name.into());
+                    }
+                    page
+                }
+                Err(e) => {
+                    warn!(error = %e, "failed to check YouTube live page");
+                    // 状態がわからないときは配信中とみなして接続を試す
+                    PageState::Live
+                }
+            };
+            match page {
+                PageState::Live => {
+                    match run(&url, owner.clone(), channel.clone(), &tx, &mut set).await {
+                        Ok(()) => {
+                            was_live = true;
+                            attempt = 0;
+                            set(Lifecycle::Reconnecting);
+                            continue;
+                        }
+                        // 配信中なのにつながらないときは、開始前でもつなぎ直し中とする
+                        Err(e) => {
+                            warn!(error = %e, attempt, "YouTube chat connect failed");
+                            set(Lifecycle::Reconnecting);
+                        }
+                    }
+                }
+                // `/live` の URL は配信が終わると配信ページではなくなる
+                PageState::Ended | PageState::Offline if was_live || page == PageState::Ended => {
+                    set(Lifecycle::Ended);
+                    break;
+                }
+                PageState::Upcoming | PageState::Offline | PageState::Ended => set(if was_live {
+                    Lifecycle::Reconnecting
+                } else {
+                    Lifecycle::Waiting
+                }),
+            }
+            sleep(backoff(attempt)).await;
+            attempt += 1;
         }
     });
 
-    /* ---------- Receiver stream ---------- */
-    Ok(stream! {
-        for await msg in UnboundedReceiverStream::new(rx) {
-            yield msg;
-        }
-    })
+    UnboundedReceiverStream::new(rx)
 }
 
 /* --------------------------------------------------------------------- */
 /*                             helpers                                   */
 /* --------------------------------------------------------------------- */
 
-/// `chat` から `(author, text)` を抽出して送信
-fn forward_chat(tx: &UnboundedSender<(String, String)>, chat: youtube_chat::item::ChatItem) {
-    let author = chat.author.name.clone().unwrap_or_default();
+/// 配信ページを取得して状態とチャンネル名を読む。
+async fn probe(url: &str) -> Result<(PageState, Option<String>)> {
+    let html = reqwest::get(url)
+        .await
+        .and_then(|r| r.error_for_status())
+        .with_context(|| format!("failed to fetch {url}"))?
+        .text()
+        .await
+        .context("failed to read YouTube live page")?;
+    Ok((page_state(&html), channel_name(&html)))
+}
+
+/// 接続してチャットを流す。取得の失敗が続くか、受け取り側が閉じたら `Ok` で戻る。
+async fn run(
+    url: &str,
+    owner: Option<Arc<str>>,
+    channel: Option<Arc<str>>,
+    tx: &UnboundedSender<ChatEvent>,
+    set: &mut impl FnMut(Lifecycle),
+) -> Result<()> {
+    let errors = Arc::new(AtomicU32::new(0));
+    let mut client = LiveChatClientBuilder::new()
+        .url(url)
+        .context("invalid YouTube live URL")?
+        .on_chat({
+            let tx = tx.clone();
+            move |chat| forward_chat(&tx, chat, owner.as_deref(), channel.as_deref())
+        })
+        .on_error({
+            let errors = errors.clone();
+            move |e| {
+                warn!(error = ?e, "YouTube chat error");
+                errors.fetch_add(1, Ordering::Relaxed);
+            }
+        })
+        .build();
+
+    client.start().await.context("start YouTube chat client")?;
+    set(Lifecycle::Live);
+
+    let mut ticker = interval(POLL_INTERVAL);
+    while !tx.is_closed() {
+        ticker.tick().await;
+        let before = errors.load(Ordering::Relaxed);
+        client.execute().await; // 返り値は ()
+        match errors.load(Ordering::Relaxed) {
+            n if n == before => errors.store(0, Ordering::Relaxed),
+            n if n >= MAX_POLL_ERRORS => break,
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// `chat` をイベントに変換して送信
+fn forward_chat(
+    tx: &UnboundedSender<ChatEvent>,
+    chat: ChatItem,
+    owner: Option<&str>,
+    channel: Option<&str>,
+) {
+    if let Some(event) = to_event(chat, owner, channel) {
+        let _ = tx.send(event);
+    }
+}
 
+/// [`ChatItem`] を [`ChatEvent`] に変換する。投稿者名も中身もないものは `None`。
+///
+/// `youtube_chat` はメンバー加入を通常の投稿と同じ形で返すので、
+/// メンバーバッジ付きで、本文がチャンネル名入りの加入メッセージ（`headerSubText`）
+/// そのものの場合だけ加入とみなす。チャンネル名がわからなければ加入は判定しない。
+/// また配信者・モデレーター・認証済みの区別がなく、いずれも `is_owner` になるので、
+/// `is_owner` はモデレーター扱いに留め、配信者は `owner_channel_id` との一致で判定する。
+pub fn to_event(
+    chat: ChatItem,
+    owner_channel_id: Option<&str>,
+    channel_name: Option<&str>,
+) -> Option<ChatEvent> {
+    let name = chat.author.name.clone().unwrap_or_default();
     let text = chat
         .message
         .iter()
         .filter_map(|m| match m {
             MessageItem::Text(t) => Some(t.as_str()),
-            _ => None,
+            // 標準の絵文字は文字そのもの、カスタム絵文字は :shortcut:
+            MessageItem::Emoji(e) => e
+                .emoji_text
+                .as_deref()
+                .or_else(|| e.image_item.as_ref()?.alt.as_deref()),
         })
         .collect::<String>();
-
-    if !author.is_empty() && !text.is_empty() {
-        let _ = tx.send((author, text));
+    if name.is_empty() {
+        return None;
     }
+    let owner = owner_channel_id.is_some_and(|id| id == chat.author.channel_id);
+
+    let message = ChatMessage {
+        id: chat.id,
+        author: Author::new(name, Platform::YouTube).with_channel_id(chat.author.channel_id),
+        badges: Badges {
+            owner,
+            moderator: chat.is_moderator || chat.is_owner,
+            member: chat.is_membership,
+            verified: chat.is_verified,
+        },
+        text,
+        timestamp: chat.timestamp.unwrap_or_else(Utc::now),
+    };
+    let event = match chat.superchat {
+        Some(SuperChat {
+            amount,
+            sticker: Some(sticker),
+            ..
+        }) => ChatEvent::SuperSticker {
+            message,
+            amount: Money::parse(&amount),
+            sticker: sticker.alt.unwrap_or_default(),
+        },
+        Some(SuperChat { amount, .. }) => ChatEvent::SuperChat {
+            message,
+            amount: Money::parse(&amount),
+        },
+        None if chat.is_membership && is_welcome(&message.text, channel_name) => {
+            ChatEvent::Membership {
+                message: ChatMessage {
+                    text: String::new(),
+                    ..message
+                },
+                tier: chat.author.badge.map(|b| b.label),
+            }
+        }
+        None if message.text.is_empty() => return None,
+        None => ChatEvent::Message(message),
+    };
+    Some(event)
+}
+
+/// `Welcome to {チャンネル名}!` / `{チャンネル名} へようこそ！` のいずれかか
+fn is_welcome(text: &str, channel: Option<&str>) -> bool {
+    let Some(channel) = channel else {
+        return false;
+    };
+    let text = text.trim().trim_end_matches(['!', '！']).trim_end();
+    text.strip_prefix("Welcome to ") == Some(channel)
+        || text.strip_suffix("へようこそ").map(str::trim_end) == Some(channel)
 }
@@ -0,0 +1,387 @@
+//! `!` で始まるチャットコマンド。
+//!
+//! - コマンドごとに必要な権限（バッジから判定）とクールダウンを持つ。
+//! - 既定値は [`CommandRouter::default`]。`COMMANDS_FILE` の JSON で上書き・無効化できる。
+//! - 応答を読み上げるか、ログに残すだけかもコマンドごとに決める。
+//! - `!skip` `!mute` は返答の再生中にも効くよう [`Controls`] で直接伝える。
+//!
+//! ```json
+//! {
+//!   "dice": { "permission": "member", "cooldown_sec": 30 },
+//!   "help": { "spoken": false },
+//!   "clear": { "enabled": false }
+//! }
+//! ```
+
+use std::{
+    collections::HashMap,
+    fmt, fs,
+    path::Path,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+use anyhow::Context;
+use serde::Deserialize;
+use tokio::sync::Notify;
+use tracing::info;
+
+use crate::{
+    error::{Error, Result},
+    model::chat_event::Badges,
+};
+
+/// `!dice` の既定の面数と上限
+const DICE_SIDES: u32 = 6;
+const MAX_DICE_SIDES: u32 = 1000;
+
+/// コマンドを使える権限。下から順に強い。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Permission {
+    Everyone,
+    Member,
+    Moderator,
+    Owner,
+}
+
+impl Permission {
+    /// 投稿者のバッジから権限を決める。
+    pub fn from_badges(badges: &Badges) -> Self {
+        if badges.owner {
+            Self::Owner
+        } else if badges.moderator {
+            Self::Moderator
+        } else if badges.member {
+            Self::Member
+        } else {
+            Self::Everyone
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Everyone => "everyone",
+            Self::Member => "member",
+            Self::Moderator => "moderator",
+            Self::Owner => "owner",
+        }
+    }
+}
+
+impl fmt::Display for Permission {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// 解釈済みのコマンド。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    Help,
+    Dice { sides: u32 },
+    Voice { speaker: u16 },
+    Persona { name: String },
+    Skip,
+    Mute,
+    Clear,
+}
+
+impl Command {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Help => "help",
+            Self::Dice { .. } => "dice",
+            Self::Voice { .. } => "voice",
+            Self::Persona { .. } => "persona",
+            Self::Skip => "skip",
+            Self::Mute => "mute",
+            Self::Clear => "clear",
+        }
+    }
+
+    /// `!name args` を解釈する。
+    pub fn parse(text: &str) -> std::result::Result<Self, Rejection> {
+        let body = text.trim().strip_prefix('!').unwrap_or(text.trim());
+        let mut words = body.split_whitespace();
+        let name = words.next().unwrap_or_default().to_lowercase();
+        let arg = words.next();
+        // 使い方の文面は Spec にあるので、ここではコマンド名だけ返す
+        let usage = |name: &str| Rejection::Usage(name.to_owned());
+
+        let cmd = match name.as_str() {
+            "help" => Self::Help,
+            "dice" => match arg {
+                None => Self::Dice { sides: DICE_SIDES },
+                Some(n) => match n.parse() {
+                    Ok(sides @ 2..=MAX_DICE_SIDES) => Self::Dice { sides },
+                    _ => return Err(usage("dice")),
+                },
+            },
+            "voice" => match arg.and_then(|n| n.parse().ok()) {
+                Some(speaker) => Self::Voice { speaker },
+                None => return Err(usage("voice")),
+            },
+            "persona" => match arg {
+                Some(name) => Self::Persona {
+                    name: name.to_owned(),
+                },
+                None => return Err(usage("persona")),
+            },
+            "skip" => Self::Skip,
+            "mute" => Self::Mute,
+            "clear" => Self::Clear,
+            _ => return Err(Rejection::Unknown(name)),
+        };
+        Ok(cmd)
+    }
+}
+
+/// コマンドを実行しない理由。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Rejection {
+    Unknown(String),
+    /// 引数が不正（使い方を返す）
+    Usage(String),
+    Disabled,
+    Forbidden {
+        required: Permission,
+    },
+    Cooldown {
+        remaining: Duration,
+    },
+}
+
+impl fmt::Display for Rejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Unknown(name) => write!(f, "unknown command: {name}"),
+            Self::Usage(usage) => write!(f, "usage: {usage}"),
+            Self::Disabled => f.write_str("command disabled"),
+            Self::Forbidden { required } => write!(f, "requires {required}"),
+            Self::Cooldown { remaining } => {
+                write!(f, "cooling down ({}s left)", remaining.as_secs().max(1))
+            }
+        }
+    }
+}
+
+/// コマンド 1 つぶんの設定。
+#[derive(Debug, Clone)]
+pub struct Spec {
+    pub name: &'static str,
+    pub usage: &'static str,
+    pub permission: Permission,
+    pub cooldown: Duration,
+    /// 応答を読み上げる（`false` ならログのみ）
+    pub spoken: bool,
+    pub enabled: bool,
+}
+
+impl Spec {
+    const fn new(
+        name: &'static str,
+        usage: &'static str,
+        permission: Permission,
+        cooldown_sec: u64,
+        spoken: bool,
+    ) -> Self {
+        Self {
+            name,
+            usage,
+            permission,
+            cooldown: Duration::from_secs(cooldown_sec),
+            spoken,
+            enabled: true,
+        }
+    }
+}
+
+/// `COMMANDS_FILE` の 1 項目（省略した項目は既定値のまま）。
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct SpecOverride {
+    pub permission: Option<Permission>,
+    pub cooldown_sec: Option<u64>,
+    pub spoken: Option<bool>,
+    pub enabled: Option<bool>,
+}
+
+/// 実行を許可されたコマンド。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Invocation {
+    pub command: Command,
+    pub spoken: bool,
+}
+
+/// コマンドの一覧と、クールダウンのための最終実行時刻。
+#[derive(Debug, Clone)]
+pub struct CommandRouter {
+    specs: Vec<Spec>,
+    last_used: HashMap<&'static str, Instant>,
+}
+
+impl Default for CommandRouter {
+    fn default() -> Self {
+        use Permission::*;
+        Self {
+            specs: vec![
+                Spec::new("help", "!help", Everyone, 30, true),
+                Spec::new("dice", "!dice [面数]", Everyone, 10, true),
+                Spec::new("voice", "!voice <話者ID>", Moderator, 5, false),
+                Spec::new("persona", "!persona <名前>", Moderator, 5, false),
+                Spec::new("skip", "!skip", Moderator, 0, false),
+                Spec::new("mute", "!mute", Moderator, 0, false),
+                Spec::new("clear", "!clear", Owner, 0, false),
+            ],
+            last_used: HashMap::new(),
+        }
+    }
+}
+
+impl CommandRouter {
+    /// 既定値に `overrides`（コマンド名 → 設定）を反映する。未知の名前は [`Error::InvalidConfig`]。
+    pub fn new(overrides: HashMap<String, SpecOverride>) -> Result<Self> {
+        let mut router = Self::default();
+        for (name, o) in overrides {
+            let spec = router
+                .specs
+                .iter_mut()
+                .find(|s| s.name == name)
+                .ok_or_else(|| Error::InvalidConfig(format!("unknown command: {name}")))?;
+            if let Some(p) = o.permission {
+                spec.permission = p;
+            }
+            if let Some(sec) = o.cooldown_sec {
+                spec.cooldown = Duration::from_secs(sec);
+            }
+            if let Some(spoken) = o.spoken {
+                spec.spoken = spoken;
+            }
+            if let Some(enabled) = o.enabled {
+                spec.enabled = enabled;
+            }
+        }
+        Ok(router)
+    }
+
+    /// JSON ファイルから読み込む。
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path)
+            .with_context(|| format!("failed to read {}", path.display()))?;
+        let overrides = serde_json::from_str(&text)
+            .map_err(|e| Error::InvalidConfig(format!("{}: {e}", path.display())))?;
+        Self::new(overrides)
+    }
+
+    pub fn spec(&self, name: &str) -> Option<&Spec> {
+        self.specs.iter().find(|s| s.name == name)
+    }
+
+    /// コメントをコマンドとして解釈し、権限とクールダウンを確かめる。
+    ///
+    /// `!` で始まらなければ `None`。モデレーター以上はクールダウンを無視する。
+    pub fn route(
+        &mut self,
+        text: &str,
+        permission: Permission,
+        now: Instant,
+    ) -> Option<std::result::Result<Invocation, Rejection>> {
+        if !text.trim_start().starts_with('!') {
+            return None;
+        }
+        Some(self.authorize(text, permission, now))
+    }
+
+    fn authorize(
+        &mut self,
+        text: &str,
+        permission: Permission,
+        now: Instant,
+    ) -> std::result::Result<Invocation, Rejection> {
+        let command = Command::parse(text).map_err(|e| match e {
+            Rejection::Usage(name) => {
+                Rejection::Usage(self.spec(&name).map_or(name, |s| s.usage.to_owned()))
+            }
+            other => other,
+        })?;
+        let spec = self
+            .specs
+            .iter()
+            .find(|s| s.name == command.name())
+            .ok_or_else(|| Rejection::Unknown(command.name().to_owned()))?;
+        if !spec.enabled {
+            return Err(Rejection::Disabled);
+        }
+        if permission < spec.permission {
+            return Err(Rejection::Forbidden {
+                required: spec.permission,
+            });
+        }
+        if permission < Permission::Moderator
+            && let Some(last) = self.last_used.get(spec.name)
+        {
+            let elapsed = now.saturating_duration_since(*last);
+            if elapsed < spec.cooldown {
+                return Err(Rejection::Cooldown {
+                    remaining: spec.cooldown - elapsed,
+                });
+            }
+        }
+        self.last_used.insert(spec.name, now);
+        Ok(Invocation {
+            spoken: spec.spoken,
+            command,
+        })
+    }
+
+    /// `permission` で使えるコマンドの一覧。
+    pub fn help(&self, permission: Permission) -> String {
+        let usable: Vec<&str> = self
+            .specs
+            .iter()
+            .filter(|s| s.enabled && s.permission <= permission)
+            .map(|s| s.usage)
+            .collect();
+        format!("使えるコマンドは {} だよ。", usable.join("、"))
+    }
+}
+
+/// サイコロを振る。
+pub fn roll(sides: u32) -> u32 {
+    rand::random_range(1..=sides.max(1))
+}
+
+/// 再生中の返答に効く操作（`!skip` `!mute`）。クローンは同じ状態を指す。
+#[derive(Debug, Clone, Default)]
+pub struct Controls {
+    muted: Arc<AtomicBool>,
+    skip: Arc<Notify>,
+}
+
+impl Controls {
+    /// 読み上げの停止・再開を切り替え、切り替え後に止まっているかを返す。
+    pub fn toggle_mute(&self) -> bool {
+        let muted = !self.muted.fetch_xor(true, Ordering::Relaxed);
+        info!(muted, "speech mute toggled");
+        muted
+    }
+
+    pub fn is_muted(&self) -> bool {
+        self.muted.load(Ordering::Relaxed)
+    }
+
+    /// 再生中の返答を打ち切る。再生中でなければ何もしない。
+    pub fn skip(&self) {
+        self.skip.notify_waiters();
+    }
+
+    /// [`skip`](Self::skip) されるまで待つ。
+    pub async fn skipped(&self) {
+        self.skip.notified().await;
+    }
+}
@@ -0,0 +1,253 @@
+//! ローカルのナレッジベース（設定資料・スケジュール・グッズ情報・FAQ など）。
+//!
+//! - ディレクトリ以下の `.md` / `.txt` を読み込み、空行や見出しで段落に分ける。
+//! - 日本語は分かち書きせず、文字 bi-gram（ASCII は単語）で索引する。
+//! - コメントごとに BM25 で上位の段落を引き、プロンプトに根拠として差し込む。
+//! - 外部の埋め込みサービスは使わない。
+
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use tracing::{debug, info};
+
+use crate::error::Result;
+
+/// BM25 のパラメータ
+const K1: f64 = 1.2;
+const B: f64 = 0.75;
+
+/// 1 段落の最大文字数（超えたら分割する）
+const MAX_PASSAGE_CHARS: usize = 400;
+
+/// 検索対象の段落。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Passage {
+    /// 読み込み元ファイル（ディレクトリからの相対パス）
+    pub source: String,
+    pub text: String,
+}
+
+/// BM25 索引付きの段落集合。
+#[derive(Debug, Default)]
+pub struct KnowledgeBase {
+    passages: Vec<Passage>,
+    /// 段落ごとの語 → 出現回数
+    terms: Vec<HashMap<String, usize>>,
+    /// 段落ごとの語数
+    lengths: Vec<usize>,
+    /// 語 → 出現する段落数
+    doc_freq: HashMap<String, usize>,
+    avg_len: f64,
+}
+
+impl KnowledgeBase {
+    /// `dir` 以下の `.md` / `.txt` をすべて読み込む。
+    pub fn load(dir: impl AsRef<Path>) -> Result<Self> {
+        let dir = dir.as_ref();
+        let mut files = Vec::new();
+        collect_files(dir, &mut files)?;
+        files.sort();
+
+        let mut passages = Vec::new();
+        for path in &files {
+            let text = fs::read_to_string(path)
+                .with_context(|| format!("failed to read {}", path.display()))?;
+            let source = path
+                .strip_prefix(dir)
+                .unwrap_or(path)
+                .to_string_lossy()
+                .into_owned();
+            passages.extend(split_passages(&text).into_iter().map(|text| Passage {
+                source: source.clone(),
+                text,
+            }));
+        }
+        info!(
+            dir = %dir.display(),
+            files = files.len(),
+            passages = passages.len(),
+            "knowledge base loaded"
+        );
+        Ok(Self::from_passages(passages))
+    }
+
+    /// 段落から索引を作る。
+    pub fn from_passages(passages: Vec<Passage>) -> Self {
+        let mut terms = Vec::with_capacity(passages.len());
+        let mut lengths = Vec::with_capacity(passages.len());
+        let mut doc_freq: HashMap<String, usize> = HashMap::new();
+
+        for p in &passages {
+            let tokens = tokenize(&p.text);
+            lengths.push(tokens.len());
+            let mut tf: HashMap<String, usize> = HashMap::new();
+            for t in tokens {
+                *tf.entry(t).or_default() += 1;
+            }
+            for t in tf.keys() {
+                *doc_freq.entry(t.clone()).or_default() += 1;
+            }
+            terms.push(tf);
+        }
+        let avg_len = if lengths.is_empty() {
+            0.0
+        } else {
+            lengths.iter().sum::<usize>() as f64 / lengths.len() as f64
+        };
+
+        Self {
+            passages,
+            terms,
+            lengths,
+            doc_freq,
+            avg_len,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.passages.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.passages.is_empty()
+    }
+
+    /// `query` に関連する段落をスコアの高い順に最大 `k` 件返す。
+    pub fn search(&self, query: &str, k: usize) -> Vec<&Passage> {
+        let mut query_terms = tokenize(query);
+        query_terms.sort();
+        query_terms.dedup();
+
+        let n = self.passages.len() as f64;
+        let mut scored: Vec<(usize, f64)> = (0..self.passages.len())
+            .filter_map(|i| {
+                let tf = &self.terms[i];
+                let norm = K1 * (1.0 - B + B * self.lengths[i] as f64 / self.avg_len.max(1.0));
+                let score: f64 = query_terms
+                    .iter()
+                    .filter_map(|t| {
+                        let f = *tf.get(t)? as f64;
+                        let df = self.doc_freq[t] as f64;
+                        let idf = ((n - df + 0.5) / (df + 0.5) + 1.0).ln();
+                        Some(idf * f * (K1 + 1.0) / (f + norm))
+                    })
+                    .sum();
+                (score > 0.0).then_some((i, score))
+            })
+            .collect();
+        scored.sort_by(|a, b| b.1.total_cmp(&a.1));
+        scored.truncate(k);
+
+        debug!(
+            query,
+            hits = ?scored.iter().map(|(i, s)| (&self.passages[*i].source, *s)).collect::<Vec<_>>(),
+            "knowledge search"
+        );
+        scored.into_iter().map(|(i, _)| &self.passages[i]).collect()
+    }
+}
+
+fn collect_files(dir: &Path, out: &mut Vec<PathBuf>) -> Result<()> {
+    let entries =
+        fs::read_dir(dir).with_context(|| format!("failed to read dir {}", dir.display()))?;
+    for entry in entries {
+        let path = entry.context("failed to read dir entry")?.path();
+        if path.is_dir() {
+            collect_files(&path, out)?;
+        } else if matches!(
+            path.extension().and_then(|e| e.to_str()),
+            Some("md" | "txt")
+        ) {
+            out.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// 見出し・空行で段落に分ける。見出しは続く段落の先頭に付ける。
+fn split_passages(text: &str) -> Vec<String> {
+    let mut out = Vec::new();
+    let mut heading = String::new();
+    let mut buf = String::new();
+
+    let mut flush = |buf: &mut String, heading: &str| {
+        let body = buf.trim();
+        if !body.is_empty() {
+            for chunk in chunk_chars(body, MAX_PASSAGE_CHARS) {
+                out.push(if heading.is_empty() {
+                    chunk
+                } else {
+                    format!("{heading}\n{chunk}")
+                });
+            }
+        }
+        buf.clear();
+    };
+
+    for line in text.lines() {
+        let trimmed = line.trim();
+        if trimmed.starts_with('#') {
+            flush(&mut buf, &heading);
+            heading = trimmed.trim_start_matches('#').trim().to_owned();
+        } else if trimmed.is_empty() {
+            flush(&mut buf, &heading);
+        } else {
+            buf.push_str(trimmed);
+            buf.push('\n');
+        }
+    }
+    flush(&mut buf, &heading);
+    out
+}
+
+fn chunk_chars(text: &str, max: usize) -> Vec<String> {
+    let chars: Vec<char> = text.chars().collect();
+    chars.chunks(max).map(|c| c.iter().collect()).collect()
+}
+
+/// ASCII の英数字は小文字化した単語、それ以外の文字列は文字 bi-gram に分ける。
+///
+/// ```
+/// use ai_tuber::service::knowledge::tokenize;
+///
+/// assert_eq!(tokenize("次の配信は Minecraft!"), ["次の", "の配", "配信", "信は", "minecraft"]);
+/// ```
+pub fn tokenize(text: &str) -> Vec<String> {
+    let mut out = Vec::new();
+    let mut word = String::new();
+    let mut run: Vec<char> = Vec::new();
+
+    fn flush_run(run: &mut Vec<char>, out: &mut Vec<String>) {
+        match run.len() {
+            0 => {}
+            1 => out.push(run[0].to_string()),
+            _ => out.extend(run.windows(2).map(|w| w.iter().collect())),
+        }
+        run.clear();
+    }
+
+    for c in text.chars() {
+        if c.is_ascii_alphanumeric() {
+            flush_run(&mut run, &mut out);
+            word.push(c.to_ascii_lowercase());
+        } else {
+            if !word.is_empty() {
+                out.push(std::mem::take(&mut word));
+            }
+            if c.is_alphanumeric() {
+                run.push(c);
+            } else {
+                flush_run(&mut run, &mut out);
+            }
+        }
+    }
+    if !word.is_empty() {
+        out.push(word);
+    }
+    flush_run(&mut run, &mut out);
+    out
+}
@@ -20,8 +20,8 @@ use crate::{error::{Error, Result}, model::emotion::Emotion};
 /// 送信用 UDP ソケット（初回だけ bind）。
 static SOCKET: OnceCell<UdpSocket> = OnceCell::new();
 
-/// 前回適用した表情名。
-static LAST: Lazy<Mutex<Option<&'static str>>> = Lazy::new(|| Mutex::new(None));
+/// 前回適用した BlendShape 名。
+static LAST: Lazy<Mutex<Option<String>>> = Lazy::new(|| Mutex::new(None));
 
 /* ───────────────────── 内部ユーティリティ ───────────────────── */
 
@@ -67,12 +67,20 @@ fn send_apply(sock: &UdpSocket) -> Result<()> {
 /// - それ以外は無駄な OSC を抑制するためスキップ
 pub fn set(em: Emotion) -> Result<()> {
     let (name, val) = em.clip();
+    set_blend(name, val)
+}
+
+/// BlendShape を直接指定して表情を切り替える（ペルソナごとの対応表用）。
+///
+/// 前回と同じ名前の扱いは [`set`] と同じ。
+pub fn set_blend(name: &str, val: f32) -> Result<()> {
     let sock = socket()?;
 
     // 前回の表情を 0.0 に戻す
     {
         let mut last = LAST.lock().unwrap(); // Poison 化しない想定
-        if let Some(prev) = *last {
+        #[allow(clippy::collapsible_if)]
+        if let Some(prev) = last.as_deref() {
             if prev != name || val == 0.0 {
                 send_blend_val(sock, prev, 0.0)?;
             }
@@ -83,7 +91,7 @@ pub fn set(em: Emotion) -> Result<()> {
             send_blend_val(sock, name, val)?;
         }
         send_apply(sock)?;
-        *last = Some(name);
+        *last = Some(name.to_owned());
     }
 
     Ok(())
@@ -29,6 +29,7 @@ const HOST: &str = "http://127.0.0.1:50021";
 mod endpoint {
     pub const AUDIO_QUERY: &str = "/audio_query";
     pub const SYNTHESIS: &str = "/synthesis";
+    pub const SPEAKERS: &str = "/speakers";
 }
 
 /* --------------------------------------------------------------------- */
@@ -88,3 +89,26 @@ pub async fn synth(text: &str, speaker: u16) -> Result<Vec<u8>> {
     // `Bytes` -> `Vec<u8>` にムーブ。clone() は発生しない。
     Ok(bytes.into())
 }
+
+/// `/speakers` から使える話者 ID（各話者のスタイル ID）をすべて返す。
+pub async fn speaker_ids() -> Result<Vec<u16>> {
+    let speakers: Value = client()?
+        .get(format!("{HOST}{}", endpoint::SPEAKERS))
+        .send()
+        .await
+        .context("GET /speakers")?
+        .error_for_status()
+        .context("/speakers non-2xx")?
+        .json()
+        .await
+        .context("deserialize speakers")?;
+
+    Ok(speakers
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|s| s["styles"].as_array())
+        .flatten()
+        .filter_map(|style| style["id"].as_u64()?.try_into().ok())
+        .collect())
+}
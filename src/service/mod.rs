@@ -1,5 +1,12 @@
 pub mod api {
+    pub mod chat_source;
     pub mod gemini_client;
+    pub mod llm;
+    pub mod local_chat;
+    pub mod openai_client;
+    pub mod retry;
+    pub mod twitch_chat;
+    pub mod youtube_api;
     pub mod youtube_chat;
 }
 
@@ -9,8 +16,23 @@ pub mod media {
     pub mod tts_voicevox;
 }
 
+pub mod commands;
+pub mod knowledge;
+pub mod moderation;
+pub mod persona;
+pub mod postprocess;
 pub mod prompt;
+pub mod scheduler;
+pub mod segmenter;
+pub mod summarizer;
+pub mod template;
+pub mod tokens;
+pub mod tools;
+pub mod viewer_memory;
 
+pub use api::chat_source::{self, ChatSource};
 pub use api::gemini_client::GeminiClient;
-pub use api::youtube_chat;
+pub use api::llm::{self, LlmBackend};
+pub use api::openai_client::OpenAiClient;
+pub use api::{local_chat, twitch_chat, youtube_api, youtube_chat};
 pub use media::{audio, avatar_osc, tts_voicevox};
@@ -0,0 +1,395 @@
+//! コメントのモデレーション（LLM に渡す前の足切り）。
+//!
+//! - ルールは JSON ファイル（`MODERATION_RULES_FILE`）で指定する。未指定なら既定値。
+//! - BAN リスト → NG ワード・正規表現 → リンク → 連続文字 → コピペ連投 → 投稿頻度 の順に判定する。
+//! - スーパーチャット・Bits・メンバー加入は BAN と NG ワード・正規表現だけで判定し、
+//!   リンク・連続文字・連投・頻度では落とさない（お礼を言いそびれないように）。
+//! - BAN と投稿頻度は表示名ではなくチャンネル ID（[`Author::key`]）で見る。
+//!   `banned_authors` にはチャンネル ID（`UC…`）か `platform:id` の形で書く。
+//! - 「wwww」「8888」のような反応の連打は連続文字として数えない。
+//! - 落としたコメントは理由付きで `moderation` ターゲットにログし、
+//!   `MODERATION_LOG_FILE` があれば JSON Lines で追記して後から監査できるようにする。
+//!
+//! ```json
+//! {
+//!   "blocked_words": ["宣伝"],
+//!   "patterns": ["(?i)free\\s*gift"],
+//!   "banned_authors": ["UCxxxxxxxxxxxxxxxxxxxxxx", "twitch:123456"],
+//!   "allow_links": false,
+//!   "max_repeated_chars": 10,
+//!   "flood": { "window_sec": 30, "threshold": 3, "min_chars": 8 },
+//!   "rate_limit": { "max_messages": 4, "per_sec": 60 }
+//! }
+//! ```
+
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    fmt,
+    fs::{self, OpenOptions},
+    io::Write,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use anyhow::Context;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde::Deserialize;
+use serde_json::json;
+use tracing::{info, warn};
+
+use crate::{
+    error::{Error, Result},
+    model::{chat_event::ChatEvent, conversation::Author},
+};
+
+static LINK: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"(?i)https?://|www\.|\b[a-z0-9-]+\.(?:com|net|org|jp|io|ly|gg)\b").unwrap()
+});
+
+/// 連投判定のために覚えておく文面・視聴者の数の目安（超えたら古いものを捨てる）
+const MAX_TRACKED: usize = 1024;
+
+/// 笑い・拍手など、連打するのが普通の文字（連続文字の判定から外す）
+const REACTION_CHARS: &[char] = &[
+    'w', 'W', 'ｗ', 'Ｗ', '8', '８', '草', 'ー', '〜', '～', '!', '！', '?', '？', '.', '。', '…',
+];
+
+/// コピペ連投の判定
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FloodRule {
+    pub window_sec: u64,
+    /// 窓の中で同じ文面がこの回数に達したら落とす
+    pub threshold: usize,
+    /// これより短い文面（挨拶・「草」など）は数えない
+    pub min_chars: usize,
+}
+
+impl Default for FloodRule {
+    fn default() -> Self {
+        Self {
+            window_sec: 30,
+            threshold: 3,
+            min_chars: 8,
+        }
+    }
+}
+
+/// 視聴者ごとの投稿頻度の上限
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RateLimit {
+    pub max_messages: usize,
+    pub per_sec: u64,
+}
+
+impl Default for RateLimit {
+    fn default() -> Self {
+        Self {
+            max_messages: 4,
+            per_sec: 60,
+        }
+    }
+}
+
+/// ルールファイルの中身。
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ModerationRules {
+    pub blocked_words: Vec<String>,
+    /// 正規表現のルール
+    pub patterns: Vec<String>,
+    /// BAN するチャンネル ID（`UC…`）または `platform:id`
+    pub banned_authors: Vec<String>,
+    pub allow_links: bool,
+    /// 同じ文字がこの回数以上続いたらスパム扱い（0 で無効、[`REACTION_CHARS`] は数えない）
+    pub max_repeated_chars: usize,
+    pub flood: FloodRule,
+    pub rate_limit: RateLimit,
+}
+
+impl Default for ModerationRules {
+    fn default() -> Self {
+        Self {
+            blocked_words: Vec::new(),
+            patterns: Vec::new(),
+            banned_authors: Vec::new(),
+            allow_links: false,
+            max_repeated_chars: 10,
+            flood: FloodRule::default(),
+            rate_limit: RateLimit::default(),
+        }
+    }
+}
+
+impl ModerationRules {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path)
+            .with_context(|| format!("failed to read {}", path.display()))?;
+        serde_json::from_str(&text)
+            .map_err(|e| Error::InvalidConfig(format!("{}: {e}", path.display())))
+    }
+}
+
+/// コメントを落とした理由。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DropReason {
+    Banned,
+    BlockedWord(String),
+    Pattern(String),
+    Link,
+    RepeatedChars,
+    Flood,
+    RateLimited,
+}
+
+impl DropReason {
+    /// ログ・監査用の種別
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Banned => "banned",
+            Self::BlockedWord(_) => "blocked_word",
+            Self::Pattern(_) => "pattern",
+            Self::Link => "link",
+            Self::RepeatedChars => "repeated_chars",
+            Self::Flood => "flood",
+            Self::RateLimited => "rate_limited",
+        }
+    }
+}
+
+impl fmt::Display for DropReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::BlockedWord(w) => write!(f, "blocked_word: {w}"),
+            Self::Pattern(p) => write!(f, "pattern: {p}"),
+            other => f.write_str(other.kind()),
+        }
+    }
+}
+
+/// ルールと、頻度判定のための直近の投稿。
+#[derive(Debug)]
+pub struct Moderator {
+    rules: ModerationRules,
+    patterns: Vec<Regex>,
+    /// チャンネル ID または [`Author::key`]
+    banned: HashSet<String>,
+    /// [`Author::key`] → 直近の投稿時刻
+    recent_by_author: HashMap<String, VecDeque<Instant>>,
+    /// 正規化した文面 → 直近の投稿時刻（視聴者をまたぐ）
+    recent_by_text: HashMap<String, VecDeque<Instant>>,
+    audit_log: Option<PathBuf>,
+}
+
+impl Moderator {
+    /// ルールを検証して作る。不正な正規表現は [`Error::InvalidConfig`]。
+    pub fn new(rules: ModerationRules) -> Result<Self> {
+        let patterns = rules
+            .patterns
+            .iter()
+            .map(|p| {
+                Regex::new(p).map_err(|e| Error::InvalidConfig(format!("moderation pattern: {e}")))
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self {
+            banned: rules.banned_authors.iter().cloned().collect(),
+            patterns,
+            rules,
+            recent_by_author: HashMap::new(),
+            recent_by_text: HashMap::new(),
+            audit_log: None,
+        })
+    }
+
+    /// 落としたコメントを JSON Lines で `path` に追記する。
+    pub fn with_audit_log(mut self, path: impl Into<PathBuf>) -> Self {
+        self.audit_log = Some(path.into());
+        self
+    }
+
+    /// 配信中に BAN する。`id` はチャンネル ID または [`Author::key`]。
+    pub fn ban(&mut self, id: &str) {
+        info!(target: "moderation", id, "author banned");
+        self.banned.insert(id.to_owned());
+    }
+
+    pub fn unban(&mut self, id: &str) -> bool {
+        self.banned.remove(id)
+    }
+
+    fn is_banned(&self, author: &Author) -> bool {
+        self.banned.contains(&author.key())
+            || author
+                .channel_id
+                .as_ref()
+                .is_some_and(|id| self.banned.contains(id))
+    }
+
+    /// コメントを判定する。落とす場合は理由を記録して返す。
+    pub fn check(&mut self, author: &Author, text: &str, now: Instant) -> Option<DropReason> {
+        let reason = self.judge(author, text, now);
+        self.report(author, text, reason)
+    }
+
+    /// チャットのイベントを判定する。有料・メンバー加入は BAN と NG ワード・正規表現だけで見る。
+    pub fn check_event(&mut self, event: &ChatEvent, now: Instant) -> Option<DropReason> {
+        let msg = event.message();
+        let reason = match event {
+            ChatEvent::Message(_) => self.judge(&msg.author, &msg.text, now),
+            _ => self.judge_content(&msg.author, &msg.text),
+        };
+        self.report(&msg.author, &msg.text, reason)
+    }
+
+    fn report(
+        &self,
+        author: &Author,
+        text: &str,
+        reason: Option<DropReason>,
+    ) -> Option<DropReason> {
+        if let Some(reason) = &reason {
+            info!(
+                target: "moderation",
+                author = %author.name,
+                id = %author.key(),
+                text,
+                reason = %reason,
+                "comment dropped"
+            );
+            self.audit(author, text, reason);
+        }
+        reason
+    }
+
+    /// BAN と NG ワード・正規表現（読み上げてはいけないもの）
+    fn judge_content(&self, author: &Author, text: &str) -> Option<DropReason> {
+        if self.is_banned(author) {
+            return Some(DropReason::Banned);
+        }
+
+        let lower = text.to_lowercase();
+        if let Some(w) = self
+            .rules
+            .blocked_words
+            .iter()
+            .find(|w| !w.is_empty() && lower.contains(&w.to_lowercase()))
+        {
+            return Some(DropReason::BlockedWord(w.clone()));
+        }
+        self.patterns
+            .iter()
+            .find(|p| p.is_match(text))
+            .map(|p| DropReason::Pattern(p.as_str().to_owned()))
+    }
+
+    fn judge(&mut self, author: &Author, text: &str, now: Instant) -> Option<DropReason> {
+        if let Some(reason) = self.judge_content(author, text) {
+            return Some(reason);
+        }
+        if !self.rules.allow_links && LINK.is_match(text) {
+            return Some(DropReason::Link);
+        }
+        if self.rules.max_repeated_chars > 0 && longest_run(text) >= self.rules.max_repeated_chars {
+            return Some(DropReason::RepeatedChars);
+        }
+
+        // 頻度系は落とした投稿も数える（連投を続けるほど解除されない）
+        let flood = &self.rules.flood;
+        if self.recent_by_text.len() > MAX_TRACKED {
+            let window = Duration::from_secs(flood.window_sec);
+            self.recent_by_text.retain(|_, t| {
+                t.back()
+                    .is_some_and(|&t| now.saturating_duration_since(t) <= window)
+            });
+        }
+        if flood.threshold > 0 && text.chars().count() >= flood.min_chars {
+            let seen = record(
+                self.recent_by_text.entry(normalize(text)).or_default(),
+                now,
+                Duration::from_secs(flood.window_sec),
+            );
+            if seen >= flood.threshold {
+                return Some(DropReason::Flood);
+            }
+        }
+        let limit = &self.rules.rate_limit;
+        if self.recent_by_author.len() > MAX_TRACKED {
+            let window = Duration::from_secs(limit.per_sec);
+            self.recent_by_author.retain(|_, t| {
+                t.back()
+                    .is_some_and(|&t| now.saturating_duration_since(t) <= window)
+            });
+        }
+        if limit.max_messages > 0 {
+            let sent = record(
+                self.recent_by_author.entry(author.key()).or_default(),
+                now,
+                Duration::from_secs(limit.per_sec),
+            );
+            if sent > limit.max_messages {
+                return Some(DropReason::RateLimited);
+            }
+        }
+        None
+    }
+
+    fn audit(&self, author: &Author, text: &str, reason: &DropReason) {
+        let Some(path) = &self.audit_log else {
+            return;
+        };
+        let line = json!({
+            "time": chrono::Local::now().to_rfc3339(),
+            "author": author.name,
+            "author_id": author.key(),
+            "text": text,
+            "reason": reason.kind(),
+            "detail": reason.to_string(),
+        });
+        let res = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .and_then(|mut f| writeln!(f, "{line}"));
+        if let Err(e) = res {
+            warn!(error = %e, path = %path.display(), "failed to write moderation log");
+        }
+    }
+}
+
+/// 窓の外の記録を捨てて `now` を追加し、窓の中の件数を返す。
+fn record(times: &mut VecDeque<Instant>, now: Instant, window: Duration) -> usize {
+    while times
+        .front()
+        .is_some_and(|&t| now.saturating_duration_since(t) > window)
+    {
+        times.pop_front();
+    }
+    times.push_back(now);
+    times.len()
+}
+
+/// 同じ文字の最長の連続数（[`REACTION_CHARS`] の連打は数えない）
+fn longest_run(text: &str) -> usize {
+    let mut best = 0;
+    let mut run = 0;
+    let mut prev = None;
+    for c in text.chars().filter(|c| !c.is_whitespace()) {
+        run = if prev == Some(c) { run + 1 } else { 1 };
+        prev = Some(c);
+        if !REACTION_CHARS.contains(&c) {
+            best = best.max(run);
+        }
+    }
+    best
+}
+
+/// コピペ判定用に空白を除き小文字にする。
+fn normalize(text: &str) -> String {
+    text.chars()
+        .filter(|c| !c.is_whitespace())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
@@ -0,0 +1,270 @@
+//! キャラクター（ペルソナ）のプロファイルと、配信中の切り替え。
+//!
+//! - `PERSONA_DIR` 以下の `*.json` を 1 ファイル 1 ペルソナとして読み込む（名前はファイル名）。
+//! - プロファイルにはプロンプト・感情ごとの話者・VMC の BlendShape 対応・生成パラメータ・
+//!   自律トークの話題を書ける。省略した項目は `Config` の値を使う。
+//! - [`PersonaRegistry::switch`] で配信を止めずに切り替える。会話履歴はそのまま引き継ぐ。
+//!
+//! ```json
+//! {
+//!   "system_prompt": "あなたは元気な猫耳 VTuber です。{{#if viewer_name}}{{viewer_name}}さんと話しています。{{/if}}",
+//!   "speaker": 3,
+//!   "speakers": { "sad": 76 },
+//!   "blend": { "happy": { "name": "Fun", "value": 0.8 } },
+//!   "generation_config": { "temperature": 1.1 },
+//!   "spontaneous_topics": ["最近ハマっているゲーム", "好きな食べ物"]
+//! }
+//! ```
+
+use std::{collections::HashMap, fs, path::Path};
+
+use anyhow::Context;
+use serde::Deserialize;
+use tracing::info;
+
+use crate::{
+    config::Config,
+    error::{Error, Result},
+    model::{emotion::Emotion, gemini_dto::GenerationConfig},
+    service::template::Template,
+};
+
+/// 既定のペルソナ名（`PERSONA_DIR` 未指定時）
+pub const DEFAULT_NAME: &str = "default";
+
+/// VMC の BlendShape 名と値
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Blend {
+    pub name: String,
+    #[serde(default = "Blend::full")]
+    pub value: f32,
+}
+
+impl Blend {
+    fn full() -> f32 {
+        1.0
+    }
+}
+
+/// 声と表情の設定。
+#[derive(Debug, Clone, Default)]
+pub struct Voice {
+    /// VOICEVOX の話者 ID
+    pub speaker: u16,
+    /// 感情ごとの話者 ID（ないものは `speaker`）
+    pub speakers: HashMap<Emotion, u16>,
+    /// 感情ごとの BlendShape（ないものは [`Emotion::clip`]）
+    pub blend: HashMap<Emotion, Blend>,
+}
+
+impl Voice {
+    pub fn speaker(&self, em: Emotion) -> u16 {
+        self.speakers.get(&em).copied().unwrap_or(self.speaker)
+    }
+
+    pub fn clip(&self, em: Emotion) -> (&str, f32) {
+        match self.blend.get(&em) {
+            Some(b) => (&b.name, b.value),
+            None => em.clip(),
+        }
+    }
+}
+
+/// キャラクター 1 人ぶんの設定。
+#[derive(Debug, Clone)]
+pub struct Persona {
+    pub name: String,
+    pub system_prompt: Template,
+    pub spontaneous_prompt: Template,
+    /// 自律トークで振る話題（空なら指定しない）
+    pub spontaneous_topics: Vec<String>,
+    pub voice: Voice,
+    /// 生成パラメータ（`None` なら `Config::generation_config`）
+    pub generation_config: Option<GenerationConfig>,
+}
+
+impl Persona {
+    /// `Config` の値だけで作る既定のペルソナ。
+    pub fn from_config(cfg: &Config) -> Self {
+        Self {
+            name: DEFAULT_NAME.into(),
+            system_prompt: cfg.bot_system_prompt.clone(),
+            spontaneous_prompt: cfg.spontaneous_prompt.clone(),
+            spontaneous_topics: Vec::new(),
+            voice: Voice {
+                speaker: cfg.voicevox_speaker,
+                ..Voice::default()
+            },
+            generation_config: None,
+        }
+    }
+
+    /// 話題をランダムに 1 つ選ぶ。
+    pub fn pick_topic(&self) -> Option<&str> {
+        if self.spontaneous_topics.is_empty() {
+            return None;
+        }
+        Some(&self.spontaneous_topics[rand::random_range(0..self.spontaneous_topics.len())])
+    }
+
+    /// このペルソナの生成パラメータを反映した設定（LLM バックエンドの再生成用）。
+    ///
+    /// `maxOutputTokens` があれば、履歴の予算で返答用に空けておくぶんもそれに合わせる。
+    pub fn apply(&self, cfg: &Config) -> Config {
+        let mut cfg = cfg.clone();
+        if let Some(gen_cfg) = &self.generation_config {
+            cfg.generation_config = gen_cfg.clone();
+            if let Some(n) = gen_cfg.max_output_tokens {
+                cfg.token_budget.reply_tokens = n as usize;
+            }
+        }
+        cfg
+    }
+}
+
+/// プロファイルファイルの中身。キーの書き間違いは読み込みエラーにする。
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct Profile {
+    system_prompt: Option<String>,
+    spontaneous_prompt: Option<String>,
+    spontaneous_topics: Vec<String>,
+    speaker: Option<u16>,
+    speakers: HashMap<Emotion, u16>,
+    blend: HashMap<Emotion, Blend>,
+    generation_config: Option<GenerationConfig>,
+}
+
+impl Profile {
+    /// 省略された項目は `base` から引き継ぐ。
+    fn into_persona(self, name: &str, base: &Persona) -> Result<Persona> {
+        let template = |src: Option<String>, fallback: &Template| match src {
+            Some(src) => Template::parse(&src).map_err(|e| match e {
+                Error::InvalidConfig(msg) => Error::InvalidConfig(format!("persona {name}: {msg}")),
+                other => other,
+            }),
+            None => Ok(fallback.clone()),
+        };
+        Ok(Persona {
+            name: name.to_owned(),
+            system_prompt: template(self.system_prompt, &base.system_prompt)?,
+            spontaneous_prompt: template(self.spontaneous_prompt, &base.spontaneous_prompt)?,
+            spontaneous_topics: self.spontaneous_topics,
+            voice: Voice {
+                speaker: self.speaker.unwrap_or(base.voice.speaker),
+                speakers: self.speakers,
+                blend: self.blend,
+            },
+            generation_config: self.generation_config.or(base.generation_config.clone()),
+        })
+    }
+}
+
+/// 読み込んだペルソナと、現在のペルソナ。
+#[derive(Debug, Clone)]
+pub struct PersonaRegistry {
+    personas: Vec<Persona>,
+    active: usize,
+}
+
+impl PersonaRegistry {
+    /// `cfg.persona_dir` から読み込む。未指定なら `Config` の値だけの 1 人。
+    pub fn from_config(cfg: &Config) -> Result<Self> {
+        let base = Persona::from_config(cfg);
+        let mut reg = match &cfg.persona_dir {
+            Some(dir) => Self::load(dir, &base)?,
+            None => Self::single(base),
+        };
+        if let Some(name) = &cfg.persona {
+            reg.switch(name)?;
+        }
+        Ok(reg)
+    }
+
+    pub fn single(persona: Persona) -> Self {
+        Self {
+            personas: vec![persona],
+            active: 0,
+        }
+    }
+
+    /// `dir` 以下の `*.json` を名前順に読み込む。最初のものが既定になる。
+    ///
+    /// プロファイルで省略した項目は `base` の値を使う。
+    pub fn load(dir: impl AsRef<Path>, base: &Persona) -> Result<Self> {
+        let dir = dir.as_ref();
+        let mut paths: Vec<_> = fs::read_dir(dir)
+            .with_context(|| format!("failed to read dir {}", dir.display()))?
+            .filter_map(|e| e.ok().map(|e| e.path()))
+            .filter(|p| p.extension().is_some_and(|e| e == "json"))
+            .collect();
+        paths.sort();
+
+        let mut personas = Vec::with_capacity(paths.len());
+        for path in paths {
+            let name = path
+                .file_stem()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .into_owned();
+            let text = fs::read_to_string(&path)
+                .with_context(|| format!("failed to read {}", path.display()))?;
+            let profile: Profile = serde_json::from_str(&text)
+                .map_err(|e| Error::InvalidConfig(format!("persona {}: {e}", path.display())))?;
+            personas.push(profile.into_persona(&name, base)?);
+        }
+        if personas.is_empty() {
+            return Err(Error::InvalidConfig(format!(
+                "no persona profiles (*.json) in {}",
+                dir.display()
+            )));
+        }
+        info!(
+            personas = ?personas.iter().map(|p| &p.name).collect::<Vec<_>>(),
+            "personas loaded"
+        );
+        Ok(Self {
+            personas,
+            active: 0,
+        })
+    }
+
+    pub fn active(&self) -> &Persona {
+        &self.personas[self.active]
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.personas.iter().map(|p| p.name.as_str())
+    }
+
+    /// 現在のペルソナを切り替える。未知の名前なら [`Error::InvalidConfig`]。
+    pub fn switch(&mut self, name: &str) -> Result<&Persona> {
+        let idx = self.position(name)?;
+        self.active = idx;
+        info!(persona = name, "persona switched");
+        Ok(&self.personas[idx])
+    }
+
+    /// `build` が成功したときだけ切り替え、その結果を返す。失敗したら何も変えない。
+    ///
+    /// ペルソナ用の LLM バックエンドなどを先に作り、プロンプト・声と一緒に入れ替えるために使う。
+    pub fn switch_with<T>(
+        &mut self,
+        name: &str,
+        build: impl FnOnce(&Persona) -> Result<T>,
+    ) -> Result<T> {
+        let idx = self.position(name)?;
+        let built = build(&self.personas[idx])?;
+        self.active = idx;
+        info!(persona = name, "persona switched");
+        Ok(built)
+    }
+
+    fn position(&self, name: &str) -> Result<usize> {
+        self.personas
+            .iter()
+            .position(|p| p.name == name)
+            .ok_or_else(|| Error::InvalidConfig(format!("unknown persona: {name}")))
+    }
+}
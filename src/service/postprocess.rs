@@ -0,0 +1,323 @@
+//! 読み上げ前の返答の後処理。
+//!
+//! - 設定した順に [`Stage`] を適用する（Markdown 除去・URL 置換・絵文字の読み・NG ワード伏せ字）。
+//! - コードブロック（```）は文をまたぐので [`Session`] が状態を持って丸ごと除く。
+//! - 1 回の返答で話す長さに上限を設け、超えたら文の切れ目で打ち切る。
+//! - 各ステージで変化があれば `debug` で前後を記録する。
+//!
+//! ```
+//! use ai_tuber::{model::emotion::Emotion, service::{postprocess::Pipeline, segmenter::Segment}};
+//!
+//! let pipeline = Pipeline::default();
+//! let mut session = pipeline.session();
+//! let seg = Segment { emotion: Emotion::Happy, text: "**詳しくは** https://example.com を見てね🎉".into() };
+//! assert_eq!(session.push(seg).unwrap().text, "詳しくは リンク を見てねおめでとう");
+//! ```
+
+use std::collections::HashMap;
+
+use once_cell::sync::Lazy;
+use regex::Regex;
+use tracing::debug;
+
+use crate::{
+    error::{Error, Result},
+    model::reply::Segment,
+};
+
+/// 既定の絵文字の読み（ないものは読まずに消す）
+const EMOJI_READINGS: &[(&str, &str)] = &[
+    ("😂", "わら"),
+    ("🤣", "わら"),
+    ("😭", "えーん"),
+    ("👍", "いいね"),
+    ("👏", "ぱちぱち"),
+    ("🎉", "おめでとう"),
+    ("🙏", "おねがい"),
+    ("❤", "ハート"),
+    ("💕", "ハート"),
+    ("✨", "キラキラ"),
+];
+
+/// 文の終わりとみなす文字（打ち切り位置の候補）
+const TERMINATORS: &[char] = &['。', '！', '？', '!', '?', '♪'];
+
+static MD_LINK: Lazy<Regex> = Lazy::new(|| Regex::new(r"\[([^\]]+)\]\([^)]*\)").unwrap());
+static MD_LINE_PREFIX: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"(?m)^\s*(?:#{1,6}|>|[-*+]|\d+\.)\s+").unwrap());
+static MD_INLINE: Lazy<Regex> = Lazy::new(|| Regex::new(r"\*\*|__|~~|`|\*").unwrap());
+static URL: Lazy<Regex> = Lazy::new(|| Regex::new(r"https?://[^\s　）)」』]+").unwrap());
+
+/// 後処理の 1 段。
+#[derive(Debug, Clone)]
+pub enum Stage {
+    /// 見出し・箇条書き・強調・インラインコード・リンク記法を外す
+    Markdown,
+    /// URL を置き換える
+    Url { replacement: String },
+    /// 絵文字を読みに置き換え、読みのないものは消す
+    Emoji { readings: HashMap<String, String> },
+    /// NG ワードを伏せる（大文字小文字は区別しない）
+    NgWords { pattern: Regex, mask: String },
+}
+
+impl Stage {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Markdown => "markdown",
+            Self::Url { .. } => "url",
+            Self::Emoji { .. } => "emoji",
+            Self::NgWords { .. } => "ng_words",
+        }
+    }
+
+    pub fn apply(&self, text: &str) -> String {
+        match self {
+            Self::Markdown => {
+                let text = MD_LINK.replace_all(text, "$1");
+                let text = MD_LINE_PREFIX.replace_all(&text, "");
+                MD_INLINE.replace_all(&text, "").into_owned()
+            }
+            Self::Url { replacement } => URL.replace_all(text, replacement.as_str()).into_owned(),
+            Self::Emoji { readings } => {
+                let longest = readings
+                    .keys()
+                    .map(|k| k.chars().count())
+                    .max()
+                    .unwrap_or(0);
+                let mut out = String::with_capacity(text.len());
+                let mut rest = text;
+                while let Some(c) = rest.chars().next() {
+                    // 肌色・国旗・ZWJ 連結などは複数のコードポイントなので、長い読みから照合する
+                    let ends: Vec<usize> = rest
+                        .char_indices()
+                        .skip(1)
+                        .map(|(i, _)| i)
+                        .chain([rest.len()])
+                        .take(longest)
+                        .collect();
+                    if let Some((end, r)) = ends
+                        .iter()
+                        .rev()
+                        .find_map(|&end| readings.get(&rest[..end]).map(|r| (end, r)))
+                    {
+                        out.push_str(r);
+                        rest = &rest[end..];
+                        continue;
+                    }
+                    rest = &rest[c.len_utf8()..];
+                    // ☀️ のように絵文字表示の指定が続く記号も絵文字として消す
+                    if !is_emoji(c) && !rest.starts_with('\u{FE0F}') {
+                        out.push(c);
+                    }
+                }
+                out
+            }
+            Self::NgWords { pattern, mask } => {
+                pattern.replace_all(text, mask.as_str()).into_owned()
+            }
+        }
+    }
+
+    /// 既定の読みで [`Stage::Emoji`] を作る。`extra` で追加・上書きする。
+    pub fn emoji(extra: HashMap<String, String>) -> Self {
+        let mut readings: HashMap<String, String> = EMOJI_READINGS
+            .iter()
+            .map(|(e, r)| (e.to_string(), r.to_string()))
+            .collect();
+        readings.extend(extra);
+        Self::Emoji { readings }
+    }
+
+    /// NG ワードの一覧から [`Stage::NgWords`] を作る。空なら `None`。
+    pub fn ng_words(words: &[String], mask: &str) -> Result<Option<Self>> {
+        let words: Vec<String> = words
+            .iter()
+            .map(|w| w.trim())
+            .filter(|w| !w.is_empty())
+            .map(regex::escape)
+            .collect();
+        if words.is_empty() {
+            return Ok(None);
+        }
+        let pattern = Regex::new(&format!("(?i){}", words.join("|")))
+            .map_err(|e| Error::InvalidConfig(format!("NG_WORDS: {e}")))?;
+        Ok(Some(Self::NgWords {
+            pattern,
+            mask: mask.to_owned(),
+        }))
+    }
+}
+
+/// 絵文字・絵文字の修飾子か（読みのないものを消す判定用）。
+///
+/// 記号のブロック（U+2600 など）は ♪ ☆ ♡ のように文中で使う記号も含むので、
+/// 既定で絵文字として表示されるものだけを挙げる。
+fn is_emoji(c: char) -> bool {
+    matches!(
+        c as u32,
+        0x1F000..=0x1FAFF
+            | 0x231A..=0x231B
+            | 0x23E9..=0x23EC
+            | 0x23F0
+            | 0x23F3
+            | 0x25FD..=0x25FE
+            | 0x2614..=0x2615
+            | 0x2648..=0x2653
+            | 0x267F
+            | 0x2693
+            | 0x26A1
+            | 0x26AA..=0x26AB
+            | 0x26BD..=0x26BE
+            | 0x26C4..=0x26C5
+            | 0x26CE
+            | 0x26D4
+            | 0x26EA
+            | 0x26F2..=0x26F3
+            | 0x26F5
+            | 0x26FA
+            | 0x26FD
+            | 0x2705
+            | 0x270A..=0x270B
+            | 0x2728
+            | 0x274C
+            | 0x274E
+            | 0x2753..=0x2755
+            | 0x2757
+            | 0x2795..=0x2797
+            | 0x27B0
+            | 0x27BF
+            | 0x2B1B..=0x2B1C
+            | 0x2B50
+            | 0x2B55
+            // 異体字セレクタ・ZWJ・キーキャップ・タグ（旗の地域指定）
+            | 0xFE0F
+            | 0x200D
+            | 0x20E3
+            | 0xE0020..=0xE007F
+    )
+}
+
+/// 後処理の設定。
+#[derive(Debug, Clone)]
+pub struct Pipeline {
+    pub stages: Vec<Stage>,
+    /// 1 回の返答で話す最大文字数（0 で無制限）
+    pub max_chars: usize,
+}
+
+impl Default for Pipeline {
+    /// Markdown → URL → 絵文字、上限なし。
+    fn default() -> Self {
+        Self {
+            stages: vec![
+                Stage::Markdown,
+                Stage::Url {
+                    replacement: "リンク".into(),
+                },
+                Stage::emoji(HashMap::new()),
+            ],
+            max_chars: 0,
+        }
+    }
+}
+
+impl Pipeline {
+    /// すべてのステージを順に適用する。
+    pub fn apply(&self, text: &str) -> String {
+        let mut text = text.to_owned();
+        for stage in &self.stages {
+            let next = stage.apply(&text);
+            if next != text {
+                debug!(stage = stage.name(), before = %text, after = %next, "postprocess");
+                text = next;
+            }
+        }
+        text
+    }
+
+    /// 1 回の返答ぶんの処理を始める。
+    pub fn session(&self) -> Session<'_> {
+        Session {
+            pipeline: self,
+            spoken: 0,
+            in_code: false,
+            done: false,
+        }
+    }
+}
+
+/// 1 回の返答の後処理。文を順に渡す。
+#[derive(Debug)]
+pub struct Session<'p> {
+    pipeline: &'p Pipeline,
+    /// ここまでに話した文字数
+    spoken: usize,
+    /// コードブロックの中か
+    in_code: bool,
+    /// 上限に達して打ち切ったか
+    done: bool,
+}
+
+impl Session<'_> {
+    /// 文を後処理する。話す必要がなければ `None`。
+    pub fn push(&mut self, seg: Segment) -> Option<Segment> {
+        if self.done {
+            debug!(text = %seg.text, "postprocess: dropped after length limit");
+            return None;
+        }
+
+        let text = self.strip_code(&seg.text);
+        let mut text = self.pipeline.apply(&text).trim().to_owned();
+        if text.is_empty() {
+            return None;
+        }
+
+        let max = self.pipeline.max_chars;
+        let len = text.chars().count();
+        if max > 0 && self.spoken + len > max {
+            self.done = true;
+            let cut = truncate_at_sentence(&text, max - self.spoken);
+            debug!(before = %text, after = %cut, max, "postprocess: truncated at length limit");
+            text = cut;
+            if text.is_empty() {
+                return None;
+            }
+        }
+        self.spoken += text.chars().count();
+        Some(Segment {
+            emotion: seg.emotion,
+            text,
+        })
+    }
+
+    /// ``` で囲まれた部分を除く。
+    fn strip_code(&mut self, text: &str) -> String {
+        if !text.contains("```") && !self.in_code {
+            return text.to_owned();
+        }
+        let mut out = String::new();
+        for (i, part) in text.split("```").enumerate() {
+            if i > 0 {
+                self.in_code = !self.in_code;
+            }
+            if !self.in_code {
+                out.push_str(part);
+            }
+        }
+        debug!(before = %text, after = %out, "postprocess: code block removed");
+        out
+    }
+}
+
+/// `max` 文字以内で、最後の文の終わりまでを返す。切れ目がなければ空。
+fn truncate_at_sentence(text: &str, max: usize) -> String {
+    let head: String = text.chars().take(max).collect();
+    match head.rfind(TERMINATORS) {
+        Some(i) => {
+            let end = i + head[i..].chars().next().map_or(0, char::len_utf8);
+            head[..end].to_owned()
+        }
+        None => String::new(),
+    }
+}
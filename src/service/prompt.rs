@@ -1,46 +1,191 @@
-use crate::model::conversation::{Message, Role};
-use crate::model::gemini_dto;
+use crate::{
+    model::{chat_event::ChatEvent, conversation::Message, reply::ReplyFormat},
+    service::{
+        knowledge::Passage,
+        template::{Template, TemplateVars},
+    },
+};
 
 pub const EMOTION_GUIDE: &str =
     "各文頭に [neutral|happy|sad|angry|relaxed|surprised] のタグを必ず付けて返答してください。";
 
+pub const JSON_GUIDE: &str = "返答は {\"emotion\", \"text\"} の配列で、感情が変わるごとに要素を分けてください。\
+     emotion は neutral|happy|sad|angry|relaxed|surprised のいずれかです。";
+
+/// 履歴に投稿者付きのコメントがあるときに添える説明
+pub const ATTRIBUTION_GUIDE: &str = "視聴者のコメントは「[時刻] 名前: 本文」の形で渡します。\
+     誰の発言かを区別し、必要なら名前を呼んで答えてください。";
+
+/// 返答形式に応じた出力ガイド
+pub fn guide(format: ReplyFormat) -> &'static str {
+    match format {
+        ReplyFormat::Tags => EMOTION_GUIDE,
+        ReplyFormat::Json => JSON_GUIDE,
+    }
+}
+
+/// ペルソナに出力ガイドを付けたシステム指示
+pub fn system_text(system_prompt: &str, format: ReplyFormat) -> String {
+    format!("{system_prompt}\n{}", guide(format))
+}
+
+/// ペルソナとは別枠でシステム指示に差し込む、その時点の文脈。
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PromptContext<'a> {
+    /// これまでの配信の要約（[`Summarizer`](crate::service::summarizer::Summarizer) が更新）
+    pub summary: Option<&'a str>,
+    /// コメントした視聴者の記録（[`ViewerRecord::describe`](crate::service::viewer_memory::ViewerRecord::describe)）
+    pub viewer: Option<&'a str>,
+    /// ナレッジベースから引いた段落
+    pub knowledge: &'a [&'a Passage],
+    /// テンプレート変数（未指定ならすべて空）
+    pub vars: Option<&'a TemplateVars>,
+    /// 自律トークで振る話題（[`build_spontaneous_prompt`] のみ）
+    pub topic: Option<&'a str>,
+}
+
+impl PromptContext<'_> {
+    /// システム指示に追加する文字列。差し込む内容がなければ `None`。
+    pub fn render(&self) -> Option<String> {
+        let mut sections = Vec::new();
+        if let Some(summary) = self.summary.filter(|s| !s.trim().is_empty()) {
+            sections.push(format!("## これまでの配信の流れ\n{summary}"));
+        }
+        if !self.knowledge.is_empty() {
+            let mut s = String::from(
+                "## 参考情報\n配信者自身や配信についての事実は、以下の情報に従って答えてください。",
+            );
+            for p in self.knowledge {
+                s.push_str(&format!("\n- ({}) {}", p.source, p.text.replace('\n', " ")));
+            }
+            sections.push(s);
+        }
+        if let Some(viewer) = self.viewer {
+            sections.push(viewer.trim_end().to_owned());
+        }
+        (!sections.is_empty()).then(|| sections.join("\n\n"))
+    }
+}
+
+/// テンプレートを `ctx` の変数で展開する。
+fn render(template: &Template, ctx: &PromptContext<'_>) -> String {
+    match ctx.vars {
+        Some(vars) => template.render(vars),
+        None => template.render(&TemplateVars::default()),
+    }
+}
+
+/// チャットのイベントを LLM に渡すコメント本文にする。
+///
+/// スーパーチャットやメンバー加入には、お礼・歓迎を促す注記を先頭に付ける。
+pub fn event_text(event: &ChatEvent) -> String {
+    let text = &event.message().text;
+    let note = match event {
+        ChatEvent::SuperChat { amount, .. } => format!(
+            "（スーパーチャット {} をいただきました。まずお礼を伝えてください）",
+            amount.display
+        ),
+        ChatEvent::SuperSticker {
+            amount, sticker, ..
+        } => format!(
+            "（スーパーステッカー「{sticker}」{} をいただきました。お礼を伝えてください）",
+            amount.display
+        ),
+        ChatEvent::Membership { tier, .. } => match tier {
+            Some(tier) => format!("（メンバーになってくれました: {tier}。歓迎してください）"),
+            None => "（メンバーになってくれました。歓迎してください）".to_owned(),
+        },
+        _ => return text.clone(),
+    };
+    if text.is_empty() {
+        note
+    } else {
+        format!("{note}\n{text}")
+    }
+}
+
+/// [`event_text`] の注記を除いた、視聴者が書いた本文。
+pub fn comment_body(text: &str) -> &str {
+    let is_note = |line: &str| {
+        [
+            "（スーパーチャット ",
+            "（スーパーステッカー「",
+            "（メンバーになってくれました",
+        ]
+        .iter()
+        .any(|p| line.starts_with(p))
+            && line.ends_with('）')
+    };
+    match text.split_once('\n') {
+        Some((note, body)) if is_note(note) => body,
+        None if is_note(text) => "",
+        _ => text,
+    }
+}
+
+/// まとめて答える複数のコメントを 1 つのユーザーターンにする。
+///
+/// 誰へのコメントかわかるよう、投稿者名を付けて並べる。
+pub fn batch_comments<'c>(comments: impl IntoIterator<Item = (&'c str, &'c str)>) -> String {
+    let mut text = String::from(
+        "複数のコメントが届いています。いくつかを選び、名前を呼びながらまとめて答えてください。",
+    );
+    for (author, comment) in comments {
+        text.push_str(&format!("\n- {author}さん: {comment}"));
+    }
+    text
+}
+
 /// コメントへの通常応答用プロンプト
 pub fn build<'a>(
-    system_prompt: &'a str,
-    history: &'a [Message],
+    system_prompt: &Template,
+    history: &'a [Message<'a>],
     max_history: usize,
-) -> Vec<gemini_dto::Content<'a>> {
-    let mut contents = Vec::with_capacity(history.len() + 1);
+    format: ReplyFormat,
+    ctx: &PromptContext<'_>,
+) -> Vec<Message<'a>> {
+    let mut messages = Vec::with_capacity(history.len() + 2);
 
     // システム指示（ガイド追加済み）
-    let sys = format!("{system_prompt}\n{EMOTION_GUIDE}");
-    let sys_text = Box::leak(sys.into_boxed_str());
-    contents.push(gemini_dto::Content {
-        role: "user",
-        parts: vec![gemini_dto::Part { text: sys_text }],
-    });
-
-    // 履歴
     let start = history.len().saturating_sub(max_history * 2);
-    contents.extend(history[start..].iter().map(|msg| gemini_dto::Content {
-        role: match msg.role {
-            Role::User => "user",
-            Role::Bot => "model",
-            Role::System => "system",
-            Role::Assistant => "model", // Assuming Assistant should be treated like Bot
-        },
-        parts: vec![gemini_dto::Part { text: &msg.text }],
-    }));
+    let mut system = system_text(&render(system_prompt, ctx), format);
+    if history[start..].iter().any(|m| m.author.is_some()) {
+        system.push('\n');
+        system.push_str(ATTRIBUTION_GUIDE);
+    }
+    messages.push(Message::system(system));
+    if let Some(text) = ctx.render() {
+        messages.push(Message::system(text));
+    }
+
+    // 履歴（視聴者のコメントは投稿者と時刻を付ける）
+    messages.extend(
+        history[start..]
+            .iter()
+            .map(|msg| Message::new(msg.role, msg.attributed())),
+    );
 
-    contents
+    messages
 }
 
 /// コメントが途切れた際の自律トーク用プロンプト
-pub fn build_spontaneous_prompt(spontaneous_prompt: &str) -> Vec<gemini_dto::Content<'_>> {
-    vec![gemini_dto::Content {
-        role: "user",
-        parts: vec![gemini_dto::Part {
-            text: Box::leak(format!("{spontaneous_prompt}\n{EMOTION_GUIDE}").into_boxed_str()),
-        }],
-    }]
+///
+/// ペルソナはシステム指示のまま、話題振りの指示をユーザーターンとして渡す。
+pub fn build_spontaneous_prompt(
+    system_prompt: &Template,
+    spontaneous_prompt: &Template,
+    format: ReplyFormat,
+    ctx: &PromptContext<'_>,
+) -> Vec<Message<'static>> {
+    let mut messages = vec![Message::system(system_text(
+        &render(system_prompt, ctx),
+        format,
+    ))];
+    messages.extend(ctx.render().map(Message::system));
+    let mut instruction = render(spontaneous_prompt, ctx);
+    if let Some(topic) = ctx.topic {
+        instruction.push_str(&format!("\n話題: {topic}"));
+    }
+    messages.push(Message::user(instruction));
+    messages
 }
@@ -0,0 +1,306 @@
+//! 返答するコメントを選ぶスケジューラ。
+//!
+//! - 届いたコメントを溜めておき、ボットの手が空いたときに最もスコアの高いものを返す。
+//! - スコアはスーパーチャット・メンバー加入・初見さん・質問・キャラクター名への言及で上がり、
+//!   古いほど下がる。
+//! - ほぼ同じ文面は 1 件にまとめ、重複数をスコアに足す（みんなが聞いている質問を優先）。
+//!   スーパーチャットとメンバー加入は定型文でも視聴者ごとに答えるのでまとめない。
+//! - `max_age` を過ぎたコメントは返さずに捨てる。
+//! - まとめて答えるモードでは、上位の数件を一度に取り出す（[`CommentQueue::next_batch`]）。
+
+use std::{
+    collections::HashSet,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use chrono::{DateTime, Utc};
+use tokio::sync::Notify;
+use tracing::debug;
+
+use crate::model::conversation::Author;
+
+/// 同じ文面とみなす文字 bi-gram の Jaccard 係数
+const DUPLICATE_SIMILARITY: f64 = 0.8;
+
+/// 質問らしさの手がかり
+const QUESTION_MARKERS: &[&str] = &[
+    "？",
+    "?",
+    "なに",
+    "何",
+    "どう",
+    "どこ",
+    "いつ",
+    "だれ",
+    "誰",
+    "なんで",
+    "教えて",
+];
+
+/// スケジューラの設定。
+#[derive(Debug, Clone)]
+pub struct SchedulerConfig {
+    /// これより古いコメントは返さない
+    pub max_age: Duration,
+    /// 溜めておく最大件数（超えたら最低スコアを捨てる）
+    pub capacity: usize,
+    /// キャラクターの呼び名（言及されたら優先）
+    pub character_names: Vec<String>,
+}
+
+impl Default for SchedulerConfig {
+    fn default() -> Self {
+        Self {
+            max_age: Duration::from_secs(60),
+            capacity: 50,
+            character_names: Vec::new(),
+        }
+    }
+}
+
+/// 返答候補のコメント。
+#[derive(Debug, Clone)]
+pub struct Comment {
+    pub author: Author,
+    pub text: String,
+    pub received_at: Instant,
+    /// 投稿時刻（プロンプトに載せる）
+    pub timestamp: DateTime<Utc>,
+    /// 初めてコメントした視聴者か
+    pub first_time: bool,
+    /// スーパーチャットなどの金額（円換算の目安）
+    pub paid_amount: Option<f64>,
+    /// メンバー加入・継続・ギフトのお知らせか
+    pub membership: bool,
+    /// まとめた重複コメントの数
+    pub duplicates: usize,
+}
+
+impl Comment {
+    pub fn new(author: impl Into<Author>, text: impl Into<String>, received_at: Instant) -> Self {
+        Self {
+            author: author.into(),
+            text: text.into(),
+            received_at,
+            timestamp: Utc::now(),
+            first_time: false,
+            paid_amount: None,
+            membership: false,
+            duplicates: 0,
+        }
+    }
+
+    /// 重複としてまとめてよいか（お礼・歓迎が必要なものは個別に答える）。
+    fn mergeable(&self) -> bool {
+        self.paid_amount.is_none() && !self.membership
+    }
+}
+
+/// 優先度付きのコメント置き場。
+#[derive(Debug, Default)]
+pub struct Scheduler {
+    cfg: SchedulerConfig,
+    queue: Vec<(Comment, HashSet<(char, char)>)>,
+}
+
+impl Scheduler {
+    pub fn new(cfg: SchedulerConfig) -> Self {
+        Self {
+            cfg,
+            queue: Vec::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.queue.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.queue.clear();
+    }
+
+    /// コメントのスコア。
+    pub fn score(&self, c: &Comment, now: Instant) -> f64 {
+        let mut score = 1.0;
+        if let Some(amount) = c.paid_amount {
+            // 金額の大小は緩やかに反映する
+            score += 5.0 + (amount.max(1.0)).log10();
+        }
+        if c.membership {
+            score += 4.0;
+        }
+        if c.first_time {
+            score += 2.0;
+        }
+        if QUESTION_MARKERS.iter().any(|m| c.text.contains(m)) {
+            score += 1.5;
+        }
+        if self
+            .cfg
+            .character_names
+            .iter()
+            .any(|n| !n.is_empty() && c.text.contains(n.as_str()))
+        {
+            score += 2.0;
+        }
+        score += c.duplicates as f64;
+
+        let age = now.saturating_duration_since(c.received_at).as_secs_f64();
+        score - age / self.cfg.max_age.as_secs_f64().max(1.0)
+    }
+
+    /// コメントを追加する。ほぼ同じコメントがあればそちらにまとめて `false`。
+    pub fn push(&mut self, comment: Comment, now: Instant) -> bool {
+        let grams = bigrams(&comment.text);
+        // スーパーチャット・メンバー加入は重複扱いせず、必ず個別に答える
+        if let Some((dup, _)) = self
+            .queue
+            .iter_mut()
+            .filter(|_| comment.mergeable())
+            .find(|(c, g)| c.mergeable() && jaccard(g, &grams) >= DUPLICATE_SIMILARITY)
+        {
+            dup.duplicates += 1;
+            // 新しい方の時刻にして、期限切れになりにくくする
+            dup.received_at = comment.received_at;
+            debug!(author = %comment.author, text = %comment.text, into = %dup.text, "comment merged as duplicate");
+            return false;
+        }
+
+        self.queue.push((comment, grams));
+        if self.queue.len() > self.cfg.capacity {
+            let worst = self.position_by(now, |a, b| b < a);
+            let (c, _) = self.queue.swap_remove(worst);
+            debug!(author = %c.author, text = %c.text, "comment evicted (queue full)");
+        }
+        true
+    }
+
+    /// 期限切れを捨て、最もスコアの高いコメントを取り出す。
+    pub fn pop_best(&mut self, now: Instant) -> Option<Comment> {
+        let max_age = self.cfg.max_age;
+        self.queue.retain(|(c, _)| {
+            let fresh = now.saturating_duration_since(c.received_at) <= max_age;
+            if !fresh {
+                debug!(author = %c.author, text = %c.text, "comment expired");
+            }
+            fresh
+        });
+        if self.queue.is_empty() {
+            return None;
+        }
+        let best = self.position_by(now, |a, b| a < b);
+        let (c, _) = self.queue.swap_remove(best);
+        debug!(
+            author = %c.author,
+            text = %c.text,
+            score = self.score(&c, now),
+            waiting = self.queue.len(),
+            "comment picked"
+        );
+        Some(c)
+    }
+
+    /// スコアの高い順に最大 `max` 件取り出す。
+    pub fn pop_batch(&mut self, now: Instant, max: usize) -> Vec<Comment> {
+        std::iter::from_fn(|| self.pop_best(now))
+            .take(max)
+            .collect()
+    }
+
+    /// `better(現在の最良, 候補)` が真になる候補の位置
+    fn position_by(&self, now: Instant, better: impl Fn(f64, f64) -> bool) -> usize {
+        let mut idx = 0;
+        let mut best = self.score(&self.queue[0].0, now);
+        for (i, (c, _)) in self.queue.iter().enumerate().skip(1) {
+            let s = self.score(c, now);
+            if better(best, s) {
+                best = s;
+                idx = i;
+            }
+        }
+        idx
+    }
+}
+
+/// タスク間で共有するスケジューラ。クローンは同じキューを指す。
+#[derive(Debug, Clone, Default)]
+pub struct CommentQueue {
+    inner: Arc<Mutex<Scheduler>>,
+    notify: Arc<Notify>,
+}
+
+impl CommentQueue {
+    pub fn new(cfg: SchedulerConfig) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(Scheduler::new(cfg))),
+            notify: Arc::new(Notify::new()),
+        }
+    }
+
+    pub fn push(&self, comment: Comment) {
+        if self.inner.lock().unwrap().push(comment, Instant::now()) {
+            self.notify.notify_one();
+        }
+    }
+
+    /// 待っているコメントをすべて捨てる。
+    pub fn clear(&self) {
+        self.inner.lock().unwrap().clear();
+    }
+
+    /// 次に返答するコメントを待つ。`select!` で中断しても取りこぼさない。
+    pub async fn next(&self) -> Comment {
+        loop {
+            if let Some(c) = self.inner.lock().unwrap().pop_best(Instant::now()) {
+                return c;
+            }
+            self.notify.notified().await;
+        }
+    }
+
+    /// 次にまとめて返答するコメントを最大 `max` 件待つ。
+    ///
+    /// 1 件目が届いたら `window` だけ待って後続を集める（再生中に届いたぶんは待たずに揃う）。
+    /// 取り出すのは待ち終えてからなので、`select!` で中断しても取りこぼさない。
+    pub async fn next_batch(&self, max: usize, window: Duration) -> Vec<Comment> {
+        let max = max.max(1);
+        loop {
+            while self.inner.lock().unwrap().is_empty() {
+                self.notify.notified().await;
+            }
+            if max > 1 && self.inner.lock().unwrap().len() < max {
+                tokio::time::sleep(window).await;
+            }
+            let batch = self.inner.lock().unwrap().pop_batch(Instant::now(), max);
+            if !batch.is_empty() {
+                return batch;
+            }
+        }
+    }
+}
+
+/// 空白を除いた文字 bi-gram
+fn bigrams(text: &str) -> HashSet<(char, char)> {
+    let chars: Vec<char> = text
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .flat_map(char::to_lowercase)
+        .collect();
+    if chars.len() == 1 {
+        return HashSet::from([(chars[0], chars[0])]);
+    }
+    chars.windows(2).map(|w| (w[0], w[1])).collect()
+}
+
+fn jaccard(a: &HashSet<(char, char)>, b: &HashSet<(char, char)>) -> f64 {
+    if a.is_empty() || b.is_empty() {
+        return 0.0;
+    }
+    let inter = a.intersection(b).count();
+    inter as f64 / (a.len() + b.len() - inter) as f64
+}
@@ -0,0 +1,260 @@
+//! LLM 出力を「感情タグ付きの文」単位に区切る。
+//!
+//! - ストリーミング中のチャンクを [`SentenceSplitter::push`] に流し込むと、
+//!   句点・感嘆符・改行・次のタグで完結した文から順に [`Segment`] を返す。
+//! - 直前の `[happy]` などのタグがその文の感情になる（未指定は `Neutral`）。
+//! - 一括変換したい場合は [`split_all`] を使う。
+//! - 構造化 (JSON) 返答は [`JsonSegments`] が配列要素の完成ごとに取り出す。
+//!   形式を問わず扱う場合は [`Segmenter`] を使う。
+//!
+//! ## 例
+//! ```rust
+//! use ai_tuber::{model::emotion::Emotion, service::segmenter::SentenceSplitter};
+//!
+//! let mut sp = SentenceSplitter::new();
+//! assert!(sp.push("[happy]こんに").is_empty());
+//! let done = sp.push("ちは！[sad]また");
+//! assert_eq!(done[0].emotion, Emotion::Happy);
+//! assert_eq!(done[0].text, "こんにちは！");
+//! assert_eq!(sp.finish().unwrap().emotion, Emotion::Sad);
+//! ```
+
+use once_cell::sync::Lazy;
+use regex::Regex;
+
+use crate::{
+    error::{Error, Result},
+    model::{
+        emotion::Emotion,
+        reply::{self, ReplyFormat},
+    },
+};
+
+pub use crate::model::reply::Segment;
+
+/// 感情タグ `[neutral]` 等（後続の空白も含めて除去する）。
+static TAG_RE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"(?i)\[(neutral|happy|sad|angry|relaxed|surprised)\]\s*").expect("valid regex")
+});
+
+/// 文末とみなす文字
+const TERMINATORS: &[char] = &['。', '！', '？', '!', '?', '\n'];
+
+/// 文末記号の直後に続いても同じ文に含める閉じ括弧など
+const CLOSERS: &[char] = &['」', '』', '）', ')', '…', '～', '〜'];
+
+/// チャンクを受け取り、完結した文を順に取り出すバッファ。
+#[derive(Debug, Clone)]
+pub struct SentenceSplitter {
+    buf: String,
+    emotion: Emotion,
+}
+
+impl Default for SentenceSplitter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SentenceSplitter {
+    pub fn new() -> Self {
+        Self {
+            buf: String::new(),
+            emotion: Emotion::Neutral,
+        }
+    }
+
+    /// チャンクを追加し、完結した文をすべて返す。
+    pub fn push(&mut self, chunk: &str) -> Vec<Segment> {
+        self.buf.push_str(chunk);
+
+        let mut out = Vec::new();
+        loop {
+            let tag = TAG_RE.captures(&self.buf).map(|c| {
+                let m = c.get(0).unwrap();
+                (m.start(), m.end(), c[1].parse().unwrap_or(Emotion::Neutral))
+            });
+            let term = sentence_end(&self.buf);
+
+            match (tag, term) {
+                // タグが先に来た: 直前までを前の感情で確定し、感情を切り替える
+                (Some((start, end, emo)), t) if t.is_none_or(|t| start < t) => {
+                    self.emit(start, &mut out);
+                    self.buf.drain(..end - start);
+                    self.emotion = emo;
+                }
+                // 文末がバッファ末尾なら「！？」のように続く可能性があるので待つ
+                (_, Some(end)) if end < self.buf.len() => self.emit(end, &mut out),
+                _ => break,
+            }
+        }
+        out
+    }
+
+    /// 残りのバッファを最後の文として取り出す。
+    pub fn finish(mut self) -> Option<Segment> {
+        let mut out = Vec::new();
+        let len = self.buf.len();
+        self.emit(len, &mut out);
+        out.pop()
+    }
+
+    /// `buf[..end]` を切り出し、空でなければ `out` に積む。
+    fn emit(&mut self, end: usize, out: &mut Vec<Segment>) {
+        let text: String = self.buf.drain(..end).collect();
+        let text = text.trim();
+        if !text.is_empty() {
+            out.push(Segment {
+                emotion: self.emotion,
+                text: text.to_owned(),
+            });
+        }
+    }
+}
+
+/// 最初の文末（閉じ括弧・連続する文末記号を含む）の終端バイト位置。
+fn sentence_end(s: &str) -> Option<usize> {
+    let (pos, c) = s.char_indices().find(|(_, c)| TERMINATORS.contains(c))?;
+    let mut end = pos + c.len_utf8();
+    for c in s[end..].chars() {
+        if TERMINATORS.contains(&c) || CLOSERS.contains(&c) {
+            end += c.len_utf8();
+        } else {
+            break;
+        }
+    }
+    Some(end)
+}
+
+/// 完成済みの返答をまとめて分割する。
+pub fn split_all(text: &str) -> Vec<Segment> {
+    let mut sp = SentenceSplitter::new();
+    let mut out = sp.push(text);
+    out.extend(sp.finish());
+    out
+}
+
+/// `[{"emotion": ..., "text": ...}, ...]` をストリームのまま要素ごとに取り出す。
+///
+/// ```rust
+/// use ai_tuber::{model::emotion::Emotion, service::segmenter::JsonSegments};
+///
+/// let mut js = JsonSegments::new();
+/// assert!(js.push(r#"[{"emotion":"happy","text":"や"#).unwrap().is_empty());
+/// let done = js.push(r#"っほー！"},{"emotion":"#).unwrap();
+/// assert_eq!(done[0].emotion, Emotion::Happy);
+/// assert_eq!(done[0].text, "やっほー！");
+/// assert!(js.finish().is_err());
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct JsonSegments {
+    buf: String,
+    /// `buf` のうち走査済みのバイト数
+    scanned: usize,
+    depth: usize,
+    in_str: bool,
+    escaped: bool,
+    /// 現在読み取り中の要素の開始位置
+    obj_start: usize,
+}
+
+impl JsonSegments {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// チャンクを追加し、閉じ括弧まで揃った要素をすべて返す。
+    pub fn push(&mut self, chunk: &str) -> Result<Vec<Segment>> {
+        self.buf.push_str(chunk);
+
+        let mut out = Vec::new();
+        for (i, c) in self.buf[self.scanned..].char_indices() {
+            let i = self.scanned + i;
+            if self.in_str {
+                match c {
+                    _ if self.escaped => self.escaped = false,
+                    '\\' => self.escaped = true,
+                    '"' => self.in_str = false,
+                    _ => {}
+                }
+                continue;
+            }
+            match c {
+                '"' => self.in_str = true,
+                '[' => self.depth += 1,
+                '{' => {
+                    if self.depth == 1 {
+                        self.obj_start = i;
+                    }
+                    self.depth += 1;
+                }
+                ']' | '}' => {
+                    self.depth = self.depth.checked_sub(1).ok_or_else(|| {
+                        Error::InvalidGeminiResponse("unbalanced JSON reply".into())
+                    })?;
+                    if c == '}' && self.depth == 1 {
+                        let seg: Segment = serde_json::from_str(&self.buf[self.obj_start..=i])
+                            .map_err(|e| {
+                                Error::InvalidGeminiResponse(format!(
+                                    "reply does not match schema: {e}"
+                                ))
+                            })?;
+                        if !seg.text.trim().is_empty() {
+                            out.push(seg);
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        self.scanned = self.buf.len();
+        Ok(out)
+    }
+
+    /// 配列が閉じているか検査する。
+    pub fn finish(self) -> Result<()> {
+        if self.depth != 0 || self.in_str {
+            return Err(Error::InvalidGeminiResponse("incomplete JSON reply".into()));
+        }
+        Ok(())
+    }
+}
+
+/// 返答形式に応じた分割器。
+#[derive(Debug, Clone)]
+pub enum Segmenter {
+    Tags(SentenceSplitter),
+    Json(JsonSegments),
+}
+
+impl Segmenter {
+    pub fn new(format: ReplyFormat) -> Self {
+        match format {
+            ReplyFormat::Tags => Self::Tags(SentenceSplitter::new()),
+            ReplyFormat::Json => Self::Json(JsonSegments::new()),
+        }
+    }
+
+    pub fn push(&mut self, chunk: &str) -> Result<Vec<Segment>> {
+        match self {
+            Self::Tags(sp) => Ok(sp.push(chunk)),
+            Self::Json(js) => js.push(chunk),
+        }
+    }
+
+    /// 残りの要素を取り出す。JSON が途中で切れていればエラー。
+    pub fn finish(self) -> Result<Vec<Segment>> {
+        match self {
+            Self::Tags(sp) => Ok(sp.finish().into_iter().collect()),
+            Self::Json(js) => js.finish().map(|()| Vec::new()),
+        }
+    }
+}
+
+/// 完成済みの返答を形式に応じて分割する。
+pub fn split(text: &str, format: ReplyFormat) -> Result<Vec<Segment>> {
+    match format {
+        ReplyFormat::Tags => Ok(split_all(text)),
+        ReplyFormat::Json => reply::parse_json(text),
+    }
+}
@@ -0,0 +1,99 @@
+//! 長時間配信向けの「これまでの流れ」要約。
+//!
+//! - 履歴から外れたターン（[`Conversation::dropped`]）が一定数たまったら、
+//!   前回の要約と合わせて LLM に渡し、新しい要約に畳み込む。
+//! - 要約は [`PromptContext::summary`](crate::service::prompt::PromptContext) として
+//!   システム指示に差し込まれる。
+//! - 要約に失敗しても配信は止めない。未要約のターンは次回に持ち越す（上限あり）。
+//! - 返答用の JSON モードやツールは使わず、[`LlmBackend::plain`] で自由記述として頼む。
+
+use tracing::{debug, warn};
+
+use crate::{
+    model::conversation::{Conversation, Message, Role},
+    service::api::llm::LlmBackend,
+};
+
+/// 記録係としての指示
+const INSTRUCTION: &str = "あなたは配信の記録係です。\
+これまでの要約と新しいやり取りを読み、配信の流れを日本語で簡潔にまとめ直してください。\
+話題・視聴者の名前や出来事・約束ごとなど、後で話を振るのに役立つことを優先して残してください。\
+感情タグや前置きは付けず、要約本文だけを出力してください。";
+
+/// 失敗が続いたとき、持ち越す未要約ターンの上限（バッチの何倍か）
+const MAX_PENDING_BATCHES: usize = 4;
+
+#[derive(Debug, Clone, Copy)]
+pub struct Summarizer {
+    /// 何ターンたまったら要約するか
+    pub batch: usize,
+    /// 要約の目安の文字数
+    pub max_chars: usize,
+}
+
+impl Summarizer {
+    pub fn new(batch: usize, max_chars: usize) -> Self {
+        Self {
+            batch: batch.max(1),
+            max_chars,
+        }
+    }
+
+    /// 未要約のターンがたまっていれば要約を更新する。更新したら `true`。
+    pub async fn update(&self, llm: &dyn LlmBackend, conv: &mut Conversation) -> bool {
+        if conv.dropped.len() < self.batch {
+            return false;
+        }
+
+        let req = self.request(&conv.summary, &conv.dropped);
+        let plain = llm.plain();
+        let llm = plain.as_deref().unwrap_or(llm);
+        match llm.chat(&req).await {
+            Ok(text) if !text.trim().is_empty() => {
+                conv.summary = text.trim().to_owned();
+                debug!(
+                    turns = conv.dropped.len(),
+                    chars = conv.summary.chars().count(),
+                    "stream summary updated"
+                );
+                conv.dropped.clear();
+                true
+            }
+            res => {
+                let reason = res.err().map_or("empty summary".into(), |e| e.to_string());
+                warn!(%reason, pending = conv.dropped.len(), "failed to update stream summary");
+                let cap = self.batch * MAX_PENDING_BATCHES;
+                if conv.dropped.len() > cap {
+                    let excess = conv.dropped.len() - cap;
+                    conv.dropped.drain(..excess);
+                }
+                false
+            }
+        }
+    }
+
+    /// 要約用のリクエストを組み立てる。
+    pub fn request(&self, summary: &str, turns: &[Message<'_>]) -> Vec<Message<'static>> {
+        let mut body = String::new();
+        if !summary.is_empty() {
+            body.push_str("## これまでの要約\n");
+            body.push_str(summary);
+            body.push_str("\n\n");
+        }
+        body.push_str("## 新しいやり取り\n");
+        for msg in turns {
+            let who = match (msg.role, &msg.author) {
+                (Role::User, Some(author)) => format!("視聴者（{}）", author.name),
+                (Role::User, None) => "視聴者".to_owned(),
+                _ => "配信者".to_owned(),
+            };
+            body.push_str(&format!("{who}: {}\n", msg.text));
+        }
+        body.push_str(&format!(
+            "\n以上を {} 文字以内の要約にまとめてください。",
+            self.max_chars
+        ));
+
+        vec![Message::system(INSTRUCTION), Message::user(body)]
+    }
+}
@@ -0,0 +1,227 @@
+//! システムプロンプト用の小さなテンプレートエンジン。
+//!
+//! - `{{name}}` で変数を展開する。使える変数は [`VARIABLES`] のみ。
+//! - `{{#if name}}…{{else}}…{{/if}}` で、変数が空でないときだけ差し込む（入れ子可）。
+//! - 未知の変数や閉じ忘れはパース時に [`Error::InvalidConfig`] になるので、起動時に気付ける。
+//! - `{{` をそのまま書きたいときは `\{{` とエスケープする。
+//!   **互換性の注意:** テンプレート導入前から `BOT_SYSTEM_PROMPT` などに `{{` を書いていた場合、
+//!   エスケープするまで起動時にエラーになる。
+//! - `viewer_name` / `recent_topics` は視聴者の書いた文字列なので、[`viewer_text`] で
+//!   1 行にまとめ長さを切ってから渡す。
+//!
+//! ```
+//! use ai_tuber::service::template::{Template, TemplateVars};
+//!
+//! let t = Template::parse("{{#if viewer_name}}{{viewer_name}}さん、{{else}}みんな、{{/if}}こんばんは").unwrap();
+//! let vars = TemplateVars { viewer_name: "たろう".into(), ..TemplateVars::default() };
+//! assert_eq!(t.render(&vars), "たろうさん、こんばんは");
+//! assert_eq!(t.render(&TemplateVars::default()), "みんな、こんばんは");
+//! assert!(Template::parse("{{weather}}").is_err());
+//! assert_eq!(Template::parse(r"\{{weather}}").unwrap().render(&vars), "{{weather}}");
+//! ```
+
+use crate::error::{Error, Result};
+
+/// テンプレートで使える変数名
+pub const VARIABLES: &[&str] = &[
+    "viewer_name",
+    "now",
+    "stream_uptime",
+    "stream_title",
+    "mood",
+    "recent_topics",
+];
+
+/// `viewer_name` の上限文字数
+pub const MAX_VIEWER_NAME_CHARS: usize = 30;
+/// `recent_topics` の上限文字数
+pub const MAX_RECENT_TOPICS_CHARS: usize = 120;
+
+/// 視聴者の書いた文字列をシステム指示に差し込める形にする。
+///
+/// 改行・制御文字を含む空白の並びを 1 つの空白にまとめ（見出しや指示行を作らせない）、
+/// `max_chars` 文字を超えたら切り詰めて `…` を付ける。
+pub fn viewer_text(text: &str, max_chars: usize) -> String {
+    let line = text
+        .split(|c: char| c.is_whitespace() || c.is_control())
+        .filter(|w| !w.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ");
+    if line.chars().count() <= max_chars {
+        return line;
+    }
+    let mut out: String = line.chars().take(max_chars.saturating_sub(1)).collect();
+    out.push('…');
+    out
+}
+
+/// リクエストごとに埋め込む値。空文字は「値なし」として `{{#if}}` で偽になる。
+#[derive(Debug, Clone, Default)]
+pub struct TemplateVars {
+    /// コメントした視聴者の名前
+    pub viewer_name: String,
+    /// 現在時刻（`YYYY-MM-DD HH:MM`）
+    pub now: String,
+    /// 配信開始からの経過時間（例: `1時間5分`）
+    pub stream_uptime: String,
+    pub stream_title: String,
+    /// 直前の返答の感情
+    pub mood: String,
+    /// 最近のコメントの話題
+    pub recent_topics: String,
+}
+
+impl TemplateVars {
+    pub fn get(&self, name: &str) -> &str {
+        match name {
+            "viewer_name" => &self.viewer_name,
+            "now" => &self.now,
+            "stream_uptime" => &self.stream_uptime,
+            "stream_title" => &self.stream_title,
+            "mood" => &self.mood,
+            "recent_topics" => &self.recent_topics,
+            _ => "",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Node {
+    Text(String),
+    Var(String),
+    If {
+        name: String,
+        then: Vec<Node>,
+        otherwise: Vec<Node>,
+    },
+}
+
+/// パース済みのテンプレート。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Template {
+    nodes: Vec<Node>,
+}
+
+impl Template {
+    /// テンプレートを解析する。構文エラー・未知の変数は [`Error::InvalidConfig`]。
+    pub fn parse(src: &str) -> Result<Self> {
+        let mut parser = Parser { rest: src };
+        let (nodes, end) = parser.nodes()?;
+        match end {
+            End::Eof => Ok(Self { nodes }),
+            End::Else => Err(invalid("`{{else}}` without `{{#if}}`")),
+            End::Close => Err(invalid("`{{/if}}` without `{{#if}}`")),
+        }
+    }
+
+    /// 変数を埋め込んだ文字列を返す。
+    pub fn render(&self, vars: &TemplateVars) -> String {
+        let mut out = String::new();
+        render_nodes(&self.nodes, vars, &mut out);
+        out
+    }
+}
+
+fn render_nodes(nodes: &[Node], vars: &TemplateVars, out: &mut String) {
+    for node in nodes {
+        match node {
+            Node::Text(t) => out.push_str(t),
+            Node::Var(name) => out.push_str(vars.get(name)),
+            Node::If {
+                name,
+                then,
+                otherwise,
+            } => {
+                let branch = if vars.get(name).is_empty() {
+                    otherwise
+                } else {
+                    then
+                };
+                render_nodes(branch, vars, out);
+            }
+        }
+    }
+}
+
+fn invalid(msg: impl std::fmt::Display) -> Error {
+    Error::InvalidConfig(format!("prompt template: {msg}"))
+}
+
+/// ノード列の終わり方
+enum End {
+    Eof,
+    Else,
+    Close,
+}
+
+struct Parser<'s> {
+    rest: &'s str,
+}
+
+impl Parser<'_> {
+    /// `{{else}}` / `{{/if}}` / 末尾までのノードを読む。
+    fn nodes(&mut self) -> Result<(Vec<Node>, End)> {
+        let mut nodes = Vec::new();
+        loop {
+            let Some(open) = self.rest.find("{{") else {
+                if !self.rest.is_empty() {
+                    nodes.push(Node::Text(self.rest.to_owned()));
+                }
+                self.rest = "";
+                return Ok((nodes, End::Eof));
+            };
+            // `\{{` は `{{` そのもの
+            if self.rest[..open].ends_with('\\') {
+                nodes.push(Node::Text(format!("{}{{{{", &self.rest[..open - 1])));
+                self.rest = &self.rest[open + 2..];
+                continue;
+            }
+            if open > 0 {
+                nodes.push(Node::Text(self.rest[..open].to_owned()));
+            }
+            let after = &self.rest[open + 2..];
+            let close = after
+                .find("}}")
+                .ok_or_else(|| invalid("unclosed `{{` (write `\\{{` for a literal `{{`)"))?;
+            let tag = after[..close].trim();
+            self.rest = &after[close + 2..];
+
+            if tag == "else" {
+                return Ok((nodes, End::Else));
+            }
+            if tag == "/if" {
+                return Ok((nodes, End::Close));
+            }
+            if let Some(name) = tag.strip_prefix("#if ") {
+                let name = variable(name.trim())?;
+                let (then, end) = self.nodes()?;
+                let otherwise = match end {
+                    End::Close => Vec::new(),
+                    End::Else => match self.nodes()? {
+                        (nodes, End::Close) => nodes,
+                        _ => return Err(invalid(format!("`{{{{#if {name}}}}}` is not closed"))),
+                    },
+                    End::Eof => return Err(invalid(format!("`{{{{#if {name}}}}}` is not closed"))),
+                };
+                nodes.push(Node::If {
+                    name,
+                    then,
+                    otherwise,
+                });
+            } else {
+                nodes.push(Node::Var(variable(tag)?));
+            }
+        }
+    }
+}
+
+fn variable(name: &str) -> Result<String> {
+    if VARIABLES.contains(&name) {
+        Ok(name.to_owned())
+    } else {
+        Err(invalid(format!(
+            "unknown variable `{name}` (available: {}; write `\\{{{{` for a literal `{{{{`)",
+            VARIABLES.join(", ")
+        )))
+    }
+}
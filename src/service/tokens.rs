@@ -0,0 +1,143 @@
+//! トークン数の計測と、トークン予算に収まる履歴ウィンドウの計算。
+//!
+//! - 計測はバックエンドの [`LlmBackend::count_tokens`]（Gemini は `countTokens`）を使い、
+//!   失敗時やオフライン時は [`estimate`] の概算で代用する。
+//! - 同じテキストは一度しか数えないよう、結果をキャッシュする。
+//! - [`TokenBudget`] はシステム指示と返答ぶんを差し引いた残りに、新しい履歴から詰める。
+//!   数えるのは [`prompt::build`](crate::service::prompt::build) が実際に送るメッセージ
+//!   （差し込み情報・投稿者と時刻付き）。
+
+use std::{
+    collections::HashMap,
+    hash::{DefaultHasher, Hash, Hasher},
+};
+
+use tracing::{debug, warn};
+
+use crate::{
+    model::conversation::{Message, Role},
+    service::api::llm::LlmBackend,
+};
+
+/// メッセージごとのロール・区切りなどのオーバーヘッド（概算）
+const MESSAGE_OVERHEAD: usize = 4;
+
+/// 文字種からトークン数を概算する。
+///
+/// ASCII は 4 文字で 1 トークン、それ以外（かな・漢字・絵文字）は 1 文字 1 トークンとみなす。
+/// 日本語主体の会話では実測よりやや多めに出るため、予算超過の心配はない。
+pub fn estimate(text: &str) -> usize {
+    let (ascii, other) = text.chars().fold((0usize, 0usize), |(a, o), c| {
+        if c.is_ascii() { (a + 1, o) } else { (a, o + 1) }
+    });
+    ascii.div_ceil(4) + other
+}
+
+/// テキスト → トークン数のキャッシュ付きカウンタ。
+#[derive(Debug, Default)]
+pub struct TokenCounter {
+    cache: HashMap<u64, usize>,
+}
+
+impl TokenCounter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// `text` のトークン数。API が失敗したら概算値を返す。
+    pub async fn count(&mut self, llm: &dyn LlmBackend, text: &str) -> usize {
+        let key = hash(text);
+        if let Some(&n) = self.cache.get(&key) {
+            return n;
+        }
+        let n = MESSAGE_OVERHEAD
+            + match llm.count_tokens(text).await {
+                Ok(n) => n,
+                Err(e) => {
+                    warn!(error = %e, "token counting failed; using local estimate");
+                    estimate(text)
+                }
+            };
+        self.cache.insert(key, n);
+        n
+    }
+
+    /// `keep` 以外のキャッシュを捨てる（履歴から消えたメッセージのぶん）。
+    fn retain<'t>(&mut self, keep: impl IntoIterator<Item = &'t str>) {
+        let keep: Vec<u64> = keep.into_iter().map(hash).collect();
+        self.cache.retain(|k, _| keep.contains(k));
+    }
+}
+
+fn hash(text: &str) -> u64 {
+    let mut h = DefaultHasher::new();
+    text.hash(&mut h);
+    h.finish()
+}
+
+/// プロンプト全体のトークン予算。
+#[derive(Debug, Clone, Copy)]
+pub struct TokenBudget {
+    /// システム指示・履歴・返答を合わせた上限
+    pub context_tokens: usize,
+    /// 返答用に空けておくトークン数
+    pub reply_tokens: usize,
+}
+
+impl TokenBudget {
+    /// `req`（`history` から [`prompt::build`](crate::service::prompt::build) で作ったもの）が予算に収まる、`history` の開始位置を返す。
+    pub async fn history_start(
+        &self,
+        counter: &mut TokenCounter,
+        llm: &dyn LlmBackend,
+        req: &[Message<'_>],
+        history: &[Message<'_>],
+    ) -> usize {
+        // 先頭のシステム指示・差し込み情報は常に送り、残りが送る形の履歴
+        let fixed = req.iter().take_while(|m| m.role == Role::System).count();
+        let (system, window) = req.split_at(fixed);
+        history.len() - window.len() + self.window_start(counter, llm, system, window).await
+    }
+
+    /// 予算に収まる履歴の開始位置を返す。最新の 1 件は必ず含める。
+    ///
+    /// `system` は常に送るメッセージ、`history` は送る形に整えた履歴。
+    pub async fn window_start(
+        &self,
+        counter: &mut TokenCounter,
+        llm: &dyn LlmBackend,
+        system: &[Message<'_>],
+        history: &[Message<'_>],
+    ) -> usize {
+        let mut fixed = self.reply_tokens;
+        for msg in system {
+            fixed += counter.count(llm, &msg.text).await;
+        }
+        let available = self.context_tokens.saturating_sub(fixed);
+
+        let mut used = 0;
+        let mut start = history.len();
+        for (i, msg) in history.iter().enumerate().rev() {
+            let n = counter.count(llm, &msg.text).await;
+            if used + n > available && start < history.len() {
+                break;
+            }
+            used += n;
+            start = i;
+        }
+
+        counter.retain(
+            system
+                .iter()
+                .chain(&history[start..])
+                .map(|m| m.text.as_ref()),
+        );
+        debug!(
+            messages = history.len() - start,
+            history_tokens = used,
+            system_and_reply = fixed,
+            "history window"
+        );
+        start
+    }
+}
@@ -0,0 +1,239 @@
+//! Function calling 用のツール登録と組み込みツール。
+//!
+//! - [`ToolRegistry`] に名前・説明・引数スキーマ・Rust ハンドラを登録する。
+//! - LLM クライアントは [`ToolRegistry::declarations`] をモデルに渡し、
+//!   返ってきた関数呼び出しを [`ToolRegistry::call`] で実行して結果を送り返す。
+//! - ハンドラのエラーは `{"error": "..."}` としてモデルに返し、会話は止めない。
+
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Arc, Mutex},
+    time::Instant,
+};
+
+use anyhow::anyhow;
+use serde_json::{Value, json};
+use tracing::{debug, warn};
+
+use crate::{
+    error::Result,
+    model::{
+        emotion::Emotion,
+        gemini_dto::{FunctionDeclaration, Tool},
+    },
+    service::{media::avatar_osc, viewer_memory::ViewerMemory},
+};
+
+/// ツールのハンドラ。引数 JSON を受け取り、結果 JSON（オブジェクト）を返す。
+pub type Handler = Arc<dyn Fn(&Value) -> Result<Value> + Send + Sync>;
+
+struct Entry {
+    decl: FunctionDeclaration,
+    handler: Handler,
+}
+
+/// 名前 → ハンドラの登録簿。
+#[derive(Default)]
+pub struct ToolRegistry {
+    tools: Vec<Entry>,
+}
+
+impl ToolRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// ツールを登録する。同名のツールは上書きする。
+    pub fn register(
+        &mut self,
+        name: &str,
+        description: &str,
+        parameters: Option<Value>,
+        handler: impl Fn(&Value) -> Result<Value> + Send + Sync + 'static,
+    ) -> &mut Self {
+        self.tools.retain(|e| e.decl.name != name);
+        self.tools.push(Entry {
+            decl: FunctionDeclaration {
+                name: name.to_owned(),
+                description: description.to_owned(),
+                parameters,
+            },
+            handler: Arc::new(handler),
+        });
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tools.is_empty()
+    }
+
+    /// Gemini の `tools` に渡す宣言。
+    pub fn declarations(&self) -> Vec<Tool> {
+        if self.tools.is_empty() {
+            return Vec::new();
+        }
+        vec![Tool {
+            function_declarations: self.tools.iter().map(|e| e.decl.clone()).collect(),
+        }]
+    }
+
+    /// ツールを実行する。未登録・失敗時もエラー内容を JSON で返す。
+    pub fn call(&self, name: &str, args: &Value) -> Value {
+        let Some(entry) = self.tools.iter().find(|e| e.decl.name == name) else {
+            warn!(tool = name, "unknown tool requested");
+            return json!({ "error": format!("unknown tool: {name}") });
+        };
+        match (entry.handler)(args) {
+            Ok(v) => {
+                debug!(tool = name, %args, result = %v, "tool called");
+                v
+            }
+            Err(e) => {
+                warn!(tool = name, %args, error = %e, "tool failed");
+                json!({ "error": e.to_string() })
+            }
+        }
+    }
+
+    /// 組み込みツール一式を登録したレジストリ。
+    ///
+    /// * `started_at` – 配信開始時刻（uptime の計算用）
+    /// * `chat_log` – 視聴者の過去コメント検索に使うログ
+    /// * `viewers` – 視聴者の長期記憶
+    pub fn builtin(started_at: Instant, chat_log: ChatLog, viewers: ViewerMemory) -> Self {
+        let mut reg = Self::new();
+
+        reg.register(
+            "get_current_time",
+            "現在の日時と、配信開始からの経過時間（分）を返す。",
+            None,
+            move |_| {
+                Ok(json!({
+                    "now": chrono::Local::now().format("%Y-%m-%d %H:%M").to_string(),
+                    "stream_uptime_minutes": started_at.elapsed().as_secs() / 60,
+                }))
+            },
+        );
+
+        reg.register(
+            "roll_dice",
+            "サイコロを振る。",
+            Some(json!({
+                "type": "object",
+                "properties": {
+                    "sides": { "type": "integer", "description": "面の数 (既定 6)" },
+                    "count": { "type": "integer", "description": "個数 (既定 1, 最大 10)" }
+                }
+            })),
+            |args| {
+                let sides = args["sides"].as_u64().unwrap_or(6).clamp(2, 1000);
+                let count = args["count"].as_u64().unwrap_or(1).clamp(1, 10);
+                let rolls: Vec<u64> = (0..count).map(|_| rand::random_range(1..=sides)).collect();
+                Ok(json!({ "rolls": rolls, "total": rolls.iter().sum::<u64>() }))
+            },
+        );
+
+        reg.register("draw_omikuji", "おみくじを引く。", None, |_| {
+            const RESULTS: &[&str] = &["大吉", "中吉", "小吉", "吉", "末吉", "凶"];
+            Ok(json!({ "result": RESULTS[rand::random_range(0..RESULTS.len())] }))
+        });
+
+        reg.register(
+            "set_emotion",
+            "アバターの表情を変える。",
+            Some(json!({
+                "type": "object",
+                "properties": {
+                    "emotion": {
+                        "type": "string",
+                        "enum": ["neutral", "happy", "sad", "angry", "relaxed", "surprised"]
+                    }
+                },
+                "required": ["emotion"]
+            })),
+            |args| {
+                let name = args["emotion"].as_str().unwrap_or_default();
+                let emo: Emotion = name
+                    .parse()
+                    .map_err(|_| anyhow!("unknown emotion: {name}"))?;
+                avatar_osc::set(emo)?;
+                Ok(json!({ "ok": true }))
+            },
+        );
+
+        reg.register(
+            "lookup_viewer_messages",
+            "指定した視聴者の最近のコメントを新しい順に返す。",
+            Some(json!({
+                "type": "object",
+                "properties": {
+                    "name": { "type": "string", "description": "視聴者の表示名" },
+                    "limit": { "type": "integer", "description": "最大件数 (既定 5)" }
+                },
+                "required": ["name"]
+            })),
+            move |args| {
+                let name = args["name"].as_str().unwrap_or_default();
+                let limit = args["limit"].as_u64().unwrap_or(5) as usize;
+                Ok(json!({ "name": name, "messages": chat_log.recent(name, limit) }))
+            },
+        );
+
+        reg.register(
+            "remember_viewer_fact",
+            "視聴者について次の配信以降も覚えておきたいこと（好み・近況など）を記録する。",
+            Some(json!({
+                "type": "object",
+                "properties": {
+                    "name": { "type": "string", "description": "視聴者の表示名" },
+                    "fact": { "type": "string", "description": "覚えておくこと（短い一文）" }
+                },
+                "required": ["name", "fact"]
+            })),
+            move |args| {
+                let name = args["name"].as_str().unwrap_or_default();
+                let fact = args["fact"].as_str().unwrap_or_default();
+                let Some(key) = viewers.find(name) else {
+                    return Err(anyhow!("unknown viewer: {name}").into());
+                };
+                Ok(json!({ "ok": viewers.remember(&key, fact) }))
+            },
+        );
+
+        reg
+    }
+}
+
+/// 視聴者ごとの直近コメント（メモリ上のみ）。クローンは同じログを共有する。
+#[derive(Clone, Default)]
+pub struct ChatLog {
+    inner: Arc<Mutex<HashMap<String, VecDeque<String>>>>,
+}
+
+impl ChatLog {
+    /// 1 人あたりの保持件数
+    const PER_AUTHOR: usize = 50;
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record(&self, author: &str, text: &str) {
+        let mut map = self.inner.lock().unwrap();
+        let q = map.entry(author.to_owned()).or_default();
+        if q.len() == Self::PER_AUTHOR {
+            q.pop_front();
+        }
+        q.push_back(text.to_owned());
+    }
+
+    /// 新しい順に最大 `limit` 件。
+    pub fn recent(&self, author: &str, limit: usize) -> Vec<String> {
+        self.inner
+            .lock()
+            .unwrap()
+            .get(author)
+            .map(|q| q.iter().rev().take(limit).cloned().collect())
+            .unwrap_or_default()
+    }
+}
@@ -0,0 +1,199 @@
+//! 視聴者ごとの長期記憶（JSON ファイルに永続化）。
+//!
+//! - 記録は [`Author::key`]（`platform:channel_id`）ごとに持ち、表示名は属性として最新に保つ。
+//! - コメントのたびに初見日・コメント数・参加した配信数を更新する。
+//! - 「覚えておくこと」は `remember_viewer_fact` ツールでモデルがまとめ直したものだけを残す
+//!   （コメント本文をそのままシステム指示に入れない）。
+//! - コメントした視聴者の記録を [`PromptContext::viewer`](crate::service::prompt::PromptContext)
+//!   として差し込み、常連さんには常連として接する。
+//! - 書き込みは [`ViewerMemory::flush`] でまとめて行い、一時ファイル経由で置き換える。
+
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+
+use crate::{error::Result, model::conversation::Author};
+
+/// 1 人あたりの「覚えていること」の上限
+const MAX_FACTS: usize = 20;
+
+/// 視聴者 1 人ぶんの記録。
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ViewerRecord {
+    /// 最後にコメントしたときの表示名
+    pub name: String,
+    /// 初めてコメントした日（`YYYY-MM-DD`）
+    pub first_seen: String,
+    /// 最後にコメントした日（`YYYY-MM-DD`）
+    pub last_seen: String,
+    pub message_count: u64,
+    /// コメントした配信の数（今回を含む）
+    pub stream_count: u64,
+    /// 覚えておくこと（古い順）
+    #[serde(default)]
+    pub facts: Vec<String>,
+}
+
+impl ViewerRecord {
+    /// 常連かどうか
+    pub fn is_regular(&self, min_streams: u64) -> bool {
+        self.stream_count >= min_streams
+    }
+
+    /// プロンプトに差し込む説明文。
+    pub fn describe(&self, min_streams: u64) -> String {
+        let mut out = format!("## コメントした視聴者: {}\n", self.name);
+        if self.is_regular(min_streams) {
+            out.push_str(&format!(
+                "- 常連さん（{} 回目の配信参加、初めて来たのは {}）。常連として親しく接してください。\n",
+                self.stream_count, self.first_seen
+            ));
+        } else if self.stream_count <= 1 && self.message_count <= 1 {
+            out.push_str("- 初見さん。歓迎してください。\n");
+        } else {
+            out.push_str(&format!(
+                "- {} 回目の配信参加（初めて来たのは {}）\n",
+                self.stream_count, self.first_seen
+            ));
+        }
+        out.push_str(&format!("- これまでのコメント数: {}\n", self.message_count));
+        if !self.facts.is_empty() {
+            out.push_str("- 覚えていること:\n");
+            for fact in &self.facts {
+                out.push_str(&format!("  - {fact}\n"));
+            }
+        }
+        out
+    }
+
+    fn add_fact(&mut self, fact: &str) -> bool {
+        let fact = fact.trim();
+        if fact.is_empty() || self.facts.iter().any(|f| f == fact) {
+            return false;
+        }
+        if self.facts.len() == MAX_FACTS {
+            self.facts.remove(0);
+        }
+        self.facts.push(fact.to_owned());
+        true
+    }
+}
+
+#[derive(Debug, Default)]
+struct Store {
+    path: Option<PathBuf>,
+    /// [`Author::key`] → 記録
+    viewers: HashMap<String, ViewerRecord>,
+    /// この配信でコメントした視聴者（キー）
+    seen_this_stream: HashSet<String>,
+    dirty: bool,
+}
+
+/// 視聴者の記録簿。クローンは同じ記録を共有する。
+#[derive(Debug, Clone, Default)]
+pub struct ViewerMemory {
+    inner: Arc<Mutex<Store>>,
+}
+
+impl ViewerMemory {
+    /// ファイルに保存しない記録簿。
+    pub fn in_memory() -> Self {
+        Self::default()
+    }
+
+    /// `path` から読み込む。ファイルがなければ空で始める。
+    pub fn open(path: impl Into<PathBuf>) -> Result<Self> {
+        let path = path.into();
+        let viewers = match fs::read_to_string(&path) {
+            Ok(text) => serde_json::from_str(&text)
+                .with_context(|| format!("failed to parse {}", path.display()))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => HashMap::new(),
+            Err(e) => {
+                return Err(anyhow::Error::new(e)
+                    .context(format!("failed to read {}", path.display()))
+                    .into());
+            }
+        };
+        Ok(Self {
+            inner: Arc::new(Mutex::new(Store {
+                path: Some(path),
+                viewers,
+                ..Store::default()
+            })),
+        })
+    }
+
+    /// コメント 1 件を記録し、更新後の記録を返す。
+    pub fn record(&self, author: &Author) -> ViewerRecord {
+        let today = chrono::Local::now().format("%Y-%m-%d").to_string();
+        let key = author.key();
+        let mut store = self.inner.lock().unwrap();
+        let first_this_stream = store.seen_this_stream.insert(key.clone());
+
+        let rec = store.viewers.entry(key).or_insert_with(|| ViewerRecord {
+            first_seen: today.clone(),
+            ..ViewerRecord::default()
+        });
+        rec.name.clone_from(&author.name);
+        rec.last_seen = today;
+        rec.message_count += 1;
+        if first_this_stream {
+            rec.stream_count += 1;
+        }
+        let rec = rec.clone();
+        store.dirty = true;
+        rec
+    }
+
+    /// 視聴者について覚えておくことを追加する。未登録の視聴者なら `false`。
+    pub fn remember(&self, key: &str, fact: &str) -> bool {
+        let mut store = self.inner.lock().unwrap();
+        let added = store
+            .viewers
+            .get_mut(key)
+            .is_some_and(|rec| rec.add_fact(fact));
+        if added {
+            debug!(viewer = key, fact, "viewer fact recorded");
+        }
+        store.dirty |= added;
+        added
+    }
+
+    pub fn get(&self, key: &str) -> Option<ViewerRecord> {
+        self.inner.lock().unwrap().viewers.get(key).cloned()
+    }
+
+    /// 表示名から記録のキーを引く。同名が複数いれば、この配信でコメントした視聴者を優先する。
+    pub fn find(&self, name: &str) -> Option<String> {
+        let store = self.inner.lock().unwrap();
+        let mut found = None;
+        for (key, _) in store.viewers.iter().filter(|(_, rec)| rec.name == name) {
+            if store.seen_this_stream.contains(key) {
+                return Some(key.clone());
+            }
+            found.get_or_insert(key);
+        }
+        found.cloned()
+    }
+
+    /// 変更があればファイルに書き出す。
+    pub fn flush(&self) -> Result<()> {
+        let mut store = self.inner.lock().unwrap();
+        let Some(path) = store.path.clone().filter(|_| store.dirty) else {
+            return Ok(());
+        };
+        let json = serde_json::to_string_pretty(&store.viewers).context("serialize viewers")?;
+        let tmp = path.with_extension("json.tmp");
+        fs::write(&tmp, json).with_context(|| format!("failed to write {}", tmp.display()))?;
+        fs::rename(&tmp, &path).with_context(|| format!("failed to replace {}", path.display()))?;
+        store.dirty = false;
+        Ok(())
+    }
+}
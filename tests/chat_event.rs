@@ -0,0 +1,190 @@
+use ai_tuber::{
+    model::chat_event::{ChatEvent, Money},
+    service::{commands::Permission, prompt, youtube_chat::to_event},
+};
+use youtube_chat::item::{Author, Badge, ChatItem, EmojiItem, ImageItem, MessageItem, SuperChat};
+
+fn item(message: Vec<MessageItem>) -> ChatItem {
+    ChatItem {
+        id: "id-1".into(),
+        author: Author {
+            name: Some("たろう".into()),
+            thumbnail: None,
+            channel_id: "UC123".into(),
+            badge: None,
+        },
+        message,
+        superchat: None,
+        is_membership: false,
+        is_verified: false,
+        is_owner: false,
+        is_moderator: false,
+        timestamp: None,
+    }
+}
+
+fn image(alt: &str) -> ImageItem {
+    ImageItem {
+        url: "https://example.com/x.png".into(),
+        alt: Some(alt.into()),
+    }
+}
+
+#[test]
+fn parses_displayed_amounts() {
+    let cases = [
+        ("￥1,000", 1000.0, "JPY"),
+        ("$5.00", 5.0, "USD"),
+        ("CA$10.00", 10.0, "CAD"),
+        ("€5,00", 5.0, "EUR"),
+        ("₩10,000", 10000.0, "KRW"),
+    ];
+    for (display, amount, currency) in cases {
+        let m = Money::parse(display);
+        assert_eq!(
+            (m.amount, m.currency.as_str()),
+            (amount, currency),
+            "{display}"
+        );
+    }
+    assert_eq!(Money::parse("$5.00").approx_jpy(), 750.0);
+}
+
+#[test]
+fn converts_messages_with_emoji_alt_text() {
+    let chat = item(vec![
+        MessageItem::Text("こんにちは".into()),
+        MessageItem::Emoji(EmojiItem {
+            image_item: Some(image(":wave:")),
+            emoji_text: Some(":_hello:".into()),
+            is_custome_emoji: Some(true),
+        }),
+    ]);
+
+    let Some(ChatEvent::Message(msg)) = to_event(chat, None, None) else {
+        panic!("expected a plain message");
+    };
+    assert_eq!(msg.text, "こんにちは:_hello:");
+    assert_eq!(msg.author.channel_id.as_deref(), Some("UC123"));
+}
+
+#[test]
+fn converts_paid_and_membership_events() {
+    let mut chat = item(vec![MessageItem::Text("いつも応援してます".into())]);
+    chat.superchat = Some(SuperChat {
+        amount: "￥500".into(),
+        color: "#FFFFFF".into(),
+        sticker: None,
+    });
+    let event = to_event(chat, None, None).unwrap();
+    assert_eq!(event.kind(), "super_chat");
+    assert_eq!(event.paid().unwrap().amount, 500.0);
+    assert_eq!(
+        prompt::event_text(&event),
+        "（スーパーチャット ￥500 をいただきました。まずお礼を伝えてください）\nいつも応援してます"
+    );
+    // 知識検索には本文だけを使う
+    assert_eq!(
+        prompt::comment_body(&prompt::event_text(&event)),
+        "いつも応援してます"
+    );
+    assert_eq!(prompt::comment_body("（笑）"), "（笑）");
+
+    let mut chat = item(Vec::new());
+    chat.superchat = Some(SuperChat {
+        amount: "$2.00".into(),
+        color: "#FFFFFF".into(),
+        sticker: Some(image("拍手するねこ")),
+    });
+    let Some(ChatEvent::SuperSticker {
+        sticker, amount, ..
+    }) = to_event(chat, None, None)
+    else {
+        panic!("expected a sticker");
+    };
+    assert_eq!(
+        (sticker.as_str(), amount.currency.as_str()),
+        ("拍手するねこ", "USD")
+    );
+
+    let mut chat = item(vec![MessageItem::Text("Welcome to ずんだ channel!".into())]);
+    chat.is_membership = true;
+    chat.author.badge = Some(Badge {
+        thumbnail: image("新規メンバー"),
+        label: "新規メンバー".into(),
+    });
+    let event = to_event(chat, None, Some("ずんだ channel")).unwrap();
+    let ChatEvent::Membership { message, tier } = &event else {
+        panic!("expected a membership");
+    };
+    assert!(message.badges.member);
+    assert_eq!(tier.as_deref(), Some("新規メンバー"));
+    assert_eq!(
+        prompt::event_text(&event),
+        "（メンバーになってくれました: 新規メンバー。歓迎してください）"
+    );
+    assert_eq!(prompt::comment_body(&prompt::event_text(&event)), "");
+}
+
+#[test]
+fn grants_owner_only_to_the_configured_channel() {
+    // youtube_chat は配信者・モデレーター・認証済みのどれでも is_owner を立てる
+    let mut verified = item(vec![MessageItem::Text("こんにちは".into())]);
+    verified.is_owner = true;
+    let Some(ChatEvent::Message(msg)) = to_event(verified.clone(), Some("UC-streamer"), None)
+    else {
+        panic!("expected a plain message");
+    };
+    assert!(!msg.badges.owner);
+    assert!(msg.badges.moderator);
+    assert_eq!(Permission::from_badges(&msg.badges), Permission::Moderator);
+
+    let mut streamer = verified;
+    streamer.author.channel_id = "UC-streamer".into();
+    let Some(ChatEvent::Message(msg)) = to_event(streamer.clone(), Some("UC-streamer"), None)
+    else {
+        panic!("expected a plain message");
+    };
+    assert_eq!(Permission::from_badges(&msg.badges), Permission::Owner);
+
+    // 配信者のチャンネルが未設定なら誰も配信者にならない
+    let Some(ChatEvent::Message(msg)) = to_event(streamer, None, None) else {
+        panic!("expected a plain message");
+    };
+    assert_eq!(Permission::from_badges(&msg.badges), Permission::Moderator);
+}
+
+#[test]
+fn ordinary_member_greetings_are_not_memberships() {
+    let member = |text: &str| {
+        let mut chat = item(vec![MessageItem::Text(text.into())]);
+        chat.is_membership = true;
+        chat
+    };
+    let channel = Some("ずんだ channel");
+
+    for text in [
+        "Welcome to the stream everyone!",
+        "初見さん、ずんだ channel へようこそ！またね",
+        "みんなへようこそ！",
+    ] {
+        let Some(ChatEvent::Message(msg)) = to_event(member(text), None, channel) else {
+            panic!("expected a plain message: {text}");
+        };
+        assert_eq!(msg.text, text);
+    }
+
+    assert_eq!(
+        to_event(member("ずんだ channel へようこそ！"), None, channel)
+            .unwrap()
+            .kind(),
+        "membership"
+    );
+    // チャンネル名がわからなければ加入とは判定しない
+    assert_eq!(
+        to_event(member("Welcome to ずんだ channel!"), None, None)
+            .unwrap()
+            .kind(),
+        "message"
+    );
+}
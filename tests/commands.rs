@@ -0,0 +1,192 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use ai_tuber::{
+    model::chat_event::Badges,
+    service::commands::{Command, CommandRouter, Controls, Permission, Rejection, SpecOverride},
+};
+
+#[test]
+fn parses_commands_and_arguments() {
+    assert_eq!(Command::parse("!help"), Ok(Command::Help));
+    assert_eq!(Command::parse("!DICE"), Ok(Command::Dice { sides: 6 }));
+    assert_eq!(Command::parse("!dice 20"), Ok(Command::Dice { sides: 20 }));
+    assert_eq!(
+        Command::parse("!voice 8"),
+        Ok(Command::Voice { speaker: 8 })
+    );
+    assert_eq!(
+        Command::parse("!persona neko"),
+        Ok(Command::Persona {
+            name: "neko".into()
+        })
+    );
+    // 運営コンソールでは ! を省略できる
+    assert_eq!(Command::parse("skip"), Ok(Command::Skip));
+
+    assert_eq!(
+        Command::parse("!dice 1"),
+        Err(Rejection::Usage("dice".into()))
+    );
+    assert_eq!(
+        Command::parse("!voice abc"),
+        Err(Rejection::Usage("voice".into()))
+    );
+    assert_eq!(
+        Command::parse("!dance"),
+        Err(Rejection::Unknown("dance".into()))
+    );
+}
+
+#[test]
+fn permissions_come_from_badges() {
+    let badges = |owner, moderator, member| Badges {
+        owner,
+        moderator,
+        member,
+        verified: false,
+    };
+    assert_eq!(
+        Permission::from_badges(&badges(false, false, false)),
+        Permission::Everyone
+    );
+    assert_eq!(
+        Permission::from_badges(&badges(false, false, true)),
+        Permission::Member
+    );
+    assert_eq!(
+        Permission::from_badges(&badges(false, true, true)),
+        Permission::Moderator
+    );
+    assert_eq!(
+        Permission::from_badges(&badges(true, false, false)),
+        Permission::Owner
+    );
+}
+
+#[test]
+fn router_checks_permission_and_cooldown() {
+    let now = Instant::now();
+    let mut router = CommandRouter::default();
+
+    assert!(
+        router
+            .route("こんにちは", Permission::Everyone, now)
+            .is_none()
+    );
+    assert_eq!(
+        router.route("!skip", Permission::Member, now),
+        Some(Err(Rejection::Forbidden {
+            required: Permission::Moderator
+        }))
+    );
+    let inv = router
+        .route("!skip", Permission::Moderator, now)
+        .unwrap()
+        .unwrap();
+    assert_eq!(inv.command, Command::Skip);
+    assert!(!inv.spoken);
+    assert_eq!(
+        router.route("!voice", Permission::Owner, now),
+        Some(Err(Rejection::Usage("!voice <話者ID>".into())))
+    );
+
+    assert!(
+        router
+            .route("!dice", Permission::Everyone, now)
+            .unwrap()
+            .is_ok()
+    );
+    let later = now + Duration::from_secs(3);
+    assert_eq!(
+        router.route("!dice", Permission::Everyone, later),
+        Some(Err(Rejection::Cooldown {
+            remaining: Duration::from_secs(7)
+        }))
+    );
+    // モデレーター以上はクールダウンを無視する
+    assert!(
+        router
+            .route("!dice", Permission::Moderator, later)
+            .unwrap()
+            .is_ok()
+    );
+    assert!(
+        router
+            .route("!dice", Permission::Everyone, now + Duration::from_secs(20))
+            .unwrap()
+            .is_ok()
+    );
+}
+
+#[test]
+fn overrides_change_specs_and_reject_unknown_names() {
+    let overrides = HashMap::from([
+        (
+            "dice".to_owned(),
+            SpecOverride {
+                permission: Some(Permission::Member),
+                cooldown_sec: Some(0),
+                ..SpecOverride::default()
+            },
+        ),
+        (
+            "clear".to_owned(),
+            SpecOverride {
+                enabled: Some(false),
+                ..SpecOverride::default()
+            },
+        ),
+    ]);
+    let mut router = CommandRouter::new(overrides).unwrap();
+    let now = Instant::now();
+
+    assert_eq!(
+        router.route("!dice", Permission::Everyone, now),
+        Some(Err(Rejection::Forbidden {
+            required: Permission::Member
+        }))
+    );
+    assert!(
+        router
+            .route("!dice", Permission::Member, now)
+            .unwrap()
+            .is_ok()
+    );
+    assert!(
+        router
+            .route("!dice", Permission::Member, now)
+            .unwrap()
+            .is_ok()
+    );
+    assert_eq!(
+        router.route("!clear", Permission::Owner, now),
+        Some(Err(Rejection::Disabled))
+    );
+    assert!(!router.help(Permission::Owner).contains("!clear"));
+    assert!(!router.help(Permission::Everyone).contains("!dice"));
+
+    let unknown = HashMap::from([("dance".to_owned(), SpecOverride::default())]);
+    assert!(CommandRouter::new(unknown).is_err());
+}
+
+#[tokio::test]
+async fn controls_toggle_mute_and_skip_waiters() {
+    let controls = Controls::default();
+    assert!(controls.toggle_mute());
+    assert!(controls.is_muted());
+    assert!(!controls.toggle_mute());
+
+    let waiter = tokio::spawn({
+        let controls = controls.clone();
+        async move { controls.skipped().await }
+    });
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    controls.skip();
+    tokio::time::timeout(Duration::from_secs(1), waiter)
+        .await
+        .unwrap()
+        .unwrap();
+}
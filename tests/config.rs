@@ -0,0 +1,33 @@
+//! 環境変数からの設定読み込み。
+//!
+//! 環境変数はプロセス全体で共有されるため、このファイルのテストは 1 つにまとめる。
+
+use ai_tuber::{config::Config, error::Error};
+
+fn set(key: &str, value: &str) {
+    // SAFETY: このテストバイナリで環境変数に触るスレッドはこのテストだけ
+    unsafe { std::env::set_var(key, value) };
+}
+
+#[test]
+fn rejects_json_replies_with_gemini_tools() {
+    set("GEMINI_API_KEY", "test-key");
+    set("YOUTUBE_LIVE_URL", "https://www.youtube.com/watch?v=test");
+    set("REPLY_FORMAT", "json");
+    set("TOOLS_ENABLED", "true");
+
+    let err = Config::from_env().unwrap_err();
+    assert!(matches!(err, Error::InvalidConfig(_)), "{err:?}");
+
+    // どちらか一方なら読める
+    set("TOOLS_ENABLED", "false");
+    assert!(Config::from_env().is_ok());
+    set("REPLY_FORMAT", "tags");
+    set("TOOLS_ENABLED", "true");
+    assert!(Config::from_env().is_ok());
+
+    // OpenAI 互換バックエンドはツールを使わない
+    set("LLM_BACKEND", "openai");
+    set("REPLY_FORMAT", "json");
+    assert!(Config::from_env().is_ok());
+}
@@ -0,0 +1,45 @@
+//! 所有したリクエストをそのまま送れることをモックサーバで検証する。
+
+mod common;
+
+use ai_tuber::{
+    model::{conversation::Message, gemini_dto::GenerateReq},
+    service::GeminiClient,
+};
+use common::{MockServer, Reply};
+use serde_json::Value;
+
+const OK_BODY: &str =
+    r#"{"candidates":[{"content":{"parts":[{"text":"[happy]はーい"}]},"finishReason":"STOP"}]}"#;
+
+#[tokio::test]
+async fn asks_with_owned_requests() {
+    let server = MockServer::start(vec![Reply::new(200, OK_BODY), Reply::new(200, OK_BODY)]).await;
+    let gemini = GeminiClient::with_base_url(&server.url(), "k", "m").unwrap();
+
+    let req: GenerateReq<'static> = {
+        let messages = vec![
+            Message::system(String::from("ペルソナ")),
+            Message::user(String::from("こんにちは")),
+        ];
+        gemini.request(&messages).into_owned()
+    };
+    // 元のメッセージを捨てた後でも、別タスクから送れる
+    let rep = tokio::spawn({
+        let gemini = gemini.clone();
+        async move { gemini.ask(req).await }
+    })
+    .await
+    .unwrap()
+    .unwrap();
+    assert_eq!(rep, "[happy]はーい");
+
+    gemini.ask(String::from("おはよう")).await.unwrap();
+
+    let reqs = server.requests();
+    let first: Value = serde_json::from_str(&reqs[0].body).unwrap();
+    assert_eq!(first["systemInstruction"]["parts"][0]["text"], "ペルソナ");
+    assert_eq!(first["contents"][0]["parts"][0]["text"], "こんにちは");
+    let second: Value = serde_json::from_str(&reqs[1].body).unwrap();
+    assert_eq!(second["contents"][0]["parts"][0]["text"], "おはよう");
+}
@@ -0,0 +1,109 @@
+//! ブロック・打ち切り・空応答の判定を DTO とモックサーバで検証する。
+
+mod common;
+
+use ai_tuber::{
+    error::Error,
+    model::gemini_dto::{self, FinishReason, GenerateRes},
+    service::GeminiClient,
+};
+use common::{MockServer, Reply};
+
+fn parse(json: &str) -> GenerateRes {
+    serde_json::from_str(json).unwrap()
+}
+
+const SAFETY: &str = r#"{"candidates":[{"finishReason":"SAFETY","safetyRatings":[
+    {"category":"HARM_CATEGORY_HARASSMENT","probability":"HIGH","blocked":true},
+    {"category":"HARM_CATEGORY_HATE_SPEECH","probability":"LOW"}]}]}"#;
+const MAX_TOKENS: &str = r#"{"candidates":[{"content":{"parts":[{"text":"  "}]},
+    "finishReason":"MAX_TOKENS"}]}"#;
+const PROMPT_BLOCKED: &str = r#"{"promptFeedback":{"blockReason":"PROHIBITED_CONTENT",
+    "safetyRatings":[{"category":"HARM_CATEGORY_SEXUALLY_EXPLICIT","probability":"HIGH"}]}}"#;
+
+#[test]
+fn parses_finish_reasons() {
+    let reason = |s: &str| serde_json::from_str::<FinishReason>(&format!("\"{s}\"")).unwrap();
+    assert_eq!(reason("MAX_TOKENS"), FinishReason::MaxTokens);
+    assert_eq!(reason("SAFETY"), FinishReason::Safety);
+    assert_eq!(
+        reason("PROHIBITED_CONTENT"),
+        FinishReason::ProhibitedContent
+    );
+    assert_eq!(reason("SOMETHING_NEW"), FinishReason::Other);
+
+    assert!(FinishReason::Safety.is_blocked());
+    assert!(FinishReason::Spii.is_blocked());
+    assert!(!FinishReason::MaxTokens.is_blocked());
+    assert!(!FinishReason::Stop.is_blocked());
+}
+
+#[test]
+fn safety_stop_reports_blocked_categories() {
+    let res = parse(SAFETY);
+    let Err(Error::GeminiBlocked(detail)) = res.check_blocked() else {
+        panic!("expected a block");
+    };
+    assert_eq!(detail, "Safety [HARM_CATEGORY_HARASSMENT]");
+    assert!(matches!(
+        parse(SAFETY).into_text(),
+        Err(Error::GeminiBlocked(_))
+    ));
+}
+
+#[test]
+fn prompt_feedback_blocks_before_candidates() {
+    let res = parse(PROMPT_BLOCKED);
+    assert!(res.candidates.is_empty());
+    let Err(Error::GeminiBlocked(detail)) = res.check_blocked() else {
+        panic!("expected a block");
+    };
+    assert_eq!(detail, "prompt: PROHIBITED_CONTENT");
+}
+
+#[test]
+fn empty_text_is_explained_by_finish_reason() {
+    assert!(parse(MAX_TOKENS).check_blocked().is_ok());
+    assert!(matches!(
+        parse(MAX_TOKENS).into_text(),
+        Err(Error::GeminiTruncated)
+    ));
+
+    assert!(matches!(
+        gemini_dto::empty_reason(Some(FinishReason::MaxTokens)),
+        Error::GeminiTruncated
+    ));
+    assert!(matches!(
+        gemini_dto::empty_reason(Some(FinishReason::Stop)),
+        Error::InvalidGeminiResponse(_)
+    ));
+    assert!(matches!(
+        gemini_dto::empty_reason(None),
+        Error::InvalidGeminiResponse(_)
+    ));
+}
+
+#[tokio::test]
+async fn client_surfaces_blocked_and_truncated_replies() {
+    let server = MockServer::start(vec![
+        Reply::new(200, PROMPT_BLOCKED),
+        Reply::new(200, SAFETY),
+        Reply::new(200, MAX_TOKENS),
+    ])
+    .await;
+    let gemini = GeminiClient::with_base_url(&server.url(), "k", "m").unwrap();
+
+    let blocked = gemini.ask("こんにちは").await.unwrap_err();
+    assert!(matches!(blocked, Error::GeminiBlocked(_)));
+    assert!(blocked.is_unusable_reply());
+    assert!(matches!(
+        gemini.ask("こんにちは").await,
+        Err(Error::GeminiBlocked(_))
+    ));
+    assert!(matches!(
+        gemini.ask("こんにちは").await,
+        Err(Error::GeminiTruncated)
+    ));
+    // 内容起因のエラーは再試行しない
+    assert_eq!(server.requests().len(), 3);
+}
@@ -0,0 +1,167 @@
+//! `GeminiClient` のリトライ・ブレーカーをモックサーバで検証する。
+
+mod common;
+
+use std::time::Duration;
+
+use ai_tuber::{
+    error::Error,
+    model::conversation::Message,
+    service::{GeminiClient, LlmBackend, api::retry::RetryPolicy},
+};
+use common::{MockServer, Reply};
+
+const OK_BODY: &str = r#"{"candidates":[{"content":{"parts":[{"text":"[happy]やっほー！"}]},"finishReason":"STOP"}]}"#;
+
+fn fast_policy() -> RetryPolicy {
+    RetryPolicy {
+        max_retries: 2,
+        base_delay: Duration::from_millis(10),
+        max_delay: Duration::from_millis(50),
+        requests_per_minute: 0,
+        breaker_threshold: 2,
+        breaker_cooldown: Duration::from_secs(60),
+    }
+}
+
+fn client(server: &MockServer) -> GeminiClient {
+    GeminiClient::with_base_url(&server.url(), "test-key", "test-model")
+        .unwrap()
+        .with_retry_policy(fast_policy())
+}
+
+#[tokio::test]
+async fn retries_transient_errors_until_success() {
+    let server = MockServer::start(vec![
+        Reply::new(503, "{}"),
+        Reply::new(429, "{}").header("Retry-After", "0"),
+        Reply::new(200, OK_BODY),
+    ])
+    .await;
+    let gemini = client(&server);
+
+    let rep = gemini.chat(&[Message::user("こんにちは")]).await.unwrap();
+
+    assert_eq!(rep, "[happy]やっほー！");
+    let reqs = server.requests();
+    assert_eq!(reqs.len(), 3);
+    assert!(
+        reqs[0]
+            .path
+            .starts_with("/models/test-model:generateContent")
+    );
+}
+
+#[tokio::test]
+async fn honors_retry_delay_from_error_body() {
+    let body = r#"{"error":{"code":429,"details":[{"@type":"type.googleapis.com/google.rpc.RetryInfo","retryDelay":"0.2s"}]}}"#;
+    let server = MockServer::start(vec![Reply::new(429, body), Reply::new(200, OK_BODY)]).await;
+    let gemini = client(&server);
+
+    let started = std::time::Instant::now();
+    gemini.chat(&[Message::user("hi")]).await.unwrap();
+
+    // max_delay (50ms) で頭打ちになる
+    assert!(started.elapsed() >= Duration::from_millis(50));
+    assert_eq!(server.requests().len(), 2);
+}
+
+#[tokio::test]
+async fn does_not_retry_client_errors() {
+    let server = MockServer::start(vec![Reply::new(400, "{}")]).await;
+    let gemini = client(&server);
+
+    let err = gemini.chat(&[Message::user("hi")]).await.unwrap_err();
+
+    assert!(matches!(err, Error::External(_)), "{err:?}");
+    assert_eq!(server.requests().len(), 1);
+}
+
+#[tokio::test]
+async fn breaker_opens_after_consecutive_failures() {
+    // 台本なし = 常に 500
+    let server = MockServer::start(vec![]).await;
+    let gemini = client(&server);
+
+    for _ in 0..2 {
+        let err = gemini.chat(&[Message::user("hi")]).await.unwrap_err();
+        assert!(matches!(err, Error::LlmUnavailable(_)), "{err:?}");
+    }
+    assert_eq!(server.requests().len(), 6);
+
+    // ブレーカー作動中はサーバに到達しない
+    let err = gemini.chat(&[Message::user("hi")]).await.unwrap_err();
+    assert!(matches!(err, Error::LlmUnavailable(_)), "{err:?}");
+    assert_eq!(server.requests().len(), 6);
+}
+
+#[tokio::test]
+async fn request_budget_delays_excess_calls() {
+    let server = MockServer::start(vec![Reply::new(200, OK_BODY), Reply::new(200, OK_BODY)]).await;
+    let gemini = GeminiClient::with_base_url(&server.url(), "k", "m")
+        .unwrap()
+        .with_retry_policy(RetryPolicy {
+            requests_per_minute: 1,
+            ..fast_policy()
+        });
+
+    gemini.chat(&[Message::user("1")]).await.unwrap();
+    let msgs = [Message::user("2")];
+    let second = tokio::time::timeout(Duration::from_millis(300), gemini.chat(&msgs)).await;
+
+    assert!(second.is_err(), "second call should wait for the budget");
+    assert_eq!(server.requests().len(), 1);
+}
+
+#[tokio::test]
+async fn half_open_breaker_lets_a_single_trial_through() {
+    let server = MockServer::start(vec![
+        Reply::new(500, "{}"),
+        Reply::new(200, OK_BODY).delay(Duration::from_millis(300)),
+    ])
+    .await;
+    let gemini = GeminiClient::with_base_url(&server.url(), "k", "m")
+        .unwrap()
+        .with_retry_policy(RetryPolicy {
+            max_retries: 0,
+            breaker_threshold: 1,
+            breaker_cooldown: Duration::from_millis(100),
+            ..fast_policy()
+        });
+
+    gemini.chat(&[Message::user("1")]).await.unwrap_err();
+    tokio::time::sleep(Duration::from_millis(150)).await;
+
+    // クールダウン明けの試行が返るまで、他の呼び出しは弾かれる
+    let trial = tokio::spawn({
+        let gemini = gemini.clone();
+        async move { gemini.chat(&[Message::user("2")]).await }
+    });
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    let err = gemini.chat(&[Message::user("3")]).await.unwrap_err();
+    assert!(matches!(err, Error::LlmUnavailable(_)), "{err:?}");
+
+    assert_eq!(trial.await.unwrap().unwrap(), "[happy]やっほー！");
+    assert_eq!(server.requests().len(), 2);
+
+    // 試行が成功すればブレーカーは閉じる
+    server.push(Reply::new(200, OK_BODY));
+    gemini.chat(&[Message::user("4")]).await.unwrap();
+}
+
+#[tokio::test]
+async fn count_tokens_is_skipped_while_breaker_is_open() {
+    let server = MockServer::start(vec![Reply::new(200, r#"{"totalTokens":7}"#)]).await;
+    let gemini = client(&server);
+
+    assert_eq!(gemini.count_tokens("こんにちは").await.unwrap(), 7);
+
+    // 以降は台本なし = 500 でブレーカーを開かせる
+    for _ in 0..2 {
+        gemini.chat(&[Message::user("hi")]).await.unwrap_err();
+    }
+    let sent = server.requests().len();
+    let err = gemini.count_tokens("こんにちは").await.unwrap_err();
+    assert!(matches!(err, Error::LlmUnavailable(_)), "{err:?}");
+    assert_eq!(server.requests().len(), sent);
+}
@@ -0,0 +1,50 @@
+//! Function calling の往復をモックサーバで検証する。
+
+mod common;
+
+use std::sync::Arc;
+
+use ai_tuber::{
+    model::conversation::Message,
+    service::{GeminiClient, LlmBackend, tools::ToolRegistry},
+};
+use common::{MockServer, Reply};
+use serde_json::{Value, json};
+
+#[tokio::test]
+async fn executes_function_call_and_sends_result_back() {
+    let call = r#"{"candidates":[{"content":{"role":"model","parts":[{"functionCall":{"name":"roll_dice","args":{"sides":6}}}]}}]}"#;
+    let text = r#"{"candidates":[{"content":{"parts":[{"text":"[happy]4が出たよ！"}]},"finishReason":"STOP"}]}"#;
+    let server = MockServer::start(vec![Reply::new(200, call), Reply::new(200, text)]).await;
+
+    let mut tools = ToolRegistry::new();
+    tools.register("roll_dice", "サイコロ", None, |_| {
+        Ok(json!({ "rolls": [4] }))
+    });
+    let gemini = GeminiClient::with_base_url(&server.url(), "k", "m")
+        .unwrap()
+        .with_tools(Arc::new(tools));
+
+    let rep = gemini
+        .chat(&[Message::user("サイコロ振って")])
+        .await
+        .unwrap();
+    assert_eq!(rep, "[happy]4が出たよ！");
+
+    let reqs = server.requests();
+    assert_eq!(reqs.len(), 2);
+    let first: Value = serde_json::from_str(&reqs[0].body).unwrap();
+    assert_eq!(
+        first["tools"][0]["functionDeclarations"][0]["name"],
+        "roll_dice"
+    );
+
+    let second: Value = serde_json::from_str(&reqs[1].body).unwrap();
+    let contents = second["contents"].as_array().unwrap();
+    assert_eq!(contents.len(), 3);
+    assert_eq!(contents[1]["parts"][0]["functionCall"]["name"], "roll_dice");
+    assert_eq!(
+        contents[2]["parts"][0]["functionResponse"]["response"]["rolls"][0],
+        4
+    );
+}
@@ -0,0 +1,66 @@
+//! ナレッジベースの BM25 検索を検証する。
+
+use ai_tuber::service::knowledge::{KnowledgeBase, Passage};
+
+fn passage(source: &str, text: &str) -> Passage {
+    Passage {
+        source: source.into(),
+        text: text.into(),
+    }
+}
+
+#[test]
+fn ranks_relevant_passages_first() {
+    let kb = KnowledgeBase::from_passages(vec![
+        passage(
+            "lore.md",
+            "誕生日は 3 月 14 日。好きな食べ物はいちごタルト。",
+        ),
+        passage(
+            "schedule.md",
+            "配信スケジュール: 毎週金曜 21 時から雑談配信。",
+        ),
+        passage(
+            "merch.md",
+            "グッズはアクリルスタンドと缶バッジを BOOTH で販売中。",
+        ),
+    ]);
+
+    let hits = kb.search("次の雑談配信はいつ？", 2);
+    assert_eq!(hits[0].source, "schedule.md");
+
+    let hits = kb.search("グッズ どこで買える？ booth", 1);
+    assert_eq!(hits.len(), 1);
+    assert_eq!(hits[0].source, "merch.md");
+
+    assert!(kb.search("xyz", 3).is_empty());
+}
+
+#[test]
+fn loads_markdown_directory_into_passages() {
+    let dir = std::env::temp_dir().join(format!("knowledge_{}", std::process::id()));
+    std::fs::create_dir_all(dir.join("faq")).unwrap();
+    std::fs::write(
+        dir.join("lore.md"),
+        "# プロフィール\n誕生日は 3 月 14 日。\n\n好きな食べ物はいちごタルト。\n",
+    )
+    .unwrap();
+    std::fs::write(
+        dir.join("faq/q.txt"),
+        "Q. 使っているマイクは？\nA. SM7B です。",
+    )
+    .unwrap();
+    std::fs::write(dir.join("ignored.json"), "{}").unwrap();
+
+    let kb = KnowledgeBase::load(&dir).unwrap();
+    assert_eq!(kb.len(), 3);
+
+    let hits = kb.search("好きな食べ物は？", 1);
+    assert_eq!(hits[0].text, "プロフィール\n好きな食べ物はいちごタルト。");
+    assert_eq!(
+        kb.search("マイク", 1)[0].text,
+        "Q. 使っているマイクは？\nA. SM7B です。"
+    );
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
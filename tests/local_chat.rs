@@ -0,0 +1,112 @@
+use std::time::Duration;
+
+use ai_tuber::{
+    model::{chat_event::ChatEvent, conversation::Platform},
+    service::{
+        ChatSource,
+        local_chat::{self, Listen, LocalChat},
+    },
+};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
+    net::{TcpListener, TcpStream},
+};
+use tokio_stream::StreamExt;
+
+/// 空いているポートを 1 つ選ぶ。
+async fn free_addr() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    listener.local_addr().unwrap().to_string()
+}
+
+fn text(event: &ChatEvent) -> (&str, &str) {
+    let msg = event.message();
+    (msg.author.name.as_str(), msg.text.as_str())
+}
+
+#[test]
+fn parses_author_prefix_and_skips_blank_lines() {
+    let event = local_chat::parse_line("@たろう  今日は何するの？ ", "rehearsal").unwrap();
+    assert_eq!(text(&event), ("たろう", "今日は何するの？"));
+    assert_eq!(event.message().author.platform, Platform::Local);
+    assert!(event.message().badges.owner);
+
+    let event = local_chat::parse_line("こんにちは", "rehearsal").unwrap();
+    assert_eq!(text(&event), ("rehearsal", "こんにちは"));
+    // 本文のない @ はそのまま本文
+    let event = local_chat::parse_line("@everyone", "rehearsal").unwrap();
+    assert_eq!(text(&event), ("rehearsal", "@everyone"));
+
+    assert!(local_chat::parse_line("   ", "rehearsal").is_none());
+}
+
+#[tokio::test]
+async fn reads_lines_from_tcp_clients() {
+    let addr = free_addr().await;
+    let source = LocalChat::new(Listen::Tcp(addr.clone()), "rehearsal");
+    assert_eq!(source.name(), "tcp");
+    let stream = source.subscribe().await.unwrap();
+
+    let mut a = TcpStream::connect(&addr).await.unwrap();
+    a.write_all(b"hello\n\n@bob hi there\n").await.unwrap();
+    let events: Vec<_> = tokio::time::timeout(Duration::from_secs(5), stream.take(2).collect())
+        .await
+        .unwrap();
+    let texts: Vec<_> = events.iter().map(text).collect();
+    assert_eq!(texts, [("rehearsal", "hello"), ("bob", "hi there")]);
+}
+
+#[tokio::test]
+async fn accepts_websocket_text_frames() {
+    let addr = free_addr().await;
+    let source = LocalChat::new(Listen::WebSocket(addr.clone()), "rehearsal");
+    let mut stream = source.subscribe().await.unwrap();
+
+    let conn = TcpStream::connect(&addr).await.unwrap();
+    let (read, mut write) = conn.into_split();
+    write
+        .write_all(
+            b"GET /chat HTTP/1.1\r\nHost: localhost\r\nUpgrade: websocket\r\n\
+              Connection: Upgrade\r\nSec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\
+              Sec-WebSocket-Version: 13\r\n\r\n",
+        )
+        .await
+        .unwrap();
+    let mut read = BufReader::new(read);
+    let mut response = Vec::new();
+    loop {
+        let mut line = String::new();
+        read.read_line(&mut line).await.unwrap();
+        if line == "\r\n" {
+            break;
+        }
+        response.push(line.trim_end().to_owned());
+    }
+    assert_eq!(response[0], "HTTP/1.1 101 Switching Protocols");
+    // RFC 6455 のサンプル
+    assert!(
+        response
+            .iter()
+            .any(|h| h.eq_ignore_ascii_case("sec-websocket-accept: s3pPLMBiTxaQ9kYGzzhZRbK+xOo="))
+    );
+
+    // マスク付きのテキストフレーム
+    let payload = "@alice こんばんは".as_bytes();
+    let mask = [0x12, 0x34, 0x56, 0x78];
+    let mut frame = vec![0x81, 0x80 | payload.len() as u8];
+    frame.extend_from_slice(&mask);
+    frame.extend(payload.iter().enumerate().map(|(i, b)| b ^ mask[i % 4]));
+    // ping には pong が返る
+    frame.extend_from_slice(&[0x89, 0x80, 0, 0, 0, 0]);
+    write.write_all(&frame).await.unwrap();
+
+    let event = tokio::time::timeout(Duration::from_secs(5), stream.next())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(text(&event), ("alice", "こんばんは"));
+
+    let mut pong = [0u8; 2];
+    read.read_exact(&mut pong).await.unwrap();
+    assert_eq!(pong, [0x8A, 0x00]);
+}
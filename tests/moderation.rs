@@ -0,0 +1,236 @@
+//! コメントのモデレーションを検証する。
+
+use std::time::{Duration, Instant};
+
+use ai_tuber::{
+    error::Error,
+    model::{
+        chat_event::{Badges, ChatEvent, ChatMessage, Money},
+        conversation::{Author, Platform},
+    },
+    service::moderation::{DropReason, ModerationRules, Moderator, RateLimit},
+};
+
+fn moderator(rules: ModerationRules) -> Moderator {
+    Moderator::new(rules).unwrap()
+}
+
+/// 表示名と同じチャンネル ID を持つ YouTube の視聴者
+fn yt(name: &str) -> Author {
+    Author::new(name, Platform::YouTube).with_channel_id(format!("UC-{name}"))
+}
+
+#[test]
+fn drops_by_content_rules() {
+    let mut m = moderator(ModerationRules {
+        blocked_words: vec!["宣伝".into()],
+        patterns: vec![r"(?i)free\s*gift".into()],
+        banned_authors: vec!["UC-spam_bot".into()],
+        ..ModerationRules::default()
+    });
+    let now = Instant::now();
+
+    assert_eq!(
+        m.check(&yt("spam_bot"), "こんにちは", now),
+        Some(DropReason::Banned)
+    );
+    assert_eq!(
+        m.check(&yt("a"), "チャンネルの宣伝です", now),
+        Some(DropReason::BlockedWord("宣伝".into()))
+    );
+    assert!(matches!(
+        m.check(&yt("a"), "FREE  GIFT here", now),
+        Some(DropReason::Pattern(_))
+    ));
+    assert_eq!(
+        m.check(&yt("a"), "見て https://example.com", now),
+        Some(DropReason::Link)
+    );
+    assert_eq!(
+        m.check(&yt("a"), "ああああああああああああ", now),
+        Some(DropReason::RepeatedChars)
+    );
+    assert_eq!(m.check(&yt("a"), "こんばんは！", now), None);
+
+    m.ban("youtube:UC-b");
+    assert_eq!(m.check(&yt("b"), "やあ", now), Some(DropReason::Banned));
+    assert!(m.unban("youtube:UC-b"));
+    assert_eq!(m.check(&yt("b"), "やあ", now), None);
+}
+
+#[test]
+fn drops_copy_paste_floods_across_authors() {
+    let mut m = moderator(ModerationRules::default());
+    let now = Instant::now();
+    let spam = "このコメントをコピペして広めて";
+
+    assert_eq!(m.check(&yt("a"), spam, now), None);
+    assert_eq!(m.check(&yt("b"), spam, now), None);
+    assert_eq!(m.check(&yt("c"), spam, now), Some(DropReason::Flood));
+    // 短い挨拶は何人が書いても通す
+    for author in ["a", "b", "c"] {
+        assert_eq!(m.check(&yt(author), "こんばんは", now), None);
+    }
+    // 窓を過ぎれば通る
+    assert_eq!(m.check(&yt("d"), spam, now + Duration::from_secs(31)), None);
+}
+
+#[test]
+fn rate_limits_each_author() {
+    let mut m = moderator(ModerationRules {
+        rate_limit: RateLimit {
+            max_messages: 2,
+            per_sec: 10,
+        },
+        ..ModerationRules::default()
+    });
+    let t0 = Instant::now();
+
+    assert_eq!(m.check(&yt("a"), "1つめ", t0), None);
+    assert_eq!(m.check(&yt("a"), "2つめ", t0), None);
+    assert_eq!(
+        m.check(&yt("a"), "3つめ", t0),
+        Some(DropReason::RateLimited)
+    );
+    assert_eq!(m.check(&yt("b"), "別の人", t0), None);
+    assert_eq!(
+        m.check(&yt("a"), "4つめ", t0 + Duration::from_secs(11)),
+        None
+    );
+}
+
+#[test]
+fn writes_audit_log_and_rejects_bad_patterns() {
+    let path = std::env::temp_dir().join(format!("moderation_{}.jsonl", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let mut m = moderator(ModerationRules {
+        blocked_words: vec!["NG".into()],
+        ..ModerationRules::default()
+    })
+    .with_audit_log(&path);
+
+    m.check(&yt("a"), "ng ワード", Instant::now());
+    let log = std::fs::read_to_string(&path).unwrap();
+    let entry: serde_json::Value = serde_json::from_str(log.lines().next().unwrap()).unwrap();
+    assert_eq!(entry["author"], "a");
+    assert_eq!(entry["reason"], "blocked_word");
+    std::fs::remove_file(&path).unwrap();
+
+    assert!(
+        Moderator::new(ModerationRules {
+            patterns: vec!["(".into()],
+            ..ModerationRules::default()
+        })
+        .is_err()
+    );
+}
+
+#[test]
+fn keys_bans_and_rate_limits_on_channel_id() {
+    let mut m = moderator(ModerationRules {
+        banned_authors: vec!["UC-troll".into(), "twitch:42".into()],
+        rate_limit: RateLimit {
+            max_messages: 1,
+            per_sec: 10,
+        },
+        ..ModerationRules::default()
+    });
+    let now = Instant::now();
+    let troll = Author::new("たろう", Platform::YouTube).with_channel_id("UC-troll");
+    let taro = Author::new("たろう", Platform::YouTube).with_channel_id("UC-taro");
+
+    // 名前を変えても BAN は外れず、同名の別人は巻き込まない
+    assert_eq!(m.check(&troll, "やあ", now), Some(DropReason::Banned));
+    let renamed = Author {
+        name: "はなこ".into(),
+        ..troll.clone()
+    };
+    assert_eq!(m.check(&renamed, "やあ", now), Some(DropReason::Banned));
+    assert_eq!(m.check(&taro, "やあ", now), None);
+    let twitch = Author::new("foo", Platform::Twitch).with_channel_id("42");
+    assert_eq!(m.check(&twitch, "hi", now), Some(DropReason::Banned));
+
+    // 投稿頻度も同名の別人とは別に数える
+    let other_taro = Author::new("たろう", Platform::YouTube).with_channel_id("UC-taro2");
+    assert_eq!(m.check(&other_taro, "こんにちは", now), None);
+    assert_eq!(
+        m.check(&taro, "もう一回", now),
+        Some(DropReason::RateLimited)
+    );
+}
+
+#[test]
+fn does_not_treat_laughter_or_applause_as_spam() {
+    let mut m = moderator(ModerationRules::default());
+    let now = Instant::now();
+
+    for text in [
+        "wwwwwwwwwwwwww",
+        "8888888888888",
+        "草草草草草草草草草草草",
+        "すごーーーーーーーーーい！！！！！！！！！！",
+    ] {
+        assert_eq!(m.check(&yt(text), text, now), None, "{text}");
+    }
+}
+
+#[test]
+fn paid_and_membership_events_skip_spam_and_rate_rules() {
+    let mut m = moderator(ModerationRules {
+        blocked_words: vec!["NG".into()],
+        banned_authors: vec!["UC-troll".into()],
+        rate_limit: RateLimit {
+            max_messages: 1,
+            per_sec: 60,
+        },
+        ..ModerationRules::default()
+    });
+    let now = Instant::now();
+    let message = |name: &str, text: &str| ChatMessage {
+        id: "m".into(),
+        author: yt(name),
+        badges: Badges::default(),
+        text: text.into(),
+        timestamp: chrono::Utc::now(),
+    };
+    let super_chat = |name: &str, text: &str| ChatEvent::SuperChat {
+        message: message(name, text),
+        amount: Money::parse("￥500"),
+    };
+
+    let chat = ChatEvent::Message(message("a", "こんにちは"));
+    assert_eq!(m.check_event(&chat, now), None);
+    // 頻度の上限に達したあとでも、リンク付きでも、お礼の対象は落とさない
+    assert_eq!(
+        m.check_event(&super_chat("a", "応援です https://example.com"), now),
+        None
+    );
+    let membership = ChatEvent::Membership {
+        message: message("a", ""),
+        tier: None,
+    };
+    assert_eq!(m.check_event(&membership, now), None);
+    assert_eq!(
+        m.check_event(&ChatEvent::Message(message("a", "もう一回")), now),
+        Some(DropReason::RateLimited)
+    );
+
+    // BAN と NG ワードは有料でも落とす
+    assert_eq!(
+        m.check_event(&super_chat("troll", "やあ"), now),
+        Some(DropReason::Banned)
+    );
+    assert_eq!(
+        m.check_event(&super_chat("b", "ng ワード"), now),
+        Some(DropReason::BlockedWord("NG".into()))
+    );
+}
+
+#[test]
+fn rejects_unknown_rule_keys() {
+    let path = std::env::temp_dir().join(format!("moderation_rules_{}.json", std::process::id()));
+    std::fs::write(&path, r#"{"rate_limit":{"max_mesages":2}}"#).unwrap();
+    let err = ModerationRules::load(&path).unwrap_err();
+    std::fs::remove_file(&path).unwrap();
+    assert!(matches!(err, Error::InvalidConfig(msg) if msg.contains("max_mesages")));
+}
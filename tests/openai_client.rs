@@ -0,0 +1,99 @@
+mod common;
+
+use ai_tuber::{
+    model::{conversation::Message, gemini_dto::GenerationConfig},
+    service::OpenAiClient,
+};
+use common::{MockServer, Reply};
+
+#[tokio::test]
+async fn posts_chat_completions_and_returns_first_choice() {
+    let server = MockServer::start(vec![Reply::new(
+        200,
+        r#"{"choices":[{"message":{"role":"assistant","content":"[happy] こんにちは！"}},
+        {"message":{"role":"assistant","content":"二番目"}}]}"#,
+    )])
+    .await;
+    let client = OpenAiClient::new(
+        &format!("{}/v1/", server.url()),
+        Some("sk-test"),
+        "local-model",
+    )
+    .unwrap();
+
+    let reply = client
+        .ask(&[Message::system("あなたは配信者です"), Message::user("やあ")])
+        .await
+        .unwrap();
+    assert_eq!(reply, "[happy] こんにちは！");
+
+    let requests = server.requests();
+    assert_eq!(requests.len(), 1);
+    assert_eq!(requests[0].method, "POST");
+    assert_eq!(requests[0].path, "/v1/chat/completions");
+    assert_eq!(requests[0].header("authorization"), Some("Bearer sk-test"));
+    let body: serde_json::Value = serde_json::from_str(&requests[0].body).unwrap();
+    assert_eq!(body["model"], "local-model");
+    assert_eq!(body["messages"][0]["role"], "system");
+    assert_eq!(body["messages"][1]["role"], "user");
+    assert_eq!(body["messages"][1]["content"], "やあ");
+}
+
+#[tokio::test]
+async fn surfaces_server_errors() {
+    let server = MockServer::start(vec![Reply::new(500, r#"{"error":"boom"}"#)]).await;
+    let client = OpenAiClient::new(&server.url(), None, "local-model").unwrap();
+
+    assert!(client.ask(&[Message::user("やあ")]).await.is_err());
+    assert_eq!(server.requests()[0].header("authorization"), None);
+}
+
+#[tokio::test]
+async fn maps_generation_config_to_request_fields() {
+    let server = MockServer::start(vec![Reply::new(
+        200,
+        r#"{"choices":[{"message":{"content":"はい"}}]}"#,
+    )])
+    .await;
+    let generation: GenerationConfig = serde_json::from_str(
+        r#"{"temperature":0.7,"topP":0.9,"topK":40,"maxOutputTokens":256,
+        "stopSequences":["</end>"],"candidateCount":2}"#,
+    )
+    .unwrap();
+    let client = OpenAiClient::new(&server.url(), None, "local-model")
+        .unwrap()
+        .with_generation_config(generation);
+
+    client.ask(&[Message::user("やあ")]).await.unwrap();
+
+    let body: serde_json::Value = serde_json::from_str(&server.requests()[0].body).unwrap();
+    assert!((body["temperature"].as_f64().unwrap() - 0.7).abs() < 1e-6);
+    assert!((body["top_p"].as_f64().unwrap() - 0.9).abs() < 1e-6);
+    assert_eq!(body["top_k"], 40);
+    assert_eq!(body["max_tokens"], 256);
+    assert_eq!(body["stop"], serde_json::json!(["</end>"]));
+    assert!(body.get("n").is_none());
+    assert!(body.get("response_format").is_none());
+}
+
+#[tokio::test]
+async fn requests_json_mode_for_json_replies() {
+    let server = MockServer::start(vec![Reply::new(
+        200,
+        r#"{"choices":[{"message":{"content":"{\"emotion\":\"happy\",\"text\":\"はい\"}"}}]}"#,
+    )])
+    .await;
+    let generation: GenerationConfig =
+        serde_json::from_str(r#"{"responseMimeType":"application/json"}"#).unwrap();
+    let client = OpenAiClient::new(&server.url(), None, "local-model")
+        .unwrap()
+        .with_generation_config(generation);
+
+    client.ask(&[Message::user("やあ")]).await.unwrap();
+
+    let body: serde_json::Value = serde_json::from_str(&server.requests()[0].body).unwrap();
+    assert_eq!(
+        body["response_format"],
+        serde_json::json!({ "type": "json_object" })
+    );
+}
@@ -0,0 +1,142 @@
+//! ペルソナのプロファイル読み込みと切り替えを検証する。
+
+use ai_tuber::{
+    error::Error,
+    model::emotion::Emotion,
+    service::{
+        persona::{Persona, PersonaRegistry, Voice},
+        template::{Template, TemplateVars},
+    },
+};
+
+fn base() -> Persona {
+    Persona {
+        name: "default".into(),
+        system_prompt: Template::parse("既定のキャラ").unwrap(),
+        spontaneous_prompt: Template::parse("自由に話して").unwrap(),
+        spontaneous_topics: Vec::new(),
+        voice: Voice {
+            speaker: 3,
+            ..Voice::default()
+        },
+        generation_config: None,
+    }
+}
+
+fn temp_dir(name: &str) -> std::path::PathBuf {
+    let dir = std::env::temp_dir().join(format!("{name}_{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[test]
+fn loads_profiles_and_switches_active_persona() {
+    let dir = temp_dir("personas");
+    std::fs::write(
+        dir.join("alice.json"),
+        r#"{
+            "system_prompt": "猫耳のアリス。{{#if viewer_name}}{{viewer_name}}さんと話す。{{/if}}",
+            "speakers": { "sad": 76 },
+            "blend": { "happy": { "name": "Fun", "value": 0.8 } },
+            "generation_config": { "temperature": 1.1 },
+            "spontaneous_topics": ["ゲーム"]
+        }"#,
+    )
+    .unwrap();
+    std::fs::write(dir.join("bob.json"), r#"{ "speaker": 13 }"#).unwrap();
+    std::fs::write(dir.join("notes.txt"), "ignored").unwrap();
+
+    let mut reg = PersonaRegistry::load(&dir, &base()).unwrap();
+    assert_eq!(reg.names().collect::<Vec<_>>(), ["alice", "bob"]);
+
+    let alice = reg.active();
+    let vars = TemplateVars {
+        viewer_name: "たろう".into(),
+        ..TemplateVars::default()
+    };
+    assert_eq!(
+        alice.system_prompt.render(&vars),
+        "猫耳のアリス。たろうさんと話す。"
+    );
+    assert_eq!(alice.voice.speaker(Emotion::Sad), 76);
+    assert_eq!(alice.voice.speaker(Emotion::Happy), 3);
+    assert_eq!(alice.voice.clip(Emotion::Happy), ("Fun", 0.8));
+    assert_eq!(alice.voice.clip(Emotion::Sad), ("Sorrow", 1.0));
+    assert_eq!(alice.pick_topic(), Some("ゲーム"));
+    assert_eq!(
+        alice.generation_config.as_ref().unwrap().temperature,
+        Some(1.1)
+    );
+
+    let bob = reg.switch("bob").unwrap();
+    assert_eq!(bob.voice.speaker(Emotion::Neutral), 13);
+    assert_eq!(
+        bob.system_prompt.render(&TemplateVars::default()),
+        "既定のキャラ"
+    );
+
+    let err = reg.switch("carol").unwrap_err();
+    assert!(matches!(err, Error::InvalidConfig(_)), "{err:?}");
+    assert_eq!(reg.active().name, "bob");
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn failed_switch_keeps_the_current_persona() {
+    let dir = temp_dir("personas_switch");
+    std::fs::write(dir.join("alice.json"), r#"{ "speaker": 8 }"#).unwrap();
+    std::fs::write(dir.join("bob.json"), r#"{ "speaker": 13 }"#).unwrap();
+    let mut reg = PersonaRegistry::load(&dir, &base()).unwrap();
+
+    // バックエンドなどの準備に失敗したら切り替えない
+    let err = reg
+        .switch_with("bob", |_| {
+            Err::<(), _>(Error::InvalidConfig("backend unavailable".into()))
+        })
+        .unwrap_err();
+    assert!(matches!(err, Error::InvalidConfig(_)), "{err:?}");
+    assert_eq!(reg.active().name, "alice");
+
+    let speaker = reg
+        .switch_with("bob", |p| Ok(p.voice.speaker(Emotion::Neutral)))
+        .unwrap();
+    assert_eq!(speaker, 13);
+    assert_eq!(reg.active().name, "bob");
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn rejects_profiles_with_unknown_template_variables() {
+    let dir = temp_dir("personas_bad");
+    std::fs::write(dir.join("x.json"), r#"{ "system_prompt": "{{weather}}" }"#).unwrap();
+
+    let err = PersonaRegistry::load(&dir, &base()).unwrap_err();
+    assert!(matches!(err, Error::InvalidConfig(_)), "{err:?}");
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn rejects_profiles_with_misspelled_keys() {
+    let dir = temp_dir("personas_typo");
+    for (file, body) in [
+        ("a.json", r#"{ "sytem_prompt": "こんにちは" }"#),
+        (
+            "b.json",
+            r#"{ "blend": { "happy": { "name": "Joy", "valeu": 0.5 } } }"#,
+        ),
+    ] {
+        std::fs::write(dir.join(file), body).unwrap();
+        let err = PersonaRegistry::load(&dir, &base()).unwrap_err();
+        assert!(
+            matches!(&err, Error::InvalidConfig(msg) if msg.contains("unknown field")),
+            "{file}: {err:?}"
+        );
+        std::fs::remove_file(dir.join(file)).unwrap();
+    }
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,119 @@
+//! 読み上げ前の後処理を検証する。
+
+use std::collections::HashMap;
+
+use ai_tuber::{
+    model::emotion::Emotion,
+    service::postprocess::{Pipeline, Stage},
+    service::segmenter::Segment,
+};
+
+fn seg(text: &str) -> Segment {
+    Segment {
+        emotion: Emotion::Neutral,
+        text: text.into(),
+    }
+}
+
+fn texts(pipeline: &Pipeline, input: &[&str]) -> Vec<String> {
+    let mut session = pipeline.session();
+    input
+        .iter()
+        .filter_map(|t| session.push(seg(t)))
+        .map(|s| s.text)
+        .collect()
+}
+
+#[test]
+fn strips_markdown_urls_emoji_and_masks_ng_words() {
+    let pipeline = Pipeline {
+        stages: vec![
+            Stage::Markdown,
+            Stage::Url {
+                replacement: "リンク".into(),
+            },
+            Stage::emoji(HashMap::from([("🍛".into(), "カレー".into())])),
+            Stage::ng_words(&["ばか".into(), "Spam".into()], "ピー")
+                .unwrap()
+                .unwrap(),
+        ],
+        max_chars: 0,
+    };
+
+    assert_eq!(
+        texts(
+            &pipeline,
+            &[
+                "## おすすめ",
+                "- **カレー**🍛は[公式](https://example.com/curry)で！",
+                "詳しくは https://example.com/a?b=1 を見てね😀",
+                "ばかって言うなSPAMするな",
+            ]
+        ),
+        [
+            "おすすめ",
+            "カレーカレーは公式で！",
+            "詳しくは リンク を見てね",
+            "ピーって言うなピーするな",
+        ]
+    );
+}
+
+#[test]
+fn drops_code_blocks_across_sentences() {
+    let pipeline = Pipeline::default();
+    assert_eq!(
+        texts(
+            &pipeline,
+            &[
+                "こう書くよ。",
+                "```rust",
+                "fn main() {}",
+                "```",
+                "簡単でしょ？"
+            ]
+        ),
+        ["こう書くよ。", "簡単でしょ？"]
+    );
+}
+
+#[test]
+fn truncates_at_sentence_boundary_when_too_long() {
+    let pipeline = Pipeline {
+        max_chars: 20,
+        ..Pipeline::default()
+    };
+    let input = [
+        "はじめまして。",
+        "今日はいい天気だね！それでね、",
+        "まだ続くよ。",
+    ];
+    assert_eq!(
+        texts(&pipeline, &input),
+        ["はじめまして。", "今日はいい天気だね！"]
+    );
+
+    // 切れ目がなければその文ごと落とす
+    let pipeline = Pipeline {
+        max_chars: 12,
+        ..pipeline
+    };
+    assert_eq!(texts(&pipeline, &input), ["はじめまして。"]);
+}
+
+#[test]
+fn keeps_text_symbols_and_reads_multi_codepoint_emoji() {
+    let stage = Stage::emoji(HashMap::from([
+        ("👍🏻".into(), "いいね".into()),
+        ("🇯🇵".into(), "にっぽん".into()),
+        ("❤️".into(), "だいすき".into()),
+    ]));
+
+    // 文中の記号は残す
+    assert_eq!(stage.apply("よろしく♪ ☆ ♡ ★ →"), "よろしく♪ ☆ ♡ ★ →");
+    // 複数コードポイントの読み（長い方を優先）
+    assert_eq!(stage.apply("👍🏻🇯🇵"), "いいねにっぽん");
+    assert_eq!(stage.apply("❤️と❤"), "だいすきとハート");
+    // 読みのない絵文字は修飾子ごと消す
+    assert_eq!(stage.apply("晴れ☀️⛄👨‍👩‍👧🏳️‍🌈！"), "晴れ！");
+}
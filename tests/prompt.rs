@@ -0,0 +1,56 @@
+use ai_tuber::{
+    model::{
+        conversation::{Author, Message, Platform, Role},
+        emotion::Emotion,
+        reply::ReplyFormat,
+    },
+    service::{
+        prompt::{self, PromptContext},
+        template::Template,
+    },
+};
+use chrono::{Local, TimeZone, Utc};
+
+#[test]
+fn history_shows_who_said_what() {
+    let at = Utc.with_ymd_and_hms(2026, 1, 2, 3, 4, 5).unwrap();
+    let history = vec![
+        Message::user("こんにちは")
+            .with_author(Author::new("たろう", Platform::YouTube).with_channel_id("UC123"))
+            .at(at),
+        Message::new(Role::Bot, "[happy]たろうさん、こんにちは！").with_emotion(Emotion::Happy),
+        Message::user("元気？").with_author("はなこ".into()),
+    ];
+    let system = Template::parse("あなたは VTuber です。").unwrap();
+
+    let req = prompt::build(
+        &system,
+        &history,
+        50,
+        ReplyFormat::Tags,
+        &PromptContext::default(),
+    );
+
+    assert!(req[0].text.contains(prompt::ATTRIBUTION_GUIDE));
+    let time = at.with_timezone(&Local).format("%H:%M");
+    assert_eq!(req[1].text, format!("[{time}] たろう: こんにちは"));
+    assert_eq!(req[2].text, "[happy]たろうさん、こんにちは！");
+    assert_eq!(req[3].text, "はなこ: 元気？");
+}
+
+#[test]
+fn anonymous_history_has_no_attribution_guide() {
+    let history = vec![Message::user("こんにちは")];
+    let system = Template::parse("あなたは VTuber です。").unwrap();
+
+    let req = prompt::build(
+        &system,
+        &history,
+        50,
+        ReplyFormat::Tags,
+        &PromptContext::default(),
+    );
+
+    assert!(!req[0].text.contains(prompt::ATTRIBUTION_GUIDE));
+    assert_eq!(req[1].text, "こんにちは");
+}
@@ -0,0 +1,75 @@
+//! プロンプト組み立てがリクエストごとにメモリを残さないことを検証する。
+//!
+//! 確保量をグローバルアロケータで数えるため、このファイルには他のテストを置かない。
+
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    sync::atomic::{AtomicIsize, Ordering},
+};
+
+use ai_tuber::{
+    model::{conversation::Message, reply::ReplyFormat},
+    service::{
+        GeminiClient,
+        prompt::{self, PromptContext},
+        template::{Template, TemplateVars},
+    },
+};
+
+/// 確保中のバイト数を数えるアロケータ
+struct Counting;
+
+static LIVE: AtomicIsize = AtomicIsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        LIVE.fetch_add(layout.size() as isize, Ordering::Relaxed);
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        LIVE.fetch_sub(layout.size() as isize, Ordering::Relaxed);
+        unsafe { System.dealloc(ptr, layout) }
+    }
+}
+
+#[global_allocator]
+static ALLOC: Counting = Counting;
+
+#[test]
+fn building_thousands_of_prompts_keeps_memory_bounded() {
+    let gemini = GeminiClient::with_base_url("http://127.0.0.1:9", "k", "m").unwrap();
+    let system = Template::parse("あなたは{{stream_title}}配信中の VTuber です。").unwrap();
+    let spontaneous = Template::parse("何か話して").unwrap();
+    let history: Vec<Message<'static>> = (0..20)
+        .map(|i| Message::user(format!("コメント {i}")))
+        .collect();
+    let vars = TemplateVars {
+        stream_title: "雑談".into(),
+        ..TemplateVars::default()
+    };
+    let ctx = PromptContext {
+        summary: Some("これまでの要約"),
+        vars: Some(&vars),
+        ..PromptContext::default()
+    };
+
+    let round = || {
+        let req = prompt::build(&system, &history, 50, ReplyFormat::Tags, &ctx);
+        let owned = gemini.request(&req).into_owned();
+        let spont =
+            prompt::build_spontaneous_prompt(&system, &spontaneous, ReplyFormat::Tags, &ctx);
+        serde_json::to_vec(&owned).unwrap().len() + spont.len()
+    };
+
+    // 遅延初期化ぶんを先に済ませておく
+    round();
+    let before = LIVE.load(Ordering::Relaxed);
+    for _ in 0..5_000 {
+        assert!(round() > 0);
+    }
+    let grown = LIVE.load(Ordering::Relaxed) - before;
+
+    // 1 リクエストは数 KB。漏れていれば 5,000 回で数十 MB 増える
+    assert!(grown < 64 * 1024, "memory grew by {grown} bytes");
+}
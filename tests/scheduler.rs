@@ -0,0 +1,151 @@
+use std::time::{Duration, Instant};
+
+use ai_tuber::service::{
+    prompt,
+    scheduler::{Comment, CommentQueue, Scheduler, SchedulerConfig},
+};
+
+fn scheduler() -> Scheduler {
+    Scheduler::new(SchedulerConfig {
+        max_age: Duration::from_secs(60),
+        capacity: 3,
+        character_names: vec!["ずんだ".into()],
+    })
+}
+
+#[test]
+fn picks_paid_first_time_questions_and_mentions_before_plain_chat() {
+    let now = Instant::now();
+    let mut s = scheduler();
+    s.push(Comment::new("a", "こんにちは", now), now);
+    s.push(Comment::new("b", "ずんだちゃん好き", now), now);
+    s.push(
+        Comment {
+            paid_amount: Some(500.0),
+            ..Comment::new("c", "いつも楽しい", now)
+        },
+        now,
+    );
+
+    assert_eq!(s.pop_best(now).unwrap().author.name, "c");
+    assert_eq!(s.pop_best(now).unwrap().author.name, "b");
+
+    s.push(Comment::new("d", "今日は何するの？", now), now);
+    s.push(
+        Comment {
+            first_time: true,
+            ..Comment::new("e", "はじめまして", now)
+        },
+        now,
+    );
+    assert_eq!(s.pop_best(now).unwrap().author.name, "e");
+    assert_eq!(s.pop_best(now).unwrap().author.name, "d");
+    assert_eq!(s.pop_best(now).unwrap().author.name, "a");
+    assert!(s.pop_best(now).is_none());
+}
+
+#[test]
+fn merges_near_duplicates_and_expires_old_comments() {
+    let start = Instant::now();
+    let mut s = scheduler();
+    assert!(s.push(Comment::new("a", "今日のゲームは何ですか", start), start));
+    assert!(!s.push(Comment::new("b", "今日のゲームは何ですか？", start), start));
+    assert!(s.push(Comment::new("c", "おつかれさま", start), start));
+    assert_eq!(s.len(), 2);
+
+    let merged = s.pop_best(start).unwrap();
+    assert_eq!(merged.author.name, "a");
+    assert_eq!(merged.duplicates, 1);
+
+    let later = start + Duration::from_secs(61);
+    assert!(s.pop_best(later).is_none());
+    assert!(s.is_empty());
+}
+
+#[test]
+fn evicts_the_lowest_score_when_full() {
+    let now = Instant::now();
+    let mut s = scheduler();
+    s.push(Comment::new("a", "わこつ", now), now);
+    s.push(Comment::new("b", "ずんだちゃん", now), now);
+    s.push(Comment::new("c", "元気？", now), now);
+    s.push(Comment::new("d", "ずんだは何が好き？", now), now);
+    assert_eq!(s.len(), 3);
+
+    let authors: Vec<_> = std::iter::from_fn(|| s.pop_best(now))
+        .map(|c| c.author.name)
+        .collect();
+    assert_eq!(authors, ["d", "b", "c"]);
+}
+
+#[tokio::test]
+async fn batch_waits_for_the_window_and_takes_the_best_comments() {
+    let queue = CommentQueue::new(SchedulerConfig::default());
+    queue.push(Comment::new("a", "わこつ", Instant::now()));
+    let waiter = tokio::spawn({
+        let queue = queue.clone();
+        async move { queue.next_batch(2, Duration::from_millis(200)).await }
+    });
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    queue.push(Comment::new("b", "今日は何するの？", Instant::now()));
+    queue.push(Comment::new("c", "おつかれさま", Instant::now()));
+
+    let batch = waiter.await.unwrap();
+    let authors: Vec<_> = batch.iter().map(|c| c.author.name.as_str()).collect();
+    // 同点なら新しいコメントを優先する
+    assert_eq!(authors, ["b", "c"]);
+
+    let rest = queue.next_batch(2, Duration::from_millis(200)).await;
+    assert_eq!(rest.len(), 1);
+    assert_eq!(rest[0].author.name, "a");
+}
+
+#[test]
+fn batched_comments_keep_their_authors() {
+    let text = prompt::batch_comments([("a", "こんにちは"), ("b", "元気？")]);
+    assert!(text.contains("\n- aさん: こんにちは\n- bさん: 元気？"));
+}
+
+#[tokio::test]
+async fn queue_wakes_the_waiting_consumer() {
+    let queue = CommentQueue::new(SchedulerConfig::default());
+    let waiter = tokio::spawn({
+        let queue = queue.clone();
+        async move { queue.next().await }
+    });
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    queue.push(Comment::new("a", "こんばんは", Instant::now()));
+
+    let got = tokio::time::timeout(Duration::from_secs(1), waiter)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(got.text, "こんばんは");
+}
+
+#[test]
+fn answers_each_membership_before_plain_chat() {
+    let now = Instant::now();
+    let mut s = scheduler();
+    let welcome = "（メンバーになってくれました。歓迎してください）";
+    s.push(Comment::new("a", "今日は何するの？", now), now);
+    for author in ["b", "c"] {
+        let pushed = s.push(
+            Comment {
+                membership: true,
+                ..Comment::new(author, welcome, now)
+            },
+            now,
+        );
+        assert!(pushed, "{author}'s membership must not be merged");
+    }
+    assert_eq!(s.len(), 3);
+
+    let authors: Vec<_> = std::iter::from_fn(|| s.pop_best(now))
+        .map(|c| (c.author.name, c.duplicates))
+        .collect();
+    assert_eq!(
+        authors,
+        [("b".into(), 0), ("c".into(), 0), ("a".to_owned(), 0)]
+    );
+}
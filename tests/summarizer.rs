@@ -0,0 +1,95 @@
+//! 履歴から外れたターンの要約をモックサーバで検証する。
+
+mod common;
+
+use std::sync::Arc;
+
+use ai_tuber::{
+    model::{
+        conversation::{Conversation, Message},
+        gemini_dto::GenerationConfig,
+        reply,
+    },
+    service::{GeminiClient, summarizer::Summarizer, tools::ToolRegistry},
+};
+use common::{MockServer, Reply};
+use serde_json::{Value, json};
+
+#[tokio::test]
+async fn folds_dropped_turns_into_summary() {
+    let body = r#"{"candidates":[{"content":{"parts":[{"text":"たろうさんとカレーの話をした。"}]},"finishReason":"STOP"}]}"#;
+    let server = MockServer::start(vec![Reply::new(200, body)]).await;
+    let gemini = GeminiClient::with_base_url(&server.url(), "k", "m").unwrap();
+    let summarizer = Summarizer::new(2, 200);
+
+    let mut conv = Conversation::new();
+    conv.push(Message::user("カレー好き？"));
+    assert!(!summarizer.update(&gemini, &mut conv).await);
+    conv.push(Message::assistant("[happy]大好き！"));
+    conv.push(Message::user("辛口派です"));
+    conv.drop_before(2);
+
+    assert!(summarizer.update(&gemini, &mut conv).await);
+    assert_eq!(conv.summary, "たろうさんとカレーの話をした。");
+    assert!(conv.dropped.is_empty());
+    assert_eq!(conv.history.len(), 1);
+
+    let reqs = server.requests();
+    assert_eq!(reqs.len(), 1);
+    let req: Value = serde_json::from_str(&reqs[0].body).unwrap();
+    let turns = req["contents"][0]["parts"][0]["text"].as_str().unwrap();
+    assert!(turns.contains("視聴者: カレー好き？"), "{turns}");
+    assert!(turns.contains("配信者: [happy]大好き！"), "{turns}");
+}
+
+#[tokio::test]
+async fn keeps_pending_turns_when_summary_fails() {
+    let server = MockServer::start(vec![Reply::new(400, "{}")]).await;
+    let gemini = GeminiClient::with_base_url(&server.url(), "k", "m").unwrap();
+    let summarizer = Summarizer::new(1, 200);
+
+    let mut conv = Conversation {
+        summary: "前の要約".into(),
+        ..Conversation::new()
+    };
+    conv.push(Message::user("こんばんは"));
+    conv.drop_before(1);
+
+    assert!(!summarizer.update(&gemini, &mut conv).await);
+    assert_eq!(conv.summary, "前の要約");
+    assert_eq!(conv.dropped.len(), 1);
+}
+
+#[tokio::test]
+async fn summarizes_without_json_mode_or_tools() {
+    let body =
+        r#"{"candidates":[{"content":{"parts":[{"text":"雑談をした。"}]},"finishReason":"STOP"}]}"#;
+    let server = MockServer::start(vec![Reply::new(200, body)]).await;
+    let mut tools = ToolRegistry::new();
+    tools.register("roll_dice", "サイコロ", None, |_| {
+        Ok(json!({ "rolls": [4] }))
+    });
+    let gemini = GeminiClient::with_base_url(&server.url(), "k", "m")
+        .unwrap()
+        .with_generation_config(GenerationConfig {
+            temperature: Some(0.5),
+            response_mime_type: Some("application/json".into()),
+            response_schema: Some(reply::response_schema()),
+            ..GenerationConfig::default()
+        })
+        .with_tools(Arc::new(tools));
+    let summarizer = Summarizer::new(1, 200);
+
+    let mut conv = Conversation::new();
+    conv.push(Message::user("こんばんは"));
+    conv.drop_before(1);
+
+    assert!(summarizer.update(&gemini, &mut conv).await);
+    assert_eq!(conv.summary, "雑談をした。");
+
+    let req: Value = serde_json::from_str(&server.requests()[0].body).unwrap();
+    assert_eq!(req["generationConfig"]["temperature"], 0.5);
+    assert!(req["generationConfig"].get("responseMimeType").is_none());
+    assert!(req["generationConfig"].get("responseSchema").is_none());
+    assert!(req.get("tools").is_none());
+}
@@ -0,0 +1,187 @@
+//! トークン数の概算・キャッシュと、予算に収まる履歴ウィンドウ。
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use ai_tuber::{
+    error::{Error, Result},
+    model::{
+        conversation::{Author, Message, Platform, Role},
+        reply::ReplyFormat,
+    },
+    service::{
+        LlmBackend,
+        knowledge::Passage,
+        llm::BoxFuture,
+        prompt::{self, PromptContext},
+        template::{Template, TemplateVars},
+        tokens::{self, TokenBudget, TokenCounter},
+    },
+};
+use chrono::{TimeZone, Utc};
+
+/// 1 文字 1 トークンで数え、呼ばれた回数を記録する。`"fail"` を含むテキストは失敗させる。
+#[derive(Default)]
+struct CharCounter {
+    calls: AtomicUsize,
+}
+
+impl LlmBackend for CharCounter {
+    fn chat<'a>(&'a self, _: &'a [Message<'a>]) -> BoxFuture<'a, Result<String>> {
+        Box::pin(async { Ok(String::new()) })
+    }
+
+    fn count_tokens<'a>(&'a self, text: &'a str) -> BoxFuture<'a, Result<usize>> {
+        self.calls.fetch_add(1, Ordering::SeqCst);
+        Box::pin(async move {
+            if text.contains("fail") {
+                Err(Error::LlmUnavailable("offline".into()))
+            } else {
+                Ok(text.chars().count())
+            }
+        })
+    }
+}
+
+#[test]
+fn estimates_ascii_by_four_and_others_by_one() {
+    assert_eq!(tokens::estimate(""), 0);
+    assert_eq!(tokens::estimate("abcd"), 1);
+    assert_eq!(tokens::estimate("abcde"), 2);
+    assert_eq!(tokens::estimate("こんにちは"), 5);
+    assert_eq!(tokens::estimate("hi こんにちは🎉"), 1 + 5 + 1);
+}
+
+#[tokio::test]
+async fn counts_each_text_once_and_falls_back_to_estimate() {
+    let llm = CharCounter::default();
+    let mut counter = TokenCounter::new();
+
+    let first = counter.count(&llm, "こんにちは").await;
+    let again = counter.count(&llm, "こんにちは").await;
+    assert_eq!(first, again);
+    assert_eq!(llm.calls.load(Ordering::SeqCst), 1);
+
+    // ロールなどのオーバーヘッドが上乗せされる
+    let overhead = first - 5;
+    assert!(overhead > 0);
+
+    // 失敗したら概算（ASCII 4 文字で 1 トークン）
+    assert_eq!(counter.count(&llm, "fail fail").await, 3 + overhead);
+    assert_eq!(llm.calls.load(Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn window_keeps_newest_messages_within_budget() {
+    let llm = CharCounter::default();
+    let mut counter = TokenCounter::new();
+    let overhead = counter.count(&llm, "").await;
+    let history: Vec<Message> = ["あ".repeat(10), "い".repeat(10), "う".repeat(10)]
+        .into_iter()
+        .map(Message::user)
+        .collect();
+    let per_message = 10 + overhead;
+    let system = [Message::system("ぺるそな")];
+    let fixed = 4 + overhead + 20;
+
+    let budget = |messages: usize| TokenBudget {
+        context_tokens: fixed + per_message * messages,
+        reply_tokens: 20,
+    };
+    assert_eq!(
+        budget(3)
+            .window_start(&mut counter, &llm, &system, &history)
+            .await,
+        0
+    );
+    assert_eq!(
+        budget(2)
+            .window_start(&mut counter, &llm, &system, &history)
+            .await,
+        1
+    );
+    // 予算が足りなくても最新の 1 件は残す
+    let tiny = TokenBudget {
+        context_tokens: 0,
+        reply_tokens: 20,
+    };
+    assert_eq!(
+        tiny.window_start(&mut counter, &llm, &system, &history)
+            .await,
+        2
+    );
+
+    // 同じテキストは数え直さない（空文字 + システム + 3 件）
+    assert_eq!(llm.calls.load(Ordering::SeqCst), 5);
+}
+
+#[tokio::test]
+async fn budgets_the_prompt_that_is_actually_sent() {
+    let llm = CharCounter::default();
+    let mut counter = TokenCounter::new();
+    let at = Utc.with_ymd_and_hms(2026, 1, 2, 3, 4, 5).unwrap();
+    let taro = Author::new("たろう", Platform::YouTube);
+    let history = vec![
+        Message::user("あ".repeat(10))
+            .with_author(taro.clone())
+            .at(at),
+        Message::new(Role::Bot, "い".repeat(10)),
+        Message::user("う".repeat(10)).with_author(taro).at(at),
+    ];
+    let system_prompt = Template::parse("配信「{{stream_title}}」").unwrap();
+    let vars = TemplateVars {
+        stream_title: "た".repeat(50),
+        ..TemplateVars::default()
+    };
+    let passage = Passage {
+        source: "faq.md".into(),
+        text: "え".repeat(50),
+    };
+    let knowledge = [&passage];
+    let ctx = PromptContext {
+        knowledge: &knowledge,
+        vars: Some(&vars),
+        ..PromptContext::default()
+    };
+    let req = prompt::build(&system_prompt, &history, 10, ReplyFormat::Tags, &ctx);
+
+    let mut sizes = Vec::new();
+    for msg in &req {
+        sizes.push(counter.count(&llm, &msg.text).await);
+    }
+    let (system, window) = sizes.split_at(req.len() - history.len());
+    let system: usize = system.iter().sum();
+    // 投稿者と時刻が付くぶん、本文より多く数える
+    assert!(window[2] > 10 + counter.count(&llm, "").await);
+
+    // 最新の 1 件ぶんと少しの余裕。差し込み情報（変数・参考情報）を除いたり
+    // 本文だけで数えたりすると 3 件とも収まってしまう
+    let budget = TokenBudget {
+        context_tokens: system + window[2] + 20 + 5,
+        reply_tokens: 20,
+    };
+    assert_eq!(
+        budget
+            .history_start(&mut counter, &llm, &req, &history)
+            .await,
+        2
+    );
+    let budget = TokenBudget {
+        context_tokens: system + window.iter().sum::<usize>() + 20,
+        reply_tokens: 20,
+    };
+    assert_eq!(
+        budget
+            .history_start(&mut counter, &llm, &req, &history)
+            .await,
+        0
+    );
+
+    // max_history で切られたぶんは送らないので、その先頭から数える
+    let req = prompt::build(&system_prompt, &history, 1, ReplyFormat::Tags, &ctx);
+    assert_eq!(
+        budget
+            .history_start(&mut counter, &llm, &req, &history)
+            .await,
+        1
+    );
+}
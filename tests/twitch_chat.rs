@@ -0,0 +1,138 @@
+use std::time::Duration;
+
+use ai_tuber::{
+    model::{chat_event::ChatEvent, conversation::Platform},
+    service::{
+        ChatSource,
+        twitch_chat::{IrcMessage, TwitchChat},
+    },
+};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    net::TcpListener,
+};
+use tokio_stream::StreamExt;
+
+const PRIVMSG: &str = "@badge-info=subscriber/8;badges=moderator/1,subscriber/6;color=#1E90FF;\
+display-name=Neko\\sChan;id=abc-1;mod=1;tmi-sent-ts=1700000000000;user-id=1234 \
+:nekochan!nekochan@nekochan.tmi.twitch.tv PRIVMSG #zunda :こんにちは！";
+const CHEER: &str = "@badges=;bits=100;display-name=Fan;id=abc-2;user-id=55 \
+:fan!fan@fan.tmi.twitch.tv PRIVMSG #zunda :Cheer100 がんばれ";
+const RESUB: &str = "@badges=subscriber/12;display-name=Regular;login=regular;msg-id=resub;\
+msg-param-sub-plan=2000;user-id=77 :tmi.twitch.tv USERNOTICE #zunda :1年になりました";
+
+#[test]
+fn parses_privmsg_tags_into_badges() {
+    let msg = IrcMessage::parse(PRIVMSG).unwrap();
+    assert_eq!(msg.command, "PRIVMSG");
+    assert_eq!(msg.params, ["#zunda", "こんにちは！"]);
+
+    let Some(ChatEvent::Message(chat)) = msg.to_event() else {
+        panic!("expected a message");
+    };
+    assert_eq!(chat.author.name, "Neko Chan");
+    assert_eq!(chat.author.channel_id.as_deref(), Some("1234"));
+    assert_eq!(chat.author.platform, Platform::Twitch);
+    assert!(chat.badges.moderator && chat.badges.member && !chat.badges.owner);
+    assert_eq!(chat.timestamp.timestamp_millis(), 1_700_000_000_000);
+    assert_eq!(chat.text, "こんにちは！");
+}
+
+#[test]
+fn parses_bits_and_subscriptions() {
+    let cheer = IrcMessage::parse(CHEER).unwrap().to_event().unwrap();
+    let paid = cheer.paid().unwrap();
+    assert_eq!((paid.amount, paid.currency.as_str()), (100.0, "BITS"));
+
+    let Some(ChatEvent::Membership { message, tier }) =
+        IrcMessage::parse(RESUB).unwrap().to_event()
+    else {
+        panic!("expected a membership");
+    };
+    assert_eq!(message.author.name, "Regular");
+    assert_eq!(message.text, "1年になりました");
+    assert_eq!(tier.as_deref(), Some("Tier 2"));
+
+    let join = IrcMessage::parse(":zunda!zunda@zunda.tmi.twitch.tv JOIN #zunda").unwrap();
+    assert!(join.to_event().is_none());
+}
+
+#[tokio::test]
+async fn reads_chat_from_a_local_irc_server() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let server = tokio::spawn(async move {
+        let (conn, _) = listener.accept().await.unwrap();
+        let (read, mut write) = conn.into_split();
+        let mut lines = BufReader::new(read).lines();
+        let mut login = Vec::new();
+        while login.len() < 4 {
+            login.push(lines.next_line().await.unwrap().unwrap());
+        }
+        write.write_all(b"PING :tmi.twitch.tv\r\n").await.unwrap();
+        let pong = lines.next_line().await.unwrap().unwrap();
+        for line in [PRIVMSG, CHEER, RESUB] {
+            write
+                .write_all(format!("{line}\r\n").as_bytes())
+                .await
+                .unwrap();
+        }
+        (login, pong)
+    });
+
+    let source = TwitchChat::new(addr.to_string(), "#Zunda").with_login("ZundaBot", "secret");
+    let stream = source.subscribe().await.unwrap();
+    let events: Vec<_> = tokio::time::timeout(Duration::from_secs(5), stream.take(3).collect())
+        .await
+        .unwrap();
+
+    let kinds: Vec<_> = events.iter().map(ChatEvent::kind).collect();
+    assert_eq!(kinds, ["message", "super_chat", "membership"]);
+
+    let (login, pong) = server.await.unwrap();
+    assert_eq!(
+        login,
+        [
+            "CAP REQ :twitch.tv/tags twitch.tv/commands",
+            "PASS oauth:secret",
+            "NICK zundabot",
+            "JOIN #zunda",
+        ]
+    );
+    assert_eq!(pong, "PONG :tmi.twitch.tv");
+}
+
+#[tokio::test]
+async fn reconnects_when_the_server_asks_to() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let server = tokio::spawn(async move {
+        let mut logins = 0;
+        for line in [PRIVMSG, CHEER] {
+            let (conn, _) = listener.accept().await.unwrap();
+            let (read, mut write) = conn.into_split();
+            let mut lines = BufReader::new(read).lines();
+            while lines.next_line().await.unwrap().unwrap() != "JOIN #zunda" {}
+            logins += 1;
+            write
+                .write_all(format!("{line}\r\n:tmi.twitch.tv RECONNECT\r\n").as_bytes())
+                .await
+                .unwrap();
+        }
+        logins
+    });
+
+    let stream = TwitchChat::new(addr.to_string(), "zunda")
+        .subscribe()
+        .await
+        .unwrap();
+    let events: Vec<_> = tokio::time::timeout(Duration::from_secs(10), stream.take(2).collect())
+        .await
+        .unwrap();
+
+    let kinds: Vec<_> = events.iter().map(ChatEvent::kind).collect();
+    assert_eq!(kinds, ["message", "super_chat"]);
+    assert_eq!(server.await.unwrap(), 2);
+}
@@ -0,0 +1,84 @@
+//! 視聴者の長期記憶が配信をまたいで残ることを検証する。
+
+use std::time::Instant;
+
+use ai_tuber::{
+    model::conversation::{Author, Platform},
+    service::{
+        tools::{ChatLog, ToolRegistry},
+        viewer_memory::ViewerMemory,
+    },
+};
+use serde_json::json;
+
+fn taro(name: &str) -> Author {
+    Author::new(name, Platform::YouTube).with_channel_id("UC-taro")
+}
+
+#[test]
+fn remembers_viewers_across_streams() {
+    let path = std::env::temp_dir().join(format!("viewer_memory_{}.json", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+
+    for stream in 1..=3 {
+        let memory = ViewerMemory::open(&path).unwrap();
+        let rec = memory.record(&taro("たろう"));
+        assert_eq!(rec.stream_count, stream);
+        memory.record(&taro("たろう"));
+        memory.flush().unwrap();
+    }
+
+    let memory = ViewerMemory::open(&path).unwrap();
+    let rec = memory.get("youtube:UC-taro").unwrap();
+    assert_eq!(rec.message_count, 6);
+    assert!(rec.is_regular(3));
+    assert!(rec.describe(3).contains("常連"));
+
+    assert!(memory.remember("youtube:UC-taro", "カレーは辛口派"));
+    assert!(!memory.remember("youtube:UC-taro", "カレーは辛口派"));
+    assert!(!memory.remember("youtube:UC-hana", "初見"));
+    assert_eq!(
+        memory.get("youtube:UC-taro").unwrap().facts,
+        ["カレーは辛口派"]
+    );
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn keys_records_by_channel_id() {
+    let memory = ViewerMemory::in_memory();
+
+    // 改名しても同じ記録、同名の別人は別の記録
+    memory.record(&taro("たろう"));
+    let renamed = memory.record(&taro("タロー"));
+    assert_eq!(renamed.message_count, 2);
+    assert_eq!(renamed.name, "タロー");
+    let other = memory.record(&Author::new("タロー", Platform::Twitch).with_channel_id("42"));
+    assert_eq!(other.message_count, 1);
+    assert!(memory.get("twitch:42").is_some());
+
+    assert!(memory.find("たろう").is_none());
+    assert!(memory.find("タロー").is_some());
+    assert!(memory.find("はなこ").is_none());
+}
+
+#[test]
+fn tool_remembers_facts_by_display_name() {
+    let memory = ViewerMemory::in_memory();
+    memory.record(&taro("たろう"));
+    let tools = ToolRegistry::builtin(Instant::now(), ChatLog::new(), memory.clone());
+
+    let res = tools.call(
+        "remember_viewer_fact",
+        &json!({ "name": "たろう", "fact": "札幌在住" }),
+    );
+    assert_eq!(res["ok"], true);
+    assert_eq!(memory.get("youtube:UC-taro").unwrap().facts, ["札幌在住"]);
+
+    let res = tools.call(
+        "remember_viewer_fact",
+        &json!({ "name": "はなこ", "fact": "初見" }),
+    );
+    assert!(res.get("error").is_some());
+}
@@ -0,0 +1,191 @@
+mod common;
+
+use std::time::Duration;
+
+use ai_tuber::{
+    model::{chat_event::ChatEvent, youtube_dto::LiveChatMessage},
+    service::{
+        ChatSource,
+        chat_source::{self, Lifecycle},
+        youtube_api::{self, YouTubeApi, YouTubeApiConfig},
+    },
+};
+use common::{MockServer, Reply};
+use tokio_stream::StreamExt;
+
+const TOKEN: &str = r#"{"access_token":"ya29.test","expires_in":3599,"token_type":"Bearer"}"#;
+
+fn config(server: &MockServer, live_chat_id: Option<&str>) -> YouTubeApiConfig {
+    YouTubeApiConfig {
+        base_url: server.url(),
+        token_url: format!("{}/token", server.url()),
+        client_id: "client".into(),
+        client_secret: "secret".into(),
+        refresh_token: "refresh-1".into(),
+        live_chat_id: live_chat_id.map(str::to_owned),
+        post_replies: true,
+    }
+}
+
+fn item(id: &str, snippet: &str) -> String {
+    format!(
+        r#"{{"id":"{id}","snippet":{snippet},"authorDetails":{{"channelId":"UC1",
+        "displayName":"たろう","isChatSponsor":true,"isChatModerator":false}}}}"#
+    )
+}
+
+const TEXT: &str = r#"{"type":"textMessageEvent","publishedAt":"2024-05-01T12:00:00Z",
+    "textMessageDetails":{"messageText":"こんにちは"}}"#;
+const SUPER_CHAT: &str = r#"{"type":"superChatEvent","superChatDetails":
+    {"amountMicros":"1000000000","currency":"JPY","amountDisplayString":"￥1,000","userComment":"応援してます"}}"#;
+
+#[test]
+fn converts_live_chat_messages_to_events() {
+    let parse = |json: String| {
+        youtube_api::to_event(serde_json::from_str::<LiveChatMessage>(&json).unwrap())
+    };
+
+    let Some(ChatEvent::Message(msg)) = parse(item("m1", TEXT)) else {
+        panic!("expected a message");
+    };
+    assert_eq!(msg.author.name, "たろう");
+    assert_eq!(msg.author.channel_id.as_deref(), Some("UC1"));
+    assert!(msg.badges.member && !msg.badges.moderator);
+    assert_eq!(msg.text, "こんにちは");
+    assert_eq!(msg.timestamp.to_rfc3339(), "2024-05-01T12:00:00+00:00");
+
+    let paid = parse(item("m2", SUPER_CHAT)).unwrap();
+    assert_eq!(paid.message().text, "応援してます");
+    assert_eq!(paid.paid().unwrap().amount, 1000.0);
+
+    let sponsor =
+        r#"{"type":"newSponsorEvent","newSponsorDetails":{"memberLevelName":"ゴールド"}}"#;
+    let Some(ChatEvent::Membership { tier, .. }) = parse(item("m3", sponsor)) else {
+        panic!("expected a membership");
+    };
+    assert_eq!(tier.as_deref(), Some("ゴールド"));
+
+    let deleted = r#"{"type":"messageDeletedEvent"}"#;
+    assert!(parse(item("m4", deleted)).is_none());
+}
+
+#[tokio::test]
+async fn polls_pages_and_stops_when_chat_goes_offline() {
+    let first = format!(
+        r#"{{"nextPageToken":"p2","pollingIntervalMillis":10,"items":[{}]}}"#,
+        item("old", TEXT)
+    );
+    let second = format!(
+        r#"{{"nextPageToken":"p3","pollingIntervalMillis":10,"offlineAt":"2024-05-01T13:00:00Z",
+        "items":[{},{}]}}"#,
+        item("m1", TEXT),
+        item("m2", SUPER_CHAT)
+    );
+    let server = MockServer::start(vec![
+        Reply::new(200, TOKEN),
+        Reply::new(200, first),
+        Reply::new(200, second),
+    ])
+    .await;
+    let api = YouTubeApi::new(config(&server, Some("chat-1"))).unwrap();
+    let sources: Vec<Box<dyn ChatSource>> = vec![Box::new(api)];
+    let lifecycle = chat_source::lifecycles(&sources);
+    let chat = sources[0].subscribe().await.unwrap();
+
+    // 最初のページ（過去ログ）は流さない
+    let events: Vec<_> = tokio::time::timeout(Duration::from_secs(5), chat.collect())
+        .await
+        .unwrap();
+    let ids: Vec<_> = events.iter().map(|e| e.message().id.as_str()).collect();
+    assert_eq!(ids, ["m1", "m2"]);
+    let states: Vec<_> = lifecycle.take(2).map(|(_, s)| s).collect().await;
+    assert_eq!(states, [Lifecycle::Live, Lifecycle::Ended]);
+
+    let requests = server.requests();
+    assert_eq!(requests[0].path, "/token");
+    assert!(requests[0].body.contains("grant_type=refresh_token"));
+    assert!(requests[0].body.contains("refresh_token=refresh-1"));
+    assert_eq!(
+        requests[1].header("authorization"),
+        Some("Bearer ya29.test")
+    );
+    assert!(requests[1].path.contains("liveChatId=chat-1"));
+    assert!(!requests[1].path.contains("pageToken"));
+    assert!(requests[2].path.contains("pageToken=p2"));
+}
+
+#[tokio::test]
+async fn posts_messages_to_the_active_broadcast_chat() {
+    let server = MockServer::start(vec![
+        Reply::new(200, TOKEN),
+        Reply::new(200, r#"{"items":[{"snippet":{"liveChatId":"chat-9"}}]}"#),
+        Reply::new(200, r#"{"items":[{"id":"UCbot"}]}"#),
+        Reply::new(200, r#"{"id":"posted"}"#),
+        Reply::new(200, r#"{"id":"posted"}"#),
+    ])
+    .await;
+    let api = YouTubeApi::new(config(&server, None)).unwrap();
+
+    api.post("こんにちは！").await.unwrap();
+    api.post(&"あ".repeat(250)).await.unwrap();
+
+    let requests = server.requests();
+    assert_eq!(
+        requests.len(),
+        5,
+        "token, chat id and channel id are reused"
+    );
+    assert!(requests[1].path.starts_with("/liveBroadcasts?"));
+    assert!(requests[1].path.contains("broadcastStatus=active"));
+    assert_eq!(requests[2].path, "/channels?part=id&mine=true");
+
+    let insert = &requests[3];
+    assert_eq!(insert.method, "POST");
+    assert_eq!(insert.path, "/liveChat/messages?part=snippet");
+    let body: serde_json::Value = serde_json::from_str(&insert.body).unwrap();
+    assert_eq!(body["snippet"]["liveChatId"], "chat-9");
+    assert_eq!(body["snippet"]["type"], "textMessageEvent");
+    assert_eq!(
+        body["snippet"]["textMessageDetails"]["messageText"],
+        "こんにちは！"
+    );
+
+    let long: serde_json::Value = serde_json::from_str(&requests[4].body).unwrap();
+    let text = long["snippet"]["textMessageDetails"]["messageText"]
+        .as_str()
+        .unwrap();
+    assert_eq!(text.chars().count(), 200);
+}
+
+#[tokio::test]
+async fn skips_messages_the_bot_posted_itself() {
+    let empty = r#"{"nextPageToken":"p2","pollingIntervalMillis":10,"items":[]}"#;
+    // own-2 は投稿の応答が届く前に取得結果に現れた自分の投稿
+    let second = format!(
+        r#"{{"nextPageToken":"p3","pollingIntervalMillis":10,"offlineAt":"2024-05-01T13:00:00Z",
+        "items":[{},{},{}]}}"#,
+        item("own-1", TEXT).replace("UC1", "UCbot"),
+        item("own-2", TEXT).replace("UC1", "UCbot"),
+        item("m1", TEXT)
+    );
+    let server = MockServer::start(vec![
+        Reply::new(200, TOKEN),
+        Reply::new(200, r#"{"items":[{"id":"UCbot"}]}"#),
+        Reply::new(200, r#"{"id":"own-1"}"#),
+        Reply::new(200, empty),
+        Reply::new(200, second),
+    ])
+    .await;
+    // main と同じく、読み取り側と投稿側は同じクライアントのクローン
+    let api = YouTubeApi::new(config(&server, Some("chat-1"))).unwrap();
+    let source: Box<dyn ChatSource> = Box::new(api.clone());
+    let poster = api;
+    poster.post("こんにちは").await.unwrap();
+
+    let stream = source.subscribe().await.unwrap();
+    let events: Vec<_> = tokio::time::timeout(Duration::from_secs(5), stream.collect())
+        .await
+        .unwrap();
+    let ids: Vec<_> = events.iter().map(|e| e.message().id.as_str()).collect();
+    assert_eq!(ids, ["m1"]);
+}
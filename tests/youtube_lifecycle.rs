@@ -0,0 +1,104 @@
+mod common;
+
+use std::time::Duration;
+
+use ai_tuber::service::{
+    ChatSource,
+    chat_source::{self, Lifecycle},
+    youtube_chat::{self, PageState, YouTubeChat},
+};
+use common::{MockServer, Reply};
+use tokio_stream::StreamExt;
+
+#[test]
+fn reads_broadcast_state_from_watch_page() {
+    let live = r#"{"liveBroadcastDetails":{"isLiveNow":true,"startTimestamp":"2024-01-01"}}"#;
+    let upcoming = r#"{"isUpcoming":true,"liveBroadcastDetails":{"isLiveNow":false}}"#;
+    let archived = r#"{"liveBroadcastDetails":{"isLiveNow":false,"endTimestamp":"x"}}"#;
+    let replay = r#"ytInitialData = {'isReplay': true}"#;
+
+    assert_eq!(youtube_chat::page_state(live), PageState::Live);
+    assert_eq!(youtube_chat::page_state(upcoming), PageState::Upcoming);
+    assert_eq!(youtube_chat::page_state(archived), PageState::Ended);
+    assert_eq!(youtube_chat::page_state(replay), PageState::Ended);
+    assert_eq!(
+        youtube_chat::page_state("<html></html>"),
+        PageState::Offline
+    );
+}
+
+#[test]
+fn reads_channel_name_from_watch_page() {
+    let html = r#"{"ownerChannelName":"ずんだ \"もち\" ch","isLiveNow":true}"#;
+    assert_eq!(
+        youtube_chat::channel_name(html).as_deref(),
+        Some("ずんだ \"もち\" ch")
+    );
+    assert_eq!(youtube_chat::channel_name("<html></html>"), None);
+}
+
+#[tokio::test]
+async fn waits_for_a_scheduled_stream_and_closes_when_it_ends() {
+    let server = MockServer::start(vec![
+        Reply::new(200, r#"{"isUpcoming":true}"#),
+        Reply::new(200, r#"{"isReplay":true}"#),
+    ])
+    .await;
+    let sources: Vec<Box<dyn ChatSource>> = vec![Box::new(YouTubeChat::new(server.url()))];
+    let mut lifecycle = chat_source::lifecycles(&sources);
+    let chat = sources[0].subscribe().await.unwrap();
+
+    let states: Vec<_> =
+        tokio::time::timeout(Duration::from_secs(10), (&mut lifecycle).take(2).collect())
+            .await
+            .unwrap();
+    assert_eq!(
+        states,
+        [
+            ("youtube".to_owned(), Lifecycle::Waiting),
+            ("youtube".to_owned(), Lifecycle::Ended),
+        ]
+    );
+    // 配信が終わるとチャットのストリームも閉じる
+    let rest: Vec<_> = tokio::time::timeout(Duration::from_secs(5), chat.collect())
+        .await
+        .unwrap();
+    assert!(rest.is_empty());
+    assert_eq!(server.requests().len(), 2);
+}
+
+#[tokio::test]
+async fn retries_when_the_chat_client_fails_to_start() {
+    let live = r#"{"liveBroadcastDetails":{"isLiveNow":true}}"#;
+    let page = format!(
+        r#"<link rel="canonical" href="https://www.youtube.com/watch?v=abc123">
+        {live} "INNERTUBE_API_KEY":"key" "clientVersion":"2.20240101" "continuation":"c1""#
+    );
+    let server = MockServer::start(vec![
+        Reply::new(200, live),
+        // チャットの初期化に必要な情報がなく start() が失敗する
+        Reply::new(200, "<html></html>"),
+        Reply::new(200, live),
+        Reply::new(200, page),
+    ])
+    .await;
+    let sources: Vec<Box<dyn ChatSource>> = vec![Box::new(YouTubeChat::new(server.url()))];
+    let mut lifecycle = chat_source::lifecycles(&sources);
+    let _chat = sources[0].subscribe().await.unwrap();
+
+    let started = std::time::Instant::now();
+    let states: Vec<_> =
+        tokio::time::timeout(Duration::from_secs(10), (&mut lifecycle).take(2).collect())
+            .await
+            .unwrap();
+    assert_eq!(
+        states,
+        [
+            ("youtube".to_owned(), Lifecycle::Reconnecting),
+            ("youtube".to_owned(), Lifecycle::Live),
+        ]
+    );
+    // 失敗のあとはバックオフしてから確かめ直す
+    assert!(started.elapsed() >= Duration::from_secs(1));
+    assert_eq!(server.requests().len(), 4);
+}